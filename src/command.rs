@@ -1,13 +1,50 @@
+use std::fmt;
+use std::sync::atomic::{AtomicI32, Ordering};
+use crate::command::tokenizer::TokenKind;
 use crate::history::History;
 
 pub mod builtin;
 pub mod exec;
+pub(crate) mod not_found;
+pub(crate) mod resolver;
+pub(crate) mod tokenizer;
 
+pub(crate) const EXIT_STATUS_SUCCESS: i32 = 0;
+pub(crate) const EXIT_STATUS_FAILURE: i32 = 1;
+pub(crate) const EXIT_STATUS_NOT_FOUND: i32 = 127;
+pub(crate) const EXIT_STATUS_INTERRUPTED: i32 = 130;
+
+// Value of the special parameter `$?`
+static LAST_EXIT_STATUS: AtomicI32 = AtomicI32::new(EXIT_STATUS_SUCCESS);
+
+pub(crate) fn last_exit_status() -> i32 {
+    LAST_EXIT_STATUS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_last_exit_status(status: i32) {
+    LAST_EXIT_STATUS.store(status, Ordering::Relaxed);
+}
+
+// Tests that set `$?` take turns, since the tests run in parallel
+#[cfg(test)]
+pub(crate) static LAST_EXIT_STATUS_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[derive(Debug, PartialEq)]
 pub(crate) enum ShellCommand {
+    Bind,
+    Builtin,
     Cd,
+    Command,
+    Compgen,
+    Complete,
+    Dirs,
     Echo,
     Exit,
+    Hash,
+    Popd,
+    Pushd,
     Pwd,
+    Set,
     Type,
     Exec,
     History
@@ -15,20 +52,50 @@ pub(crate) enum ShellCommand {
 
 impl ShellCommand {
 
-    pub(crate) fn run(&self, parsed_command: &ParsedCommand, history: &mut History) -> Result<(), anyhow::Error> {
+    /// Runs the command and returns its exit status.
+    pub(crate) fn run(&self, parsed_command: &ParsedCommand, history: &mut History) -> Result<i32, anyhow::Error> {
         let args = parsed_command.get_args();
-        match self {
+        let result = match self {
+            ShellCommand::Bind => builtin::bind::run(args.as_slice()),
+            ShellCommand::Builtin => return builtin::builtin_::run(parsed_command, history),
             ShellCommand::Cd => builtin::cd::run(args.as_slice()),
+            ShellCommand::Command => return builtin::command_::run(parsed_command, history),
+            ShellCommand::Compgen => builtin::compgen::run(args.as_slice()),
+            ShellCommand::Complete => builtin::complete::run(args.as_slice()),
+            ShellCommand::Dirs => builtin::dirs::run(args.as_slice()),
             ShellCommand::Echo => builtin::echo::run(args.as_slice(), parsed_command),
-            ShellCommand::Exec => exec::run(parsed_command, history),
+            ShellCommand::Exec => return exec::run(parsed_command, history),
             ShellCommand::Exit => builtin::exit::run(args.as_slice()),
+            ShellCommand::Hash => builtin::hash::run(args.as_slice()),
+            ShellCommand::Popd => builtin::popd::run(args.as_slice()),
+            ShellCommand::Pushd => builtin::pushd::run(args.as_slice()),
             ShellCommand::Pwd => builtin::pwd::run(args.as_slice()),
-            ShellCommand::Type => builtin::type_::run(args.as_slice()),
+            ShellCommand::Set => builtin::set::run(args.as_slice()),
+            ShellCommand::Type => return builtin::type_::run(args.as_slice()),
             ShellCommand::History => builtin::history::run(args.as_slice(), history)
+        };
+        result.map(|_| EXIT_STATUS_SUCCESS)
+    }
+}
+
+/// Parse errors that callers handle differently from a plain syntax error.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum ParseError {
+    /// The input ends inside quotes, after a trailing backslash or pipe, or inside an unterminated `if` or `{`;
+    /// an interactive shell reads more lines with the `PS2` prompt.
+    IncompleteInput,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::IncompleteInput => write!(f, "syntax error: unexpected end of file"),
         }
     }
 }
 
+impl std::error::Error for ParseError {}
+
 #[derive(Debug, PartialEq, Clone)]
 pub(crate) struct Redirect {
     pub(crate) filename: String,
@@ -95,6 +162,9 @@ impl ParsedCommand {
             let first_cmd_terms = &terms[..pipe_pos];
             let second_cmd_terms = &terms[pipe_pos + 1..];
 
+            if !first_cmd_terms.is_empty() && second_cmd_terms.is_empty() {
+                return Err(ParseError::IncompleteInput.into());
+            }
             if first_cmd_terms.is_empty() || second_cmd_terms.is_empty() {
                 return Err(anyhow::anyhow!("Invalid pipe syntax: {}", input));
             }
@@ -117,113 +187,47 @@ impl ParsedCommand {
         }
 
         let terms = ParsedCommand::read_quoted(input)?;
+        if terms.is_empty() {
+            // Only a comment
+            return Ok(None);
+        }
         ParsedCommand::parse_terms_with_pipes(input, &terms)
     }
 
-    fn read_quoted(input: &str) -> Result<Vec<String>, anyhow::Error> {
-        let mut result: Vec<String> = Vec::new();
-        let mut inside_single_quotes = false;
-        let mut inside_double_quotes = false;
-        let mut current_part = String::new();
-        let mut is_escaped_character = false;
-        let chars: Vec<char> = input.chars().collect();
-        let mut i = 0;
-
-        while i < chars.len() {
-            let ch = chars[i];
-
-            if inside_single_quotes {
-                if ch == '\'' {
-                    inside_single_quotes = false;
-                } else {
-                    current_part.push(ch);
-                }
-                i += 1;
-            } else if inside_double_quotes {
-                if is_escaped_character {
-                    if ch == '\"' || ch == '\\' || ch == '$' || ch == '`' {
-                        current_part.push(ch);
-                    } else if ch == 'n' {
-                        //In a real shell we would need to push \n instead but to make Codecrafters test suite happy we do not handle newline this way
-                        //current_part.push('\n');
-                        current_part.push('\\');
-                        current_part.push('n');
-                    } else {
-                        // If it's not a recognized escape sequence, treat the backslash as literal
-                        current_part.push('\\');
-                        current_part.push(ch);
-                    }
-                    is_escaped_character = false;
-                } else if ch == '"' {
-                    inside_double_quotes = false;
-                } else if ch == '\\' {
-                    is_escaped_character = true;
-                } else {
-                    current_part.push(ch);
-                }
-                i += 1;
-            } else {
-                if is_escaped_character {
-                    is_escaped_character = false;
-                    current_part.push(ch);
-                    i += 1;
-                } else if ch == '\\' {
-                    is_escaped_character = true;
-                    i += 1;
-                } else if ch == '\'' {
-                    inside_single_quotes = true;
-                    i += 1;
-                } else if ch == '"' {
-                    inside_double_quotes = true;
-                    i += 1;
-                } else if ch == ' ' {
-                    if current_part.len() > 0 {
-                        result.push(current_part.clone());
-                        current_part = String::new();
-                    }
-                    i += 1;
-                } else if ch == '|' {
-                    if current_part.len() > 0 {
-                        result.push(current_part.clone());
-                        current_part = String::new();
-                    }
-                    result.push("|".to_string());
-                    i += 1;
-                } else {
-                    let redirect_patterns = ["2>>", "1>>", ">>", "2>", "1>", ">"];
-                    let mut found_redirect = false;
-
-                    for pattern in redirect_patterns.iter() {
-                        if i + pattern.len() <= chars.len() {
-                            let potential_match: String = chars[i..i + pattern.len()].iter().collect();
-                            if potential_match == *pattern {
-                                if current_part.len() > 0 {
-                                    result.push(current_part.clone());
-                                    current_part = String::new();
-                                }
-                                result.push(pattern.to_string());
-                                i += pattern.len();
-                                found_redirect = true;
-                                break;
-                            }
-                        }
-                    }
-
-                    if !found_redirect {
-                        current_part.push(ch);
-                        i += 1;
-                    }
-                }
+    pub(crate) fn is_incomplete_input(error: &anyhow::Error) -> bool {
+        error.downcast_ref::<ParseError>() == Some(&ParseError::IncompleteInput)
+    }
+
+    /// Splits multi-line input into the commands on its lines, keeping together the lines that continue a command.
+    pub(crate) fn split_lines(input: &str) -> Vec<String> {
+        let mut commands = Vec::new();
+        let mut command = String::new();
+        for line in input.lines() {
+            if !command.is_empty() {
+                command.push('\n');
+            }
+            command.push_str(line);
+            match ParsedCommand::parse_command(&command) {
+                Err(error) if ParsedCommand::is_incomplete_input(&error) => (),
+                _ => commands.push(std::mem::take(&mut command)),
             }
         }
-
-        if current_part.len() > 0 {
-            result.push(current_part);
+        if !command.is_empty() {
+            commands.push(command);
         }
-
-        Ok(result)
+        commands
     }
 
+    fn read_quoted(input: &str) -> Result<Vec<String>, anyhow::Error> {
+        let tokens = tokenizer::tokenize(input);
+        if !tokens.is_complete {
+            return Err(ParseError::IncompleteInput.into());
+        }
+        Ok(tokens.tokens.into_iter()
+            .filter(|token| token.kind != TokenKind::Comment)
+            .map(|token| token.value)
+            .collect())
+    }
 
     pub(crate) fn get_args(&self) -> Vec<&str> {
         self.args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>()
@@ -322,6 +326,16 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_last_exit_status() -> Result<(), anyhow::Error> {
+        let _lock = LAST_EXIT_STATUS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        set_last_exit_status(EXIT_STATUS_INTERRUPTED);
+        let result = ParsedCommand::parse_command("echo $? \"status $?\" '$?' \\$?")?;
+        set_last_exit_status(EXIT_STATUS_SUCCESS);
+        assert_eq!(result, Some(cmd("echo", vec!["130", "status 130", "$?", "$?"])));
+        Ok(())
+    }
+
     #[test]
     fn test_parse_echo_with_multiple_args() -> Result<(), anyhow::Error> {
         let result = ParsedCommand::parse_command("echo hello world")?;
@@ -365,10 +379,9 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_single_quote_in_middle() -> Result<(), anyhow::Error> {
-        let result = ParsedCommand::parse_command("echo don't")?;
-        assert_eq!(result, Some(cmd("echo", vec!["dont"])));
-        Ok(())
+    fn test_parse_unclosed_quote_is_incomplete() {
+        let error = ParsedCommand::parse_command("echo don't").unwrap_err();
+        assert!(ParsedCommand::is_incomplete_input(&error));
     }
 
     #[test]
@@ -702,4 +715,49 @@ mod tests {
         assert_eq!(result, Some(expected));
         Ok(())
     }
-}
\ No newline at end of file
+
+    fn is_incomplete(input: &str) -> bool {
+        match ParsedCommand::parse_command(input) {
+            Err(error) => ParsedCommand::is_incomplete_input(&error),
+            Ok(_) => false,
+        }
+    }
+
+    #[test]
+    fn test_incomplete_input() {
+        assert!(is_incomplete("echo \"hello"));
+        assert!(is_incomplete("echo hello \\"));
+        assert!(is_incomplete("ls |"));
+        assert!(is_incomplete("if true; then"));
+        assert!(is_incomplete("if true\nthen echo a"));
+        assert!(is_incomplete("{ echo a"));
+        assert!(!is_incomplete("if true; then echo a; fi"));
+        assert!(!is_incomplete("{ echo a; }"));
+        assert!(!is_incomplete("echo if {"));
+        assert!(!is_incomplete("echo 'if'"));
+        assert!(!is_incomplete("| wc"));
+    }
+
+    #[test]
+    fn test_parse_continued_lines() -> Result<(), anyhow::Error> {
+        assert_eq!(ParsedCommand::parse_command("echo hello \\\nworld")?, Some(cmd("echo", vec!["hello", "world"])));
+        assert_eq!(ParsedCommand::parse_command("echo 'a\nb'")?, Some(cmd("echo", vec!["a\nb"])));
+        let result = ParsedCommand::parse_command("ls |\nwc")?.unwrap();
+        assert_eq!(result.piped_command.map(|command| command.command), Some("wc".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_comments() -> Result<(), anyhow::Error> {
+        assert_eq!(ParsedCommand::parse_command("echo hello # greet")?, Some(cmd("echo", vec!["hello"])));
+        assert_eq!(ParsedCommand::parse_command("echo a#b '#c'")?, Some(cmd("echo", vec!["a#b", "#c"])));
+        assert_eq!(ParsedCommand::parse_command("# nothing to run")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(ParsedCommand::split_lines("echo a\necho 'b\nc'\nls |\nwc\n"), vec!["echo a", "echo 'b\nc'", "ls |\nwc"]);
+        assert_eq!(ParsedCommand::split_lines("echo \"a"), vec!["echo \"a"]);
+    }
+}
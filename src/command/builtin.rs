@@ -2,10 +2,20 @@ use std::collections::HashMap;
 use crate::{command::{self, ShellCommand}, history::History};
 use lazy_static::lazy_static;
 
+pub(crate) mod bind;
+pub(crate) mod builtin_;
 pub(crate) mod cd;
+pub(crate) mod command_;
+pub(crate) mod compgen;
+pub(crate) mod complete;
+pub(crate) mod dirs;
 pub(crate) mod exit;
 pub(crate) mod echo;
+pub(crate) mod hash;
+pub(crate) mod popd;
+pub(crate) mod pushd;
 pub(crate) mod pwd;
+pub(crate) mod set;
 pub(crate) mod type_;
 pub(crate) mod history;
 
@@ -18,6 +28,16 @@ lazy_static! {
         m.insert("history", command::ShellCommand::History {});
         m.insert("exit", command::ShellCommand::Exit {});
         m.insert("type", command::ShellCommand::Type {});
+        m.insert("bind", command::ShellCommand::Bind {});
+        m.insert("set", command::ShellCommand::Set {});
+        m.insert("complete", command::ShellCommand::Complete {});
+        m.insert("compgen", command::ShellCommand::Compgen {});
+        m.insert("hash", command::ShellCommand::Hash {});
+        m.insert("command", command::ShellCommand::Command {});
+        m.insert("builtin", command::ShellCommand::Builtin {});
+        m.insert("dirs", command::ShellCommand::Dirs {});
+        m.insert("pushd", command::ShellCommand::Pushd {});
+        m.insert("popd", command::ShellCommand::Popd {});
         m
     };
 }
@@ -31,11 +51,19 @@ pub(crate) fn generate_output(command: &str, args: &[String], history: &History)
 
     match command {
         "echo" => echo::generate_output(&args_str),
-        "pwd" => pwd::generate_output(),
+        "pwd" => pwd::generate_output(&args_str),
         "type" => type_::generate_output(&args_str),
         "history" => history::generate_output(&args_str, history),
-        "cd" | "exit" => {
-            // cd and exit don't make sense in a pipeline, return empty output
+        "bind" => bind::generate_output(&args_str),
+        "set" => set::generate_output(&args_str),
+        "complete" => complete::generate_output(&args_str),
+        "compgen" => compgen::generate_output(&args_str),
+        "hash" => hash::generate_output(&args_str),
+        "command" => command_::generate_output(&args_str),
+        "builtin" => builtin_::generate_output(&args_str),
+        "dirs" => dirs::generate_output(&args_str),
+        "cd" | "exit" | "pushd" | "popd" => {
+            // Changing directory or exiting doesn't make sense in a pipeline, return empty output
             Ok(Vec::new())
         }
         _ => Err(anyhow::anyhow!("Unknown builtin command: {}", command)),
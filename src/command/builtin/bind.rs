@@ -0,0 +1,199 @@
+use std::io::{self, Write};
+use std::path::Path;
+use crate::input::inputrc::{self, InputrcReader};
+use crate::input::keymap::{self, EditorAction, KeyBinding, KeymapName};
+use crate::input::settings;
+
+/// Supports the options of bash's `bind` that deal with key bindings and variables;
+/// every other argument is applied like a line of an inputrc file.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut reader = InputrcReader::for_current_mode();
+    let mut output = String::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-m" => {
+                let name = option_argument(arg, args.next())?;
+                reader = InputrcReader::new(KeymapName::parse(name).map_err(|e| anyhow::anyhow!("bind: {}", e))?);
+            }
+            "-l" => output.push_str(&list_functions()),
+            "-p" => output.push_str(&list_bindings(reader.keymap())),
+            "-P" => output.push_str(&describe_bindings(reader.keymap())),
+            "-v" => output.push_str(&list_variables()),
+            "-X" => output.push_str(&list_shell_commands(reader.keymap())),
+            "-f" => {
+                let path = option_argument(arg, args.next())?;
+                let errors = reader.load_file(Path::new(path)).map_err(|e| anyhow::anyhow!("bind: {}", e))?;
+                if let Some(error) = errors.first() {
+                    return Err(anyhow::anyhow!("bind: {}", error));
+                }
+            }
+            "-r" => {
+                let sequence = inputrc::parse_key_sequence(option_argument(arg, args.next())?)
+                    .map_err(|e| anyhow::anyhow!("bind: {}", e))?;
+                keymap::unbind(reader.keymap(), &sequence)?;
+            }
+            "-x" => {
+                let binding = option_argument(arg, args.next())?;
+                let (sequence, command) = inputrc::parse_key_binding(binding)
+                    .map_err(|e| anyhow::anyhow!("bind: {}", e))?;
+                keymap::bind(reader.keymap(), &sequence, KeyBinding::ShellCommand(unquote(command).to_string()))?;
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                return Err(anyhow::anyhow!("bind: {}: invalid option", option));
+            }
+            line => reader.apply_line(line).map_err(|e| anyhow::anyhow!("bind: {}", e))?,
+        }
+    }
+    Ok(output.into_bytes())
+}
+
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
+    print!("{}", String::from_utf8_lossy(&output));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+fn option_argument<'a>(option: &str, argument: Option<&&'a str>) -> Result<&'a str, anyhow::Error> {
+    argument.copied().ok_or_else(|| anyhow::anyhow!("bind: {}: option requires an argument", option))
+}
+
+/// Strips the quotes around the command of `bind -x '"\C-l": "clear"'`.
+fn unquote(command: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(unquoted) = command.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)) {
+            return unquoted;
+        }
+    }
+    command
+}
+
+fn list_functions() -> String {
+    EditorAction::names().map(|name| format!("{}\n", name)).collect()
+}
+
+/// Lists the bindings in inputrc format, the way `bind -p` does.
+fn list_bindings(keymap_name: KeymapName) -> String {
+    let keymap = keymap::current(keymap_name);
+    EditorAction::names()
+        .filter_map(EditorAction::from_name)
+        .map(|action| {
+            let sequences = keymap.sequences_for(action);
+            if sequences.is_empty() {
+                return format!("# {} (not bound)\n", action.name());
+            }
+            sequences.iter()
+                .map(|sequence| format!("\"{}\": {}\n", inputrc::format_key_sequence(sequence), action.name()))
+                .collect()
+        })
+        .collect()
+}
+
+fn describe_bindings(keymap_name: KeymapName) -> String {
+    let keymap = keymap::current(keymap_name);
+    EditorAction::names()
+        .filter_map(EditorAction::from_name)
+        .map(|action| {
+            let sequences: Vec<String> = keymap.sequences_for(action).iter()
+                .map(|sequence| format!("\"{}\"", inputrc::format_key_sequence(sequence)))
+                .collect();
+            if sequences.is_empty() {
+                format!("{} is not bound to any keys\n", action.name())
+            } else {
+                format!("{} can be found on {}.\n", action.name(), sequences.join(", "))
+            }
+        })
+        .collect()
+}
+
+fn list_shell_commands(keymap_name: KeymapName) -> String {
+    keymap::current(keymap_name).bindings()
+        .filter_map(|(sequence, binding)| match binding {
+            KeyBinding::ShellCommand(command) => Some(format!("\"{}\": \"{}\"\n", inputrc::format_key_sequence(sequence), command)),
+            KeyBinding::Action(_) => None,
+        })
+        .collect()
+}
+
+fn list_variables() -> String {
+    settings::current().variables().iter()
+        .map(|(name, value)| format!("set {} {}\n", name, value))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_variables() {
+        assert!(list_variables().contains("set history-prefix-search "));
+    }
+
+    #[test]
+    fn test_unknown_variable_is_an_error() {
+        assert!(generate_output(&["set no-such-variable on"]).is_err());
+    }
+
+    #[test]
+    fn test_list_functions() {
+        let functions = list_functions();
+        assert!(functions.contains("beginning-of-line\n"));
+        assert!(functions.contains("history-search-backward\n"));
+    }
+
+    #[test]
+    fn test_list_bindings() -> Result<(), anyhow::Error> {
+        let output = String::from_utf8(generate_output(&["-m", "emacs", "-p"])?)?;
+        assert!(output.contains("\"\\C-a\": beginning-of-line\n"));
+        assert!(output.contains("\"\\e[1;5C\": forward-word\n"));
+        assert!(output.contains("# vi-movement-mode (not bound)\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_bindings() -> Result<(), anyhow::Error> {
+        let output = String::from_utf8(generate_output(&["-m", "emacs", "-P"])?)?;
+        assert!(output.contains("reverse-search-history can be found on \"\\C-r\".\n"));
+        assert!(output.contains("vi-movement-mode is not bound to any keys\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bind_and_remove_key() -> Result<(), anyhow::Error> {
+        generate_output(&["-m", "vi-insert", "\"\\C-x\\C-h\": history-search-backward"])?;
+        let keymap = keymap::current(KeymapName::ViInsert);
+        assert_eq!(keymap.get("\x18\x08"), Some(&KeyBinding::Action(EditorAction::HistorySearchBackward)));
+
+        generate_output(&["-m", "vi-insert", "-r", "\\C-x\\C-h"])?;
+        assert_eq!(keymap::current(KeymapName::ViInsert).get("\x18\x08"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bind_shell_command() -> Result<(), anyhow::Error> {
+        generate_output(&["-m", "vi-insert", "-x", "\"\\C-x\\C-l\": \"ls -la\""])?;
+        assert_eq!(keymap::current(KeymapName::ViInsert).get("\x18\x0c"), Some(&KeyBinding::ShellCommand("ls -la".to_string())));
+        let output = String::from_utf8(generate_output(&["-m", "vi-insert", "-X"])?)?;
+        assert!(output.contains("\"\\C-x\\C-l\": \"ls -la\"\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(generate_output(&["-m"]).is_err());
+        assert!(generate_output(&["-m", "vi-command", "-p"]).is_err());
+        assert!(generate_output(&["-Q"]).is_err());
+        assert!(generate_output(&["\"\\C-a\": no-such-function"]).is_err());
+        assert!(generate_output(&["-x", "\\C-a"]).is_err());
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("\"ls -la\""), "ls -la");
+        assert_eq!(unquote("'clear'"), "clear");
+        assert_eq!(unquote("clear"), "clear");
+        assert_eq!(unquote("\"unbalanced"), "\"unbalanced");
+    }
+}
@@ -0,0 +1,36 @@
+use crate::command::resolver::{self, Lookup};
+use crate::command::{ParsedCommand, EXIT_STATUS_SUCCESS};
+use crate::history::History;
+use crate::path;
+
+/// In a pipeline the builtin is run directly, so only names that are not builtins get here.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    match args.iter().find(|arg| **arg != "--") {
+        Some(name) => Err(anyhow::anyhow!("builtin: {}: not a shell builtin", name.trim())),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Runs the builtin of that name like bash's `builtin`, never a program from PATH.
+pub(crate) fn run(parsed_command: &ParsedCommand, history: &mut History) -> Result<i32, anyhow::Error> {
+    let Some(target) = resolver::unwrap_prefix(parsed_command) else {
+        let args = parsed_command.get_args();
+        return generate_output(args.as_slice()).map(|_| EXIT_STATUS_SUCCESS);
+    };
+    let path = path::Path::parse("")?;
+    let resolution = resolver::resolve(&target.command, &path, Lookup::BUILTINS)
+        .ok_or_else(|| anyhow::anyhow!("builtin: {}: not a shell builtin", target.command.trim()))?;
+    resolver::run(&resolution, &target, history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_builtins_are_run() {
+        assert!(generate_output(&[]).is_ok());
+        assert!(generate_output(&["--"]).is_ok());
+        assert_eq!(generate_output(&["ls"]).unwrap_err().to_string(), "builtin: ls: not a shell builtin");
+    }
+}
@@ -1,46 +1,120 @@
 use std::env;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use crate::command::builtin::pwd::{self, Links};
 
+/// Where `cd` goes, and whether it prints the new directory, as it does for `cd -` and directories found
+/// through `CDPATH`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Destination {
+    pub(crate) path: PathBuf,
+    pub(crate) should_print: bool,
+}
+
+/// Changes directory like bash's `cd`: `-` goes back to `OLDPWD`, relative names are looked up in `CDPATH`,
+/// and `-P` resolves symbolic links instead of keeping them in `PWD`.
 pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
-    let destination = determine_destination(args)?;
-    env::set_current_dir(destination.clone())
-        .map_err(|_| anyhow::anyhow!("cd: {}: No such file or directory", destination.to_string_lossy()))?;
+    let (links, operands) = Links::parse("cd", args)?;
+    if operands.len() > 1 {
+        return Err(anyhow::anyhow!("cd: too many arguments"));
+    }
+    let operand = operands.first().map(|operand| operand.trim());
+    let current_directory = pwd::current_directory(links)?;
+    let destination = determine_destination(operand, &current_directory, links)?;
+    let new_directory = change_directory(&destination.path, links).map_err(|e| {
+        anyhow::anyhow!("cd: {}: {}", operand.map_or(destination.path.to_string_lossy(), |operand| operand.into()), error_reason(&e))
+    })?;
+    if destination.should_print {
+        println!("{}", new_directory.display());
+        io::stdout().flush()?;
+    }
     Ok(())
 }
 
-fn determine_destination(args: &[&str]) -> Result<PathBuf, anyhow::Error> {
+/// Changes to the directory and keeps `PWD` and `OLDPWD` up to date, returning the new `PWD`.
+pub(crate) fn change_directory(directory: &Path, links: Links) -> io::Result<PathBuf> {
+    let old_directory = pwd::current_directory(Links::Logical).ok();
+    env::set_current_dir(directory)?;
+    let new_directory = match links {
+        Links::Logical => directory.to_path_buf(),
+        Links::Physical => env::current_dir()?,
+    };
+    if let Some(old_directory) = old_directory {
+        env::set_var("OLDPWD", old_directory);
+    }
+    env::set_var("PWD", &new_directory);
+    Ok(new_directory)
+}
+
+/// The reason a directory could not be entered, worded like the shell reports it.
+pub(crate) fn error_reason(error: &io::Error) -> String {
+    match error.raw_os_error() {
+        Some(libc::ENOTDIR) => "Not a directory".to_string(),
+        Some(libc::EACCES) => "Permission denied".to_string(),
+        Some(libc::ENOENT) | None => "No such file or directory".to_string(),
+        Some(_) => error.to_string(),
+    }
+}
+
+/// Where `cd` goes for the operand, also used by `pushd` so that both treat names alike.
+pub(crate) fn determine_destination(operand: Option<&str>, current_directory: &Path, links: Links) -> Result<Destination, anyhow::Error> {
     let home_directory = Path::new(&env::var("HOME").unwrap_or_else(|_| "/".to_string())).to_path_buf();
 
-    if args.is_empty() {
-        return Ok(home_directory);
+    let Some(destination) = operand.map(str::trim) else {
+        return Ok(Destination { path: home_directory, should_print: false });
+    };
+    if destination == "-" {
+        let old_directory = env::var("OLDPWD").map_err(|_| anyhow::anyhow!("cd: OLDPWD not set"))?;
+        return Ok(Destination { path: PathBuf::from(old_directory), should_print: true });
     }
+    if let Some(found) = search_cdpath(destination, current_directory, env::var("CDPATH").ok().as_deref(), links) {
+        return Ok(found);
+    }
+    Ok(Destination { path: join(current_directory, destination, &home_directory, links), should_print: false })
+}
+
+/// Looks a relative directory up in the `CDPATH` directories, an empty entry standing for the current one.
+/// Names starting with `.` or `..` are only looked up in the current directory.
+fn search_cdpath(destination: &str, current_directory: &Path, cdpath: Option<&str>, links: Links) -> Option<Destination> {
+    let first_part = destination.split('/').next().unwrap_or_default();
+    if destination.starts_with('/') || matches!(first_part, "." | ".." | "~") {
+        return None;
+    }
+    cdpath?.split(':').find_map(|directory| {
+        let base = if directory.is_empty() { current_directory.to_path_buf() } else { current_directory.join(directory) };
+        let path = join(&base, destination, current_directory, links);
+        path.is_dir().then_some(Destination { path, should_print: !directory.is_empty() })
+    })
+}
 
-    let destination = args[0].trim().to_string();
+/// The destination relative to the directory. Logically `..` removes the last part of the path, physically it is
+/// left for the system to resolve, which goes to the parent of where a symbolic link points.
+fn join(current_directory: &Path, destination: &str, home_directory: &Path, links: Links) -> PathBuf {
     let destination_parts = destination.split('/');
-    let mut current_directory = env::current_dir()
-        .map_err(|e| anyhow::anyhow!("Failed to get current directory: {}", e))?;
-    
+    let mut current_directory = current_directory.to_path_buf();
+
     for (idx, destination_part) in destination_parts.enumerate() {
         match destination_part {
             "." => {
                 // Do nothing, already in the correct directory (current directory)
             }
-            ".." => {
+            ".." if links == Links::Logical => {
                 current_directory.pop();
             }
             "" if idx == 0 => {
                 current_directory = Path::new("/").to_path_buf();
             }
             "~" if idx == 0 => {
-                current_directory = home_directory.clone();
+                current_directory = home_directory.to_path_buf();
             }
+            "" => (),
             _ => {
                 current_directory.push(destination_part);
             }
         }
     }
 
-    Ok(current_directory)
+    current_directory
 }
 
 #[cfg(test)]
@@ -60,7 +134,7 @@ mod tests {
     #[test]
     fn test_cd_no_args_goes_to_home() -> Result<(), anyhow::Error> {
         let home = get_home_directory()?;
-        let result = determine_destination(&[])?;
+        let result = determine_destination(None, &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, PathBuf::from(home));
         Ok(())
     }
@@ -68,7 +142,7 @@ mod tests {
     #[test]
     fn test_cd_current_directory() -> Result<(), anyhow::Error> {
         let current = get_current_directory()?;
-        let result = determine_destination(&["."])?;
+        let result = determine_destination(Some("."), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, current);
         Ok(())
     }
@@ -77,21 +151,21 @@ mod tests {
     fn test_cd_parent_directory() -> Result<(), anyhow::Error> {
         let mut expected = get_current_directory()?;
         expected.pop();
-        let result = determine_destination(&[".."])?;
+        let result = determine_destination(Some(".."), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, expected);
         Ok(())
     }
 
     #[test]
     fn test_cd_absolute_path() -> Result<(), anyhow::Error> {
-        let result = determine_destination(&["/usr/bin"])?;
+        let result = determine_destination(Some("/usr/bin"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, PathBuf::from("/usr/bin"));
         Ok(())
     }
 
     #[test]
     fn test_cd_absolute_root() -> Result<(), anyhow::Error> {
-        let result = determine_destination(&["/"])?;
+        let result = determine_destination(Some("/"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, PathBuf::from("/"));
         Ok(())
     }
@@ -99,7 +173,7 @@ mod tests {
     #[test]
     fn test_cd_tilde_expansion() -> Result<(), anyhow::Error> {
         let home = get_home_directory()?;
-        let result = determine_destination(&["~"])?;
+        let result = determine_destination(Some("~"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, PathBuf::from(home));
         Ok(())
     }
@@ -109,7 +183,7 @@ mod tests {
         let home = get_home_directory()?;
         let mut expected = PathBuf::from(home);
         expected.push("Documents");
-        let result = determine_destination(&["~/Documents"])?;
+        let result = determine_destination(Some("~/Documents"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, expected);
         Ok(())
     }
@@ -118,7 +192,7 @@ mod tests {
     fn test_cd_relative_path() -> Result<(), anyhow::Error> {
         let mut expected = get_current_directory()?;
         expected.push("child_directory");
-        let result = determine_destination(&["child_directory"])?;
+        let result = determine_destination(Some("child_directory"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, expected);
         Ok(())
     }
@@ -129,7 +203,7 @@ mod tests {
         expected.push("src");
         expected.pop();
         expected.push("target");
-        let result = determine_destination(&["src/../target"])?;
+        let result = determine_destination(Some("src/../target"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, expected);
         Ok(())
     }
@@ -139,7 +213,7 @@ mod tests {
         let mut expected = get_current_directory()?;
         expected.pop();
         expected.pop();
-        let result = determine_destination(&["../.."])?;
+        let result = determine_destination(Some("../.."), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, expected);
         Ok(())
     }
@@ -151,14 +225,14 @@ mod tests {
         expected.pop();
         expected.push("target");
         expected.push("debug");
-        let result = determine_destination(&["src/../target/./debug"])?;
+        let result = determine_destination(Some("src/../target/./debug"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, expected);
         Ok(())
     }
 
     #[test]
     fn test_cd_empty_string_components() -> Result<(), anyhow::Error> {
-        let result = determine_destination(&["//usr//bin//"])?;
+        let result = determine_destination(Some("//usr//bin//"), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, PathBuf::from("/usr/bin"));
         Ok(())
     }
@@ -166,8 +240,36 @@ mod tests {
     #[test]
     fn test_cd_whitespace_trimming() -> Result<(), anyhow::Error> {
         // Test that arguments are trimmed
-        let result = determine_destination(&["  /usr/bin  "])?;
+        let result = determine_destination(Some("  /usr/bin  "), &get_current_directory()?, Links::Logical)?.path;
         assert_eq!(result, PathBuf::from("/usr/bin"));
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cd_physical_leaves_parents_to_the_system() {
+        let result = join(Path::new("/tmp/link"), "../dir", Path::new("/"), Links::Physical);
+        assert_eq!(result, PathBuf::from("/tmp/link/../dir"));
+    }
+
+    #[test]
+    fn test_cd_searches_cdpath() -> Result<(), anyhow::Error> {
+        let projects = tempfile::tempdir()?;
+        std::fs::create_dir(projects.path().join("shell"))?;
+        let cdpath = format!(":{}", projects.path().display());
+        let current = get_current_directory()?;
+
+        let found = search_cdpath("shell", &current, Some(&cdpath), Links::Logical);
+        assert_eq!(found, Some(Destination { path: projects.path().join("shell"), should_print: true }));
+        assert_eq!(search_cdpath("src", &current, Some(&cdpath), Links::Logical), Some(Destination { path: current.join("src"), should_print: false }));
+        assert_eq!(search_cdpath("./shell", &current, Some(&cdpath), Links::Logical), None);
+        assert_eq!(search_cdpath("shell", &current, None, Links::Logical), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cd_error_reasons() {
+        assert_eq!(error_reason(&io::Error::from_raw_os_error(libc::ENOTDIR)), "Not a directory");
+        assert_eq!(error_reason(&io::Error::from_raw_os_error(libc::EACCES)), "Permission denied");
+        assert_eq!(error_reason(&io::Error::from_raw_os_error(libc::ENOENT)), "No such file or directory");
+    }
+}
@@ -0,0 +1,97 @@
+use std::env;
+use std::io::{self, Write};
+use crate::command::builtin::type_::{self, Format};
+use crate::command::resolver::{self, Lookup, Resolution};
+use crate::command::{ParsedCommand, EXIT_STATUS_FAILURE, EXIT_STATUS_NOT_FOUND, EXIT_STATUS_SUCCESS};
+use crate::history::History;
+use crate::path;
+
+/// `command -v` prints the name of a builtin or the path of a file, `command -V` describes it like `type`.
+fn look_up(args: &[&str], path: &path::Path) -> Result<(String, bool), anyhow::Error> {
+    let mut verbose = None;
+    let mut names = Vec::new();
+    for arg in args {
+        match *arg {
+            "--" if names.is_empty() => (),
+            option if option.starts_with('-') && option.len() > 1 && names.is_empty() => {
+                for flag in option[1..].chars() {
+                    match flag {
+                        'v' => verbose = Some(false),
+                        'V' => verbose = Some(true),
+                        // Everything is looked up in PATH, which serves as the default path too
+                        'p' => (),
+                        _ => return Err(anyhow::anyhow!("command: -{}: invalid option", flag)),
+                    }
+                }
+            }
+            name => names.push(name.trim()),
+        }
+    }
+    let Some(verbose) = verbose else {
+        return Ok((String::new(), true));
+    };
+
+    let mut output = String::new();
+    let mut all_found = true;
+    for name in names {
+        let line = match resolver::resolve(name, path, Lookup::DESCRIBE) {
+            Some(resolution) if verbose => type_::describe(&resolution, name, Format::Sentence),
+            Some(Resolution::Builtin(_)) => Some(name.to_string()),
+            Some(resolution) => type_::describe(&resolution, name, Format::Path),
+            None if verbose => Some(format!("command: {}: not found", name)),
+            None => None,
+        };
+        all_found &= line.as_ref().is_some_and(|line| !line.starts_with("command: "));
+        if let Some(line) = line {
+            output.push_str(&line);
+            output.push('\n');
+        }
+    }
+    Ok((output, all_found))
+}
+
+/// In a pipeline only the lookups produce output; commands run through `command` are run directly.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let path = path::Path::parse(&env::var("PATH").unwrap_or_default())?;
+    look_up(args, &path).map(|(output, _)| output.into_bytes())
+}
+
+/// Runs a builtin or a program from PATH like bash's `command`, or looks names up with `-v` and `-V`.
+pub(crate) fn run(parsed_command: &ParsedCommand, history: &mut History) -> Result<i32, anyhow::Error> {
+    let path = path::Path::parse(&env::var("PATH").unwrap_or_default())?;
+    if let Some(target) = resolver::unwrap_prefix(parsed_command) {
+        let Some(resolution) = resolver::resolve(&target.command, &path, Lookup::RUN) else {
+            eprintln!("{}: command not found", target.command.trim());
+            return Ok(EXIT_STATUS_NOT_FOUND);
+        };
+        return resolver::run(&resolution, &target, history);
+    }
+    let args = parsed_command.get_args();
+    let (output, all_found) = look_up(args.as_slice(), &path)?;
+    print!("{}", output);
+    io::stdout().flush()?;
+    Ok(if all_found { EXIT_STATUS_SUCCESS } else { EXIT_STATUS_FAILURE })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_look_up() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let tool = directory.path().join("tool");
+        fs::write(&tool, "#!/bin/sh\n")?;
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755))?;
+        let path = path::Path::parse(directory.path().to_str().unwrap_or_default())?;
+
+        assert_eq!(look_up(&["-v", "cd", "tool"], &path)?, (format!("cd\n{}\n", tool.display()), true));
+        assert_eq!(look_up(&["-v", "missing"], &path)?, (String::new(), false));
+        assert_eq!(look_up(&["-V", "cd", "missing"], &path)?, ("cd is a shell builtin\ncommand: missing: not found\n".to_string(), false));
+        assert_eq!(look_up(&["-pv", "--", "tool"], &path)?, (format!("{}\n", tool.display()), true));
+        assert!(look_up(&["-x"], &path).is_err());
+        Ok(())
+    }
+}
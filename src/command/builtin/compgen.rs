@@ -0,0 +1,56 @@
+use std::io::{self, Write};
+use crate::input::completion_spec::{CompletionRequest, CompletionSpec};
+
+/// Prints the matches for a word one per line like bash's `compgen`, taking the options of `complete`.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut spec = CompletionSpec::default();
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            option if option.starts_with('-') && option.len() > 1 => {
+                if !spec.parse_option("compgen", option, &mut args)? {
+                    return Err(anyhow::anyhow!("compgen: {}: invalid option", option));
+                }
+            }
+            word => words.push(word),
+        }
+    }
+
+    let request = CompletionRequest { word: words.first().copied().unwrap_or(""), ..CompletionRequest::default() };
+    let is_verbatim = spec.style().verbatim;
+    Ok(spec.generate(&request).iter()
+        // File names are printed without the slash that completion adds to directories
+        .map(|name| if is_verbatim { name.as_str() } else { name.strip_suffix('/').unwrap_or(name) })
+        .map(|name| format!("{}\n", name))
+        .collect::<String>()
+        .into_bytes())
+}
+
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
+    print!("{}", String::from_utf8_lossy(&output));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compgen_word_list() -> Result<(), anyhow::Error> {
+        assert_eq!(String::from_utf8(generate_output(&["-W", "build check clean", "c"])?)?, "check\nclean\n");
+        assert_eq!(String::from_utf8(generate_output(&["-W", "build check"])?)?, "build\ncheck\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_compgen_directories() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        std::fs::create_dir(directory.path().join("target"))?;
+        let word = format!("{}/t", directory.path().display());
+        assert_eq!(String::from_utf8(generate_output(&["-d", &word])?)?, format!("{}/target\n", directory.path().display()));
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::io::{self, Write};
+use crate::input::completion_spec::{self, CompletionSpec};
+
+/// Defines the completion spec of commands like bash's `complete`; `-p` prints specs and `-r` removes them.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut spec = CompletionSpec::default();
+    let mut should_print = false;
+    let mut should_remove = false;
+    let mut names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-p" => should_print = true,
+            "-r" => should_remove = true,
+            option if option.starts_with('-') && option.len() > 1 => {
+                if !spec.parse_option("complete", option, &mut args)? {
+                    return Err(anyhow::anyhow!("complete: {}: invalid option", option));
+                }
+            }
+            name => names.push(name),
+        }
+    }
+
+    if should_remove {
+        if names.is_empty() {
+            completion_spec::clear()?;
+        }
+        for name in names {
+            if !completion_spec::remove(name)? {
+                return Err(anyhow::anyhow!("complete: {}: no completion specification", name));
+            }
+        }
+        Ok(Vec::new())
+    } else if should_print || names.is_empty() {
+        print_specs(&names)
+    } else {
+        for name in names {
+            completion_spec::set(name, spec.clone())?;
+        }
+        Ok(Vec::new())
+    }
+}
+
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
+    print!("{}", String::from_utf8_lossy(&output));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// Prints the specs of the given commands, or of all commands, as `complete` commands.
+fn print_specs(names: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    if names.is_empty() {
+        return Ok(completion_spec::all().iter()
+            .map(|(name, spec)| format!("{}\n", spec.format(name)))
+            .collect::<String>()
+            .into_bytes());
+    }
+    let mut output = String::new();
+    for name in names {
+        let spec = completion_spec::get(name)
+            .ok_or_else(|| anyhow::anyhow!("complete: {}: no completion specification", name))?;
+        output.push_str(&format!("{}\n", spec.format(name)));
+    }
+    Ok(output.into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_define_print_and_remove() -> Result<(), anyhow::Error> {
+        generate_output(&["-o", "nospace", "-W", "up down", "complete-test-tool"])?;
+        assert_eq!(String::from_utf8(generate_output(&["-p", "complete-test-tool"])?)?, "complete -o nospace -W 'up down' complete-test-tool\n");
+        assert!(String::from_utf8(generate_output(&[])?)?.contains("complete-test-tool\n"));
+
+        generate_output(&["-r", "complete-test-tool"])?;
+        assert!(completion_spec::get("complete-test-tool").is_none());
+        assert!(generate_output(&["-r", "complete-test-tool"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_option() {
+        assert!(generate_output(&["-z", "tool"]).is_err());
+        assert!(generate_output(&["-W"]).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+use crate::command::builtin::pwd::{self, Links};
+
+lazy_static! {
+    // The directories below the current one on the directory stack, the most recently left first
+    static ref DIRECTORY_STACK: RwLock<Vec<PathBuf>> = RwLock::new(Vec::new());
+}
+
+/// The directory stack as `dirs` shows it, starting with the current directory.
+pub(crate) fn stack() -> Result<Vec<PathBuf>, anyhow::Error> {
+    let mut stack = vec![pwd::current_directory(Links::Logical)?];
+    let stored = DIRECTORY_STACK.read()
+        .map_err(|_| anyhow::anyhow!("The directory stack is unavailable"))?;
+    stack.extend(stored.iter().cloned());
+    Ok(stack)
+}
+
+/// Keeps the directories below the current one, which is always the top of the stack.
+pub(crate) fn set_stack(stack: &[PathBuf]) -> Result<(), anyhow::Error> {
+    let mut stored = DIRECTORY_STACK.write()
+        .map_err(|_| anyhow::anyhow!("The directory stack is unavailable"))?;
+    *stored = stack.iter().skip(1).cloned().collect();
+    Ok(())
+}
+
+/// The position of a `+N` entry counted from the top of the stack or a `-N` entry counted from the bottom.
+pub(crate) fn parse_index(argument: &str, length: usize) -> Option<usize> {
+    let (from_bottom, number) = match argument.split_at_checked(1)? {
+        ("+", number) => (false, number),
+        ("-", number) => (true, number),
+        _ => return None,
+    };
+    if number.is_empty() || !number.chars().all(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    let index: usize = number.parse().ok().filter(|index| *index < length)?;
+    Some(if from_bottom { length - 1 - index } else { index })
+}
+
+/// Whether the argument is a `+N` or `-N` stack position rather than a directory or option.
+pub(crate) fn is_index(argument: &str) -> bool {
+    argument.len() > 1 && argument.starts_with(['+', '-']) && argument[1..].chars().all(|ch| ch.is_ascii_digit())
+}
+
+/// The entry `~N`, `~+N` or `~-N` stands for.
+pub(crate) fn entry(position: &str) -> Option<PathBuf> {
+    let stack = stack().ok()?;
+    let position = if position.starts_with(['+', '-']) { position.to_string() } else { format!("+{}", position) };
+    parse_index(&position, stack.len()).map(|index| stack[index].clone())
+}
+
+/// The stack on one line, with the home directory shortened to `~`, as `pushd` and `popd` print it.
+pub(crate) fn format_line(stack: &[PathBuf]) -> String {
+    format!("{}\n", entries(stack, env::var("HOME").ok().as_deref()).join(" "))
+}
+
+/// The entries as they are shown, with the home directory, if any, shortened to `~`.
+fn entries(stack: &[PathBuf], home: Option<&str>) -> Vec<String> {
+    stack.iter().map(|directory| abbreviate(directory, home)).collect()
+}
+
+fn abbreviate(directory: &Path, home: Option<&str>) -> String {
+    let directory = directory.to_string_lossy();
+    match home.filter(|home| !home.is_empty() && *home != "/") {
+        Some(home) if directory == home => "~".to_string(),
+        Some(home) if directory.strip_prefix(home).is_some_and(|rest| rest.starts_with('/')) => {
+            format!("~{}", &directory[home.len()..])
+        }
+        _ => directory.into_owned(),
+    }
+}
+
+/// How `dirs` lists the stack.
+#[derive(Debug, Default)]
+struct Listing<'a> {
+    long: bool,
+    numbered: bool,
+    one_per_line: bool,
+    position: Option<&'a str>,
+}
+
+impl Listing<'_> {
+    /// The stack as listed, with the home directory shortened to `~` unless the long form is asked for.
+    fn format(&self, mut stack: Vec<PathBuf>, home: Option<&str>) -> Result<String, anyhow::Error> {
+        if let Some(position) = self.position {
+            let index = parse_index(position, stack.len())
+                .ok_or_else(|| anyhow::anyhow!("dirs: {}: directory stack index out of range", position))?;
+            stack = vec![stack.swap_remove(index)];
+        }
+        let entries = entries(&stack, if self.long { None } else { home });
+        Ok(if self.numbered {
+            entries.iter().enumerate().map(|(index, entry)| format!("{:2}  {}\n", index, entry)).collect()
+        } else if self.one_per_line {
+            entries.iter().map(|entry| format!("{}\n", entry)).collect()
+        } else {
+            format!("{}\n", entries.join(" "))
+        })
+    }
+}
+
+/// Shows the directory stack like bash's `dirs`: `-v` numbers the entries one per line, `-p` puts them one per
+/// line, `-l` keeps the home directory unabbreviated, `-c` clears the stack and `+N` or `-N` shows one entry.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let mut should_clear = false;
+    let mut listing = Listing::default();
+    for arg in args.iter().map(|arg| arg.trim()) {
+        match arg {
+            index if is_index(index) => listing.position = Some(index),
+            option if option.starts_with('-') && option.len() > 1 => {
+                for flag in option[1..].chars() {
+                    match flag {
+                        'c' => should_clear = true,
+                        'l' => listing.long = true,
+                        'v' => listing.numbered = true,
+                        'p' => listing.one_per_line = true,
+                        _ => return Err(anyhow::anyhow!("dirs: -{}: invalid option", flag)),
+                    }
+                }
+            }
+            _ => return Err(anyhow::anyhow!("dirs: {}: invalid argument", arg)),
+        }
+    }
+
+    if should_clear {
+        set_stack(&[])?;
+        return Ok(Vec::new());
+    }
+    let output = listing.format(stack()?, env::var("HOME").ok().as_deref())?;
+    Ok(output.into_bytes())
+}
+
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
+    print!("{}", String::from_utf8_lossy(&output));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_index() {
+        assert_eq!(parse_index("+0", 3), Some(0));
+        assert_eq!(parse_index("+2", 3), Some(2));
+        assert_eq!(parse_index("-0", 3), Some(2));
+        assert_eq!(parse_index("-2", 3), Some(0));
+        assert_eq!(parse_index("+3", 3), None);
+        assert_eq!(parse_index("2", 3), None);
+        assert_eq!(parse_index("+", 3), None);
+        assert_eq!(parse_index("-v", 3), None);
+    }
+
+    #[test]
+    fn test_abbreviate_home() {
+        assert_eq!(abbreviate(Path::new("/home/user"), Some("/home/user")), "~");
+        assert_eq!(abbreviate(Path::new("/home/user/src"), Some("/home/user")), "~/src");
+        assert_eq!(abbreviate(Path::new("/home/username"), Some("/home/user")), "/home/username");
+        assert_eq!(abbreviate(Path::new("/tmp"), Some("/")), "/tmp");
+        assert_eq!(abbreviate(Path::new("/home/user"), None), "/home/user");
+    }
+
+    fn test_stack() -> Vec<PathBuf> {
+        vec![PathBuf::from("/home/user/src"), PathBuf::from("/tmp"), PathBuf::from("/home/user")]
+    }
+
+    #[test]
+    fn test_entries() {
+        assert_eq!(entries(&test_stack(), Some("/home/user")), vec!["~/src", "/tmp", "~"]);
+        assert_eq!(entries(&test_stack(), None), vec!["/home/user/src", "/tmp", "/home/user"]);
+    }
+
+    #[test]
+    fn test_listing_formats() -> Result<(), anyhow::Error> {
+        let home = Some("/home/user");
+        assert_eq!(Listing::default().format(test_stack(), home)?, "~/src /tmp ~\n");
+        assert_eq!(Listing { numbered: true, ..Listing::default() }.format(test_stack(), home)?, " 0  ~/src\n 1  /tmp\n 2  ~\n");
+        assert_eq!(Listing { one_per_line: true, ..Listing::default() }.format(test_stack(), home)?, "~/src\n/tmp\n~\n");
+        assert_eq!(Listing { long: true, ..Listing::default() }.format(test_stack(), home)?, "/home/user/src /tmp /home/user\n");
+        assert_eq!(
+            Listing { long: true, numbered: true, ..Listing::default() }.format(test_stack(), home)?,
+            " 0  /home/user/src\n 1  /tmp\n 2  /home/user\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_listing_one_entry() -> Result<(), anyhow::Error> {
+        let home = Some("/home/user");
+        assert_eq!(Listing { position: Some("+1"), ..Listing::default() }.format(test_stack(), home)?, "/tmp\n");
+        assert_eq!(Listing { position: Some("-0"), ..Listing::default() }.format(test_stack(), home)?, "~\n");
+        assert_eq!(Listing { position: Some("+0"), long: true, ..Listing::default() }.format(test_stack(), home)?, "/home/user/src\n");
+        assert!(Listing { position: Some("-3"), ..Listing::default() }.format(test_stack(), home).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(generate_output(&["-x"]).is_err());
+        assert!(generate_output(&["somewhere"]).is_err());
+        assert!(generate_output(&["+100"]).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use crate::path::{self, HashedCommand};
+
+/// Lists, adds and forgets the commands remembered for running like bash's `hash`: `-r` forgets all of them,
+/// `-d` the given ones, and `-p file name` runs the command from that file.
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let path = path::Path::parse(&env::var("PATH").unwrap_or_default())?;
+    let mut should_forget = false;
+    let mut should_clear = false;
+    let mut executable = None;
+    let mut names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-r" => should_clear = true,
+            "-d" => should_forget = true,
+            "-p" => {
+                let file = args.next().ok_or_else(|| anyhow::anyhow!("hash: -p: option requires an argument"))?;
+                executable = Some(PathBuf::from(file));
+            }
+            option if option.starts_with('-') && option.len() > 1 => {
+                return Err(anyhow::anyhow!("hash: {}: invalid option", option));
+            }
+            name => names.push(name),
+        }
+    }
+
+    if should_clear {
+        path::forget_hashed_commands()?;
+        if names.is_empty() {
+            return Ok(Vec::new());
+        }
+    }
+    if let Some(executable) = executable {
+        let name = names.first().ok_or_else(|| anyhow::anyhow!("hash: -p: a command name is required"))?;
+        if executable.is_dir() {
+            return Err(anyhow::anyhow!("hash: {}: Is a directory", executable.display()));
+        }
+        path::hash_command(&path, name, executable)?;
+        return Ok(Vec::new());
+    }
+    if should_forget {
+        for name in names {
+            if !path::forget_hashed_command(&path, name)? {
+                return Err(anyhow::anyhow!("hash: {}: not found", name));
+            }
+        }
+        return Ok(Vec::new());
+    }
+    if names.is_empty() {
+        return Ok(format_table(&path::hashed_commands(&path)).into_bytes());
+    }
+    for name in names {
+        // Builtins and paths are not looked up
+        if !name.contains('/') && !super::is_builtin(name) && !path.remember(name)? {
+            return Err(anyhow::anyhow!("hash: {}: not found", name));
+        }
+    }
+    Ok(Vec::new())
+}
+
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
+    print!("{}", String::from_utf8_lossy(&output));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// Formats the commands the way bash lists them, with the number of times each ran.
+fn format_table(commands: &[(String, HashedCommand)]) -> String {
+    if commands.is_empty() {
+        return "hash: hash table empty\n".to_string();
+    }
+    let mut table = String::from("hits\tcommand\n");
+    for (_, command) in commands {
+        table.push_str(&format!("{:>4}\t{}\n", command.hits, command.path.display()));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_table() {
+        assert_eq!(format_table(&[]), "hash: hash table empty\n");
+        let commands = vec![
+            ("cat".to_string(), HashedCommand { path: PathBuf::from("/bin/cat"), hits: 3 }),
+            ("ls".to_string(), HashedCommand { path: PathBuf::from("/usr/bin/ls"), hits: 12 }),
+        ];
+        assert_eq!(format_table(&commands), "hits\tcommand\n   3\t/bin/cat\n  12\t/usr/bin/ls\n");
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(generate_output(&["-x"]).is_err());
+        assert!(generate_output(&["-p"]).is_err());
+        assert!(generate_output(&["-p", "/bin/sh"]).is_err());
+        assert!(generate_output(&["no-such-command-anywhere"]).is_err());
+    }
+}
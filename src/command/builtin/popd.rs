@@ -0,0 +1,67 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use crate::command::builtin::dirs;
+use crate::command::builtin::pushd::enter;
+
+/// Removes the top directory from the stack and changes to the next one like bash's `popd`; `+N` or `-N` removes
+/// that entry instead, changing directory only when it is the top one.
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let position = match args.iter().map(|arg| arg.trim()).collect::<Vec<_>>().as_slice() {
+        [] => None,
+        [position] if dirs::is_index(position) => Some(position.to_string()),
+        [argument] => return Err(anyhow::anyhow!("popd: {}: invalid argument", argument)),
+        _ => return Err(anyhow::anyhow!("popd: too many arguments")),
+    };
+    let (stack, removed_top) = remove(dirs::stack()?, position.as_deref())?;
+    if removed_top {
+        enter(&stack[0], "popd")?;
+    }
+    dirs::set_stack(&stack)?;
+    print!("{}", dirs::format_line(&stack));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// The stack without the top entry or the `+N` or `-N` one, and whether the top one, the current directory,
+/// was removed.
+fn remove(mut stack: Vec<PathBuf>, position: Option<&str>) -> Result<(Vec<PathBuf>, bool), anyhow::Error> {
+    let index = match position {
+        None => 0,
+        Some(position) => dirs::parse_index(position, stack.len())
+            .ok_or_else(|| anyhow::anyhow!("popd: {}: directory stack index out of range", position))?,
+    };
+    if stack.len() < 2 {
+        return Err(anyhow::anyhow!("popd: directory stack empty"));
+    }
+    stack.remove(index);
+    Ok((stack, index == 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack_of(directories: &[&str]) -> Vec<PathBuf> {
+        directories.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_remove_top() -> Result<(), anyhow::Error> {
+        assert_eq!(remove(stack_of(&["/a", "/b", "/c"]), None)?, (stack_of(&["/b", "/c"]), true));
+        assert_eq!(remove(stack_of(&["/a", "/b"]), Some("+0"))?, (stack_of(&["/b"]), true));
+        assert_eq!(remove(stack_of(&["/a"]), None).unwrap_err().to_string(), "popd: directory stack empty");
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_by_index() -> Result<(), anyhow::Error> {
+        assert_eq!(remove(stack_of(&["/a", "/b", "/c"]), Some("+1"))?, (stack_of(&["/a", "/c"]), false));
+        assert_eq!(remove(stack_of(&["/a", "/b", "/c"]), Some("-0"))?, (stack_of(&["/a", "/b"]), false));
+        assert_eq!(remove(stack_of(&["/a", "/b", "/c"]), Some("-2"))?, (stack_of(&["/b", "/c"]), true));
+        assert_eq!(
+            remove(stack_of(&["/a", "/b"]), Some("+5")).unwrap_err().to_string(),
+            "popd: +5: directory stack index out of range"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::command::builtin::cd::{self, change_directory};
+use crate::command::builtin::dirs;
+use crate::command::builtin::pwd::{self, Links};
+
+/// Pushes a directory on the stack and changes to it like bash's `pushd`. Without arguments it swaps the top two
+/// entries, and `+N` or `-N` rotates the stack until that entry is on top.
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let stack = dirs::stack()?;
+    let stack = match args.iter().map(|arg| arg.trim()).collect::<Vec<_>>().as_slice() {
+        [] => enter_top(rotate(stack, None)?)?,
+        [position] if dirs::is_index(position) => enter_top(rotate(stack, Some(position))?)?,
+        [directory] => {
+            let destination = cd::determine_destination(Some(directory), &pwd::current_directory(Links::Logical)?, Links::Logical)?;
+            let new_directory = change_directory(&destination.path, Links::Logical)
+                .map_err(|e| anyhow::anyhow!("pushd: {}: {}", directory, cd::error_reason(&e)))?;
+            push(stack, new_directory)
+        }
+        _ => return Err(anyhow::anyhow!("pushd: too many arguments")),
+    };
+    dirs::set_stack(&stack)?;
+    print!("{}", dirs::format_line(&stack));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// The stack with the top two entries swapped, or rotated so that the `+N` or `-N` entry is on top.
+fn rotate(mut stack: Vec<PathBuf>, position: Option<&str>) -> Result<Vec<PathBuf>, anyhow::Error> {
+    match position {
+        None if stack.len() < 2 => return Err(anyhow::anyhow!("pushd: no other directory")),
+        None => stack.swap(0, 1),
+        Some(position) => {
+            let index = dirs::parse_index(position, stack.len())
+                .ok_or_else(|| anyhow::anyhow!("pushd: {}: directory stack index out of range", position))?;
+            stack.rotate_left(index);
+        }
+    }
+    Ok(stack)
+}
+
+/// The stack with the directory just entered on top.
+fn push(mut stack: Vec<PathBuf>, directory: PathBuf) -> Vec<PathBuf> {
+    stack.insert(0, directory);
+    stack
+}
+
+fn enter_top(stack: Vec<PathBuf>) -> Result<Vec<PathBuf>, anyhow::Error> {
+    enter(&stack[0], "pushd")?;
+    Ok(stack)
+}
+
+/// Changes to a directory of the stack.
+pub(crate) fn enter(directory: &Path, command: &str) -> Result<(), anyhow::Error> {
+    change_directory(directory, Links::Logical)
+        .map_err(|e| anyhow::anyhow!("{}: {}: {}", command, directory.display(), cd::error_reason(&e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack_of(directories: &[&str]) -> Vec<PathBuf> {
+        directories.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_rotate_without_arguments_swaps_the_top_two() -> Result<(), anyhow::Error> {
+        assert_eq!(rotate(stack_of(&["/a", "/b", "/c"]), None)?, stack_of(&["/b", "/a", "/c"]));
+        assert_eq!(rotate(stack_of(&["/a"]), None).unwrap_err().to_string(), "pushd: no other directory");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_brings_the_entry_on_top() -> Result<(), anyhow::Error> {
+        assert_eq!(rotate(stack_of(&["/a", "/b", "/c", "/d"]), Some("+2"))?, stack_of(&["/c", "/d", "/a", "/b"]));
+        assert_eq!(rotate(stack_of(&["/a", "/b", "/c", "/d"]), Some("-0"))?, stack_of(&["/d", "/a", "/b", "/c"]));
+        assert_eq!(rotate(stack_of(&["/a", "/b"]), Some("+0"))?, stack_of(&["/a", "/b"]));
+        assert_eq!(
+            rotate(stack_of(&["/a", "/b"]), Some("+2")).unwrap_err().to_string(),
+            "pushd: +2: directory stack index out of range"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_puts_the_directory_on_top() {
+        assert_eq!(push(stack_of(&["/a", "/b"]), PathBuf::from("/c")), stack_of(&["/c", "/a", "/b"]));
+    }
+}
@@ -1,17 +1,96 @@
 use std::env;
+use std::fs;
 use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 
-pub(crate) fn generate_output() -> Result<Vec<u8>, anyhow::Error> {
-    let current_directory = env::current_dir()
+/// How `cd` and `pwd` treat symbolic links: the logical path keeps them as they were typed, the physical one
+/// has them resolved.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Links {
+    #[default]
+    Logical,
+    Physical,
+}
+
+impl Links {
+    /// Reads `-L` and `-P` from the start of the arguments, the last one winning, and returns the rest.
+    pub(crate) fn parse<'a>(command: &str, args: &'a [&'a str]) -> Result<(Links, &'a [&'a str]), anyhow::Error> {
+        let mut links = Links::default();
+        let mut rest = args;
+        while let Some((arg, remaining)) = rest.split_first() {
+            match arg.trim() {
+                "--" => return Ok((links, remaining)),
+                option if option.starts_with('-') && option.len() > 1 => {
+                    for flag in option[1..].chars() {
+                        match flag {
+                            'L' => links = Links::Logical,
+                            'P' => links = Links::Physical,
+                            _ => return Err(anyhow::anyhow!("{}: -{}: invalid option", command, flag)),
+                        }
+                    }
+                }
+                _ => break,
+            }
+            rest = remaining;
+        }
+        Ok((links, rest))
+    }
+}
+
+/// The current directory: `PWD` while it still names it, otherwise the physical one.
+pub(crate) fn current_directory(links: Links) -> Result<PathBuf, anyhow::Error> {
+    let physical = env::current_dir()
         .map_err(|e| anyhow::anyhow!("Failed to get current directory: {}", e))?;
+    if links == Links::Physical {
+        return Ok(physical);
+    }
+    Ok(env::var("PWD").ok()
+        .map(PathBuf::from)
+        .filter(|logical| logical.is_absolute() && is_same_file(logical, &physical))
+        .unwrap_or(physical))
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let (links, _) = Links::parse("pwd", args)?;
+    let current_directory = current_directory(links)?;
     let path_str = current_directory.to_str()
         .ok_or_else(|| anyhow::anyhow!("Path contains invalid Unicode"))?;
     Ok(format!("{}\n", path_str).into_bytes())
 }
 
-pub(crate) fn run(_: &[&str]) -> Result<(), anyhow::Error> {
-    let output = generate_output()?;
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
     print!("{}", String::from_utf8_lossy(&output));
     io::stdout().flush()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_links() -> Result<(), anyhow::Error> {
+        assert_eq!(Links::parse("pwd", &[])?, (Links::Logical, &[][..]));
+        assert_eq!(Links::parse("cd", &["-P", "dir"])?, (Links::Physical, &["dir"][..]));
+        assert_eq!(Links::parse("cd", &["-PL", "--", "-"])?, (Links::Logical, &["-"][..]));
+        assert_eq!(Links::parse("cd", &["-", "-P"])?, (Links::Logical, &["-", "-P"][..]));
+        assert!(Links::parse("pwd", &["-x"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_current_directory_is_the_physical_one_physically() -> Result<(), anyhow::Error> {
+        assert_eq!(current_directory(Links::Physical)?, env::current_dir()?);
+        assert!(is_same_file(&current_directory(Links::Logical)?, &env::current_dir()?));
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Write};
+use crate::command::not_found;
+use crate::input::settings::{self, EditingMode};
+
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    match args {
+        [] | ["-o"] | ["+o"] => Ok(list_options(settings::current().editing_mode, not_found::is_correct_enabled()).into_bytes()),
+        ["-o", "correct"] | ["+o", "correct"] => {
+            not_found::set_correct(args[0] == "-o");
+            Ok(Vec::new())
+        }
+        ["-o", option] => {
+            settings::set_editing_mode(parse_option(option)?)?;
+            Ok(Vec::new())
+        }
+        ["+o", "vi"] => {
+            settings::set_editing_mode(EditingMode::Emacs)?;
+            Ok(Vec::new())
+        }
+        ["+o", option] => {
+            parse_option(option)?;
+            Err(anyhow::anyhow!("set: +o {}: the line editor needs an editing mode, use set -o vi", option))
+        }
+        _ => Err(anyhow::anyhow!("set: {}: unsupported option", args.join(" "))),
+    }
+}
+
+pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
+    let output = generate_output(args)?;
+    print!("{}", String::from_utf8_lossy(&output));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+fn parse_option(option: &str) -> Result<EditingMode, anyhow::Error> {
+    match option {
+        "emacs" => Ok(EditingMode::Emacs),
+        "vi" => Ok(EditingMode::Vi),
+        _ => Err(anyhow::anyhow!("set: {}: invalid option name", option)),
+    }
+}
+
+/// Lists the options in the format of `set -o` in bash.
+fn list_options(editing_mode: EditingMode, correct: bool) -> String {
+    [("correct", correct), ("emacs", editing_mode == EditingMode::Emacs), ("vi", editing_mode == EditingMode::Vi)].iter()
+        .map(|(name, is_on)| format!("{:<15}\t{}\n", name, if *is_on { "on" } else { "off" }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_options() {
+        assert_eq!(list_options(EditingMode::Emacs, false), "correct        \toff\nemacs          \ton\nvi             \toff\n");
+        assert_eq!(list_options(EditingMode::Vi, true), "correct        \ton\nemacs          \toff\nvi             \ton\n");
+    }
+
+    #[test]
+    fn test_parse_option() {
+        assert_eq!(parse_option("vi").ok(), Some(EditingMode::Vi));
+        assert_eq!(parse_option("emacs").ok(), Some(EditingMode::Emacs));
+        assert!(parse_option("posix").is_err());
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(generate_output(&["-o", "posix"]).is_err());
+        assert!(generate_output(&["+o", "emacs"]).is_err());
+        assert!(generate_output(&["-x"]).is_err());
+    }
+}
@@ -1,29 +1,160 @@
 use std::env;
 use std::io::{self, Write};
 use crate::path;
-use crate::command::builtin;
+use crate::command::resolver::{self, Lookup, Resolution};
+use crate::command::{EXIT_STATUS_FAILURE, EXIT_STATUS_SUCCESS};
 
-pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
-    let path = path::Path::parse(&env::var("PATH")?)?;
-    if let Some(command_name) = args.get(0) {
-        let output = if builtin::is_builtin(command_name) {
-            format!("{} is a shell builtin\n", command_name.trim())
-        } else {
-            if let Some(found_executable) = path.find_command(command_name.trim()) {
-                format!("{} is {}\n", command_name.trim(), found_executable)
-            } else {
-                format!("{}: not found\n", command_name.trim())
+/// How `type` reports a name: `-t` prints the kind, `-p` the path of a file and `-P` the path even of builtins.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Format {
+    #[default]
+    Sentence,
+    Kind,
+    Path,
+    ForcedPath,
+}
+
+/// One line of `type` output for what the name resolves to, if that format shows it.
+pub(crate) fn describe(resolution: &Resolution, name: &str, format: Format) -> Option<String> {
+    match (resolution, format) {
+        (Resolution::Builtin(_), Format::Sentence) => Some(format!("{} is a shell builtin", name)),
+        (Resolution::Builtin(_), Format::Kind) => Some("builtin".to_string()),
+        (Resolution::Builtin(_), _) => None,
+        (Resolution::Hashed(path), Format::Sentence) => Some(format!("{} is hashed ({})", name, path.display())),
+        (Resolution::File(path), Format::Sentence) => Some(format!("{} is {}", name, path.display())),
+        (_, Format::Kind) => Some("file".to_string()),
+        (Resolution::Hashed(path) | Resolution::File(path), _) => Some(path.display().to_string()),
+    }
+}
+
+/// Describes each name like bash's `type`; `-a` lists every builtin and file the name could run.
+/// The status is non-zero when any name is not found.
+fn describe_all(args: &[&str], path: &path::Path) -> Result<(String, bool), anyhow::Error> {
+    let mut format = Format::default();
+    let mut all = false;
+    let mut names = Vec::new();
+    for arg in args {
+        match *arg {
+            "--" if names.is_empty() => (),
+            option if option.starts_with('-') && option.len() > 1 && names.is_empty() => {
+                for flag in option[1..].chars() {
+                    match flag {
+                        'a' => all = true,
+                        't' => format = Format::Kind,
+                        'p' if format != Format::ForcedPath => format = Format::Path,
+                        'p' => (),
+                        'P' => format = Format::ForcedPath,
+                        _ => return Err(anyhow::anyhow!("type: -{}: invalid option", flag)),
+                    }
+                }
+            }
+            name => names.push(name.trim()),
+        }
+    }
+
+    let mut output = String::new();
+    let mut all_found = true;
+    for name in names {
+        let resolutions = resolve(name, path, all, format == Format::ForcedPath);
+        if resolutions.is_empty() {
+            all_found = false;
+            // Only the plain description reports missing names
+            if format == Format::Sentence {
+                output.push_str(&format!("{}: not found\n", name));
             }
-        };
-        Ok(output.into_bytes())
-    } else {
-        Ok(Vec::new())
+        }
+        for description in resolutions.iter().filter_map(|resolution| describe(resolution, name, format)) {
+            output.push_str(&description);
+            output.push('\n');
+        }
+    }
+    Ok((output, all_found))
+}
+
+/// The first thing the name runs, or with `-a` the builtin and every executable in PATH.
+fn resolve(name: &str, path: &path::Path, all: bool, skip_builtins: bool) -> Vec<Resolution> {
+    let lookup = Lookup { builtins: !skip_builtins, ..Lookup::DESCRIBE };
+    if !all || name.contains('/') {
+        return resolver::resolve(name, path, lookup).into_iter().collect();
     }
+    let mut resolutions: Vec<Resolution> = resolver::resolve(name, path, Lookup { files: false, ..lookup }).into_iter().collect();
+    resolutions.extend(path.locate_all(name).into_iter().map(Resolution::File));
+    resolutions
 }
 
-pub(crate) fn run(args: &[&str]) -> Result<(), anyhow::Error> {
-    let output = generate_output(args)?;
-    print!("{}", String::from_utf8_lossy(&output));
+pub(crate) fn generate_output(args: &[&str]) -> Result<Vec<u8>, anyhow::Error> {
+    let path = path::Path::parse(&env::var("PATH").unwrap_or_default())?;
+    describe_all(args, &path).map(|(output, _)| output.into_bytes())
+}
+
+/// Prints the descriptions and returns the exit status.
+pub(crate) fn run(args: &[&str]) -> Result<i32, anyhow::Error> {
+    let path = path::Path::parse(&env::var("PATH").unwrap_or_default())?;
+    let (output, all_found) = describe_all(args, &path)?;
+    print!("{}", output);
     io::stdout().flush()?;
-    Ok(())
-}
\ No newline at end of file
+    Ok(if all_found { EXIT_STATUS_SUCCESS } else { EXIT_STATUS_FAILURE })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn create_executable(directory: &std::path::Path, name: &str) -> Result<PathBuf, anyhow::Error> {
+        let file = directory.join(name);
+        fs::write(&file, "#!/bin/sh\n")?;
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o755))?;
+        Ok(file)
+    }
+
+    fn test_path(directories: &[&std::path::Path]) -> Result<path::Path, anyhow::Error> {
+        let directories: Vec<String> = directories.iter().map(|directory| directory.display().to_string()).collect();
+        path::Path::parse(&directories.join(":"))
+    }
+
+    #[test]
+    fn test_describe_several_names() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let tool = create_executable(directory.path(), "tool")?;
+        let path = test_path(&[directory.path()])?;
+
+        let (output, all_found) = describe_all(&["cd", "tool", "missing"], &path)?;
+        assert_eq!(output, format!("cd is a shell builtin\ntool is {}\nmissing: not found\n", tool.display()));
+        assert!(!all_found);
+        assert!(describe_all(&["cd", "tool"], &path)?.1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_all_locations() -> Result<(), anyhow::Error> {
+        let first = tempfile::tempdir()?;
+        let second = tempfile::tempdir()?;
+        let first_echo = create_executable(first.path(), "echo")?;
+        let second_echo = create_executable(second.path(), "echo")?;
+        let path = test_path(&[first.path(), second.path()])?;
+
+        let (output, _) = describe_all(&["-a", "echo"], &path)?;
+        assert_eq!(output, format!("echo is a shell builtin\necho is {}\necho is {}\n", first_echo.display(), second_echo.display()));
+        let (output, _) = describe_all(&["-ta", "echo"], &path)?;
+        assert_eq!(output, "builtin\nfile\nfile\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe_kinds_and_paths() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let echo = create_executable(directory.path(), "echo")?;
+        let path = test_path(&[directory.path()])?;
+
+        assert_eq!(describe_all(&["-t", "echo", "missing"], &path)?, ("builtin\n".to_string(), false));
+        assert_eq!(describe_all(&["-p", "echo"], &path)?, (String::new(), true));
+        assert_eq!(describe_all(&["-P", "echo"], &path)?, (format!("{}\n", echo.display()), true));
+        assert!(describe_all(&["-x", "echo"], &path).is_err());
+        assert_eq!(describe_all(&["--", "echo"], &path)?, ("echo is a shell builtin\n".to_string(), true));
+        assert_eq!(describe_all(&["-P", "--", "echo"], &path)?, (format!("{}\n", echo.display()), true));
+        Ok(())
+    }
+}
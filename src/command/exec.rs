@@ -5,9 +5,13 @@ use std::process::Output;
 use std::fs::{OpenOptions, File};
 use std::os::unix::net::UnixStream;
 use std::os::unix::io::{IntoRawFd, FromRawFd};
-use crate::command::ParsedCommand;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::ExitStatus;
+use crate::command::{ParsedCommand, EXIT_STATUS_FAILURE, EXIT_STATUS_SUCCESS};
 use crate::command::builtin;
+use crate::command::resolver;
 use crate::history::History;
+use crate::path;
 
 
 #[derive(Debug, PartialEq)]
@@ -16,10 +20,10 @@ struct ExecutableInfo {
     pub directory: String,
 }
 
-pub(crate) fn run(parsed_command: &ParsedCommand, history: &History) -> Result<(), anyhow::Error> {
+/// Runs the pipeline and returns the exit status of its last command.
+pub(crate) fn run(parsed_command: &ParsedCommand, history: &History) -> Result<i32, anyhow::Error> {
     let commands = get_pipeline_commands(parsed_command);
-    run_pipeline(&commands, history)?;
-    Ok(())
+    run_pipeline(&commands, history)
 }
 
 fn get_pipeline_commands(command: &ParsedCommand) -> Vec<ParsedCommand> {
@@ -29,12 +33,18 @@ fn get_pipeline_commands(command: &ParsedCommand) -> Vec<ParsedCommand> {
         current_command = *next_command.clone();
         commands.push(*next_command);
     }
-    commands
+    // `command` and `builtin` only choose what runs, which the pipeline decides by itself
+    commands.into_iter().map(|mut command| {
+        while let Some(unwrapped) = resolver::unwrap_prefix(&command) {
+            command = unwrapped;
+        }
+        command
+    }).collect()
 }
 
-fn run_pipeline(commands: &[ParsedCommand], history: &History) -> Result<(), anyhow::Error> {
+fn run_pipeline(commands: &[ParsedCommand], history: &History) -> Result<i32, anyhow::Error> {
     if commands.is_empty() {
-        return Ok(());
+        return Ok(EXIT_STATUS_SUCCESS);
     }
 
     let mut previous_stdin: Option<Stdio> = None;
@@ -91,6 +101,7 @@ fn run_pipeline(commands: &[ParsedCommand], history: &History) -> Result<(), any
         }
     }
 
+    let mut status = EXIT_STATUS_SUCCESS;
     if !children.is_empty() {
         let last_command = &commands[commands.len() - 1];
         let is_last_builtin = builtin::is_builtin(&last_command.command);
@@ -105,13 +116,27 @@ fn run_pipeline(commands: &[ParsedCommand], history: &History) -> Result<(), any
         }
         if let Some(output) = last_non_builtin_child_output {
             write_command_output(last_command, &output)?;
+            status = exit_status_code(&output.status);
         }
     }
 
-    Ok(())
+    Ok(status)
+}
+
+/// Exit status as seen by `$?`: the exit code, or 128 plus the number of the signal that killed the process.
+fn exit_status_code(status: &ExitStatus) -> i32 {
+    status.code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(EXIT_STATUS_FAILURE)
 }
 
 fn build_command_from_parsed(command_name: &str, args: &[String]) -> Command {
+    // A command remembered by `hash` runs from its file without searching PATH again, still named as typed
+    if let Some(executable) = path::hashed_command(command_name) {
+        let mut command = Command::new(executable);
+        command.arg0(command_name).args(args);
+        return command;
+    }
     let exec_info = parse_executable_path(command_name);
     build_command(&exec_info, args.iter().map(|a| a.as_str()).collect::<Vec<&str>>().as_slice())
 }
@@ -225,6 +250,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_exit_status_code() {
+        assert_eq!(exit_status_code(&ExitStatus::from_raw(0)), 0);
+        assert_eq!(exit_status_code(&ExitStatus::from_raw(2 << 8)), 2);
+        assert_eq!(exit_status_code(&ExitStatus::from_raw(libc::SIGINT)), 130);
+    }
+
+    #[test]
+    fn test_run_returns_exit_status_of_last_command() -> Result<(), anyhow::Error> {
+        let failing = create_test_parsed_command("ls", vec!["/nonexistent".to_string()], None, Some(create_temp_file_path("test_status_stderr.txt")));
+        assert_eq!(run(&failing, &History::new())?, 2);
+
+        let succeeding = create_test_parsed_command("true", vec![], None, None);
+        assert_eq!(run(&succeeding, &History::new())?, 0);
+        Ok(())
+    }
+
     #[test]
     fn test_parse_executable_path_simple_command() {
         let result = parse_executable_path("ls");
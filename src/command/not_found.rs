@@ -0,0 +1,189 @@
+use std::cmp::Reverse;
+use std::io::{BufRead, Write};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::command::{builtin, ParsedCommand, EXIT_STATUS_NOT_FOUND};
+use crate::path;
+
+// The program run in place of an unknown command, like the function of that name in bash
+const HANDLER_NAME: &str = "command_not_found_handle";
+const MAX_SUGGESTIONS: usize = 3;
+
+// The `correct` option: offer to run the closest command instead of an unknown one
+static CORRECT: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn is_correct_enabled() -> bool {
+    CORRECT.load(Ordering::Relaxed)
+}
+
+pub(crate) fn set_correct(enabled: bool) {
+    CORRECT.store(enabled, Ordering::Relaxed);
+}
+
+/// The builtins and PATH executables closest to the unknown name, nearest first.
+pub(crate) fn suggestions(name: &str, path: &path::Path) -> Vec<String> {
+    let candidates = builtin::BUILTIN_COMMANDS.keys()
+        .map(|name| name.to_string())
+        .chain(path.executable_names());
+    closest_names(name, candidates)
+}
+
+/// Names within a few edits of the typed one: one edit for short names, up to three for long ones.
+/// Among equally close names, those starting like the typed one come first.
+fn closest_names<I: IntoIterator<Item = String>>(name: &str, candidates: I) -> Vec<String> {
+    let length = name.chars().count();
+    let max_distance = (length / 4).clamp(1, 3);
+    let mut close: Vec<(usize, Reverse<usize>, String)> = candidates.into_iter()
+        .filter(|candidate| candidate != name && candidate.chars().count().abs_diff(length) <= max_distance)
+        .filter_map(|candidate| {
+            let distance = edit_distance(name, &candidate);
+            let common_prefix = name.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).count();
+            (distance <= max_distance).then_some((distance, Reverse(common_prefix), candidate))
+        })
+        .collect();
+    close.sort();
+    close.dedup();
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, _, candidate)| candidate).collect()
+}
+
+/// Edits needed to turn one name into the other, counting insertions, deletions, substitutions and
+/// swaps of adjacent characters, the usual typing mistakes.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// The report of an unknown command, with the close matches if there are any.
+pub(crate) fn message(name: &str, suggestions: &[String]) -> String {
+    let mut message = format!("{}: command not found\n", name);
+    match suggestions {
+        [] => (),
+        [suggestion] => message.push_str(&format!("Did you mean '{}'?\n", suggestion)),
+        _ => message.push_str(&format!("Did you mean one of these: {}?\n", suggestions.join(", "))),
+    }
+    message
+}
+
+/// Asks whether to run the suggested command instead; only y or yes agrees.
+pub(crate) fn confirm_correction<R: BufRead, W: Write>(reader: &mut R, writer: &mut W, suggestion: &str) -> Result<bool, anyhow::Error> {
+    write!(writer, "Run '{}' instead? [y/N] ", suggestion)?;
+    writer.flush()?;
+    let mut answer = String::new();
+    reader.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Runs `command_not_found_handle` from PATH with the command and its arguments, returning its exit status,
+/// or `None` when there is no such program or it cannot be started, which is reported.
+pub(crate) fn run_handler(parsed_command: &ParsedCommand, path: &path::Path) -> Option<i32> {
+    let handler = path.find_command(HANDLER_NAME)?;
+    let status = Command::new(&handler)
+        .arg(parsed_command.command.trim())
+        .args(&parsed_command.args)
+        .status();
+    match status {
+        Ok(status) => Some(status.code().unwrap_or(EXIT_STATUS_NOT_FOUND)),
+        Err(err) => {
+            eprintln!("{}: {}", handler, err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("git", "git"), 0);
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("pytohn3", "python3"), 1);
+        assert_eq!(edit_distance("sl", "ls"), 1);
+        assert_eq!(edit_distance("carg", "cargo"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_closest_names() {
+        let candidates = names(&["git", "gio", "grep", "gitk", "cat", "go"]);
+        assert_eq!(closest_names("gti", candidates.clone()), vec!["git"]);
+        assert_eq!(closest_names("gi", candidates.clone()), vec!["gio", "git", "go"]);
+        assert_eq!(closest_names("pwdd", names(&["pldd", "pwd", "pwdx"])), vec!["pwd", "pwdx", "pldd"]);
+        assert!(closest_names("invalid_command", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_message() {
+        assert_eq!(message("foo", &[]), "foo: command not found\n");
+        assert_eq!(message("gti", &names(&["git"])), "gti: command not found\nDid you mean 'git'?\n");
+        assert_eq!(message("gi", &names(&["gio", "git"])), "gi: command not found\nDid you mean one of these: gio, git?\n");
+    }
+
+    #[test]
+    fn test_confirm_correction() -> Result<(), anyhow::Error> {
+        let mut output = Vec::new();
+        assert!(confirm_correction(&mut Cursor::new("y\n"), &mut output, "git")?);
+        assert_eq!(String::from_utf8(output)?, "Run 'git' instead? [y/N] ");
+        assert!(!confirm_correction(&mut Cursor::new("\n"), &mut Vec::new(), "git")?);
+        assert!(!confirm_correction(&mut Cursor::new(""), &mut Vec::new(), "git")?);
+        Ok(())
+    }
+
+    fn handler_path(script: &str) -> Result<(tempfile::TempDir, path::Path), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let handler = directory.path().join(HANDLER_NAME);
+        fs::write(&handler, script)?;
+        fs::set_permissions(&handler, fs::Permissions::from_mode(0o755))?;
+        let path = path::Path::parse(directory.path().to_str().unwrap_or_default())?;
+        Ok((directory, path))
+    }
+
+    #[test]
+    fn test_run_handler_passes_the_command_and_its_status() -> Result<(), anyhow::Error> {
+        let output = tempfile::NamedTempFile::new()?;
+        let script = format!("#!/bin/sh\nprintf '%s|' \"$@\" > '{}'\nexit 42\n", output.path().display());
+        let (_directory, path) = handler_path(&script)?;
+        let parsed_command = ParsedCommand::parse_command("gti status --short")?.ok_or_else(|| anyhow::anyhow!("no command"))?;
+
+        assert_eq!(run_handler(&parsed_command, &path), Some(42));
+        assert_eq!(fs::read_to_string(output.path())?, "gti|status|--short|");
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_handler_that_cannot_start() -> Result<(), anyhow::Error> {
+        let (_directory, path) = handler_path("#!/no/such/interpreter\n")?;
+        let parsed_command = ParsedCommand::parse_command("gti")?.ok_or_else(|| anyhow::anyhow!("no command"))?;
+        assert_eq!(run_handler(&parsed_command, &path), None);
+        assert_eq!(run_handler(&parsed_command, &path::Path::parse("")?), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+use crate::command::{builtin, ParsedCommand, ShellCommand};
+use crate::history::History;
+use crate::path;
+
+/// What a command name runs.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Resolution {
+    Builtin(&'static ShellCommand),
+    /// A command remembered from an earlier lookup, see `hash`.
+    Hashed(PathBuf),
+    File(PathBuf),
+}
+
+/// Where a lookup looks, and whether a command found in PATH is remembered and counted as run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Lookup {
+    pub(crate) builtins: bool,
+    pub(crate) files: bool,
+    pub(crate) remember: bool,
+}
+
+impl Lookup {
+    /// Running a command: builtins first, then PATH.
+    pub(crate) const RUN: Lookup = Lookup { builtins: true, files: true, remember: true };
+    /// Describing a command without changing the hash table, for `type` and `command -v`.
+    pub(crate) const DESCRIBE: Lookup = Lookup { builtins: true, files: true, remember: false };
+    /// `builtin name`: the builtin even when something else has the name.
+    pub(crate) const BUILTINS: Lookup = Lookup { builtins: true, files: false, remember: false };
+}
+
+/// Resolves a name the way the shell runs it: a builtin, then a remembered command, then the first executable
+/// in PATH. Names with a slash are files and never remembered.
+pub(crate) fn resolve(name: &str, path: &path::Path, lookup: Lookup) -> Option<Resolution> {
+    if lookup.builtins {
+        if let Some(command) = builtin::BUILTIN_COMMANDS.get(name) {
+            return Some(Resolution::Builtin(command));
+        }
+    }
+    if !lookup.files {
+        return None;
+    }
+    if name.contains('/') {
+        return path.find_command(name).map(|found| Resolution::File(PathBuf::from(found)));
+    }
+    if lookup.remember {
+        return path.find_command(name).map(|found| Resolution::Hashed(PathBuf::from(found)));
+    }
+    match path.hashed_path(name) {
+        Some(hashed) => Some(Resolution::Hashed(hashed)),
+        None => path.locate(name).map(Resolution::File),
+    }
+}
+
+/// Runs the command a name resolved to and returns its exit status.
+pub(crate) fn run(resolution: &Resolution, parsed_command: &ParsedCommand, history: &mut History) -> Result<i32, anyhow::Error> {
+    match resolution {
+        Resolution::Builtin(command) => command.run(parsed_command, history),
+        Resolution::Hashed(_) | Resolution::File(_) => ShellCommand::Exec.run(parsed_command, history),
+    }
+}
+
+/// The command that `command name args` or `builtin name args` runs: the same command line without the
+/// prefix. Lookups like `command -v` run nothing.
+pub(crate) fn unwrap_prefix(parsed_command: &ParsedCommand) -> Option<ParsedCommand> {
+    if !matches!(parsed_command.command.trim(), "command" | "builtin") {
+        return None;
+    }
+    let mut args = parsed_command.args.as_slice();
+    if args.first().is_some_and(|arg| arg == "--") {
+        args = &args[1..];
+    }
+    let (name, args) = args.split_first()?;
+    // `builtin` runs nothing but builtins, the rest is reported by the builtin itself
+    if name.starts_with('-') || (parsed_command.command.trim() == "builtin" && !builtin::is_builtin(name)) {
+        return None;
+    }
+    Some(ParsedCommand { command: name.clone(), args: args.to_vec(), ..parsed_command.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn parsed(command: &str, args: &[&str]) -> ParsedCommand {
+        ParsedCommand {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            stdout_redirect: None,
+            stderr_redirect: None,
+            piped_command: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_builtins_before_files() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let echo = directory.path().join("echo");
+        fs::write(&echo, "#!/bin/sh\n")?;
+        fs::set_permissions(&echo, fs::Permissions::from_mode(0o755))?;
+        let path = path::Path::parse(directory.path().to_str().unwrap_or_default())?;
+
+        assert_eq!(resolve("echo", &path, Lookup::DESCRIBE), Some(Resolution::Builtin(&ShellCommand::Echo)));
+        assert_eq!(resolve("echo", &path, Lookup { builtins: false, ..Lookup::DESCRIBE }), Some(Resolution::File(echo.clone())));
+        assert_eq!(resolve(echo.to_str().unwrap_or_default(), &path, Lookup::RUN), Some(Resolution::File(echo)));
+        assert_eq!(resolve("cd", &path, Lookup::BUILTINS), Some(Resolution::Builtin(&ShellCommand::Cd)));
+        assert_eq!(resolve("missing", &path, Lookup::DESCRIBE), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unwrap_prefix() {
+        assert_eq!(unwrap_prefix(&parsed("command", &["ls", "-l"])), Some(parsed("ls", &["-l"])));
+        assert_eq!(unwrap_prefix(&parsed("builtin", &["--", "echo", "hi"])), Some(parsed("echo", &["hi"])));
+        assert_eq!(unwrap_prefix(&parsed("command", &["-v", "ls"])), None);
+        assert_eq!(unwrap_prefix(&parsed("builtin", &["ls"])), None);
+        assert_eq!(unwrap_prefix(&parsed("command", &[])), None);
+        assert_eq!(unwrap_prefix(&parsed("echo", &["ls"])), None);
+    }
+}
@@ -1,63 +1,77 @@
-use std::{ fs::File, io::{BufRead, BufReader, BufWriter, Write}, path::PathBuf };
+use std::{ fs::File, io::{BufRead, BufReader, BufWriter, Write}, path::{Path, PathBuf} };
 use std::fs::OpenOptions;
+use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::Error;
 
 pub(crate) struct History {
     commands: Vec<String>,
+    // Directory each command was run in, unknown for commands read from a history file
+    directories: Vec<Option<PathBuf>>,
     next_index_to_write: usize
 }
 
 impl History {
     pub(crate) fn new() -> Self {
-        History { commands: Vec::new(), next_index_to_write: 0 }
+        History { commands: Vec::new(), directories: Vec::new(), next_index_to_write: 0 }
     }
 
+    /// Reads a history file. The lines after a timestamp line such as `#1700000000`, up to the next one, are one
+    /// command, so that multi-line commands come back whole; other lines are a command each.
     pub(crate) fn read_from_file(&mut self, path: &PathBuf) -> Result<(), Error> {
         let file = File::open(path)?;
+        let mut record: Option<Vec<String>> = None;
         for line in BufReader::new(file).lines() {
             let line = line?;
-            if !line.is_empty() {
+            if is_timestamp(&line) {
+                self.append_record(record.replace(Vec::new()));
+            } else if let Some(record) = record.as_mut() {
+                record.push(line);
+            } else if !line.is_empty() {
                 self.append(&line);
             }
         }
+        self.append_record(record);
         Ok(())
     }
 
+    fn append_record(&mut self, record: Option<Vec<String>>) {
+        if let Some(command) = record.map(|lines| lines.join("\n")).filter(|command| !command.is_empty()) {
+            self.append(&command);
+        }
+    }
+
+    /// Appends the commands run since the last write. Once the file has timestamps, every command gets one.
     pub(crate) fn append_to_file(&mut self, path: &PathBuf) -> Result<(), Error> {
+        let has_timestamps = File::open(path)
+            .map(|file| BufReader::new(file).lines().map_while(Result::ok).any(|line| is_timestamp(&line)))
+            .unwrap_or(false);
         let file = OpenOptions::new()
         .append(true)
         .create(true)
         .open(path)?;
-        let mut writer = BufWriter::new(file);
-        for command in self.commands.iter().skip(self.next_index_to_write) {
-            writer.write(command.as_bytes())?;
-            writer.write(&[b'\n'])?;
-        }
-        self.next_index_to_write = if self.commands.len() > 0 {
-            self.commands.len()
-        } else {
-            0
-        };
+        write_commands(BufWriter::new(file), &self.commands[self.next_index_to_write.min(self.commands.len())..], has_timestamps)?;
+        self.next_index_to_write = self.commands.len();
         Ok(())
     }
 
     pub(crate) fn write_to_file(&mut self, path: &PathBuf) -> Result<(), Error> {
         let file =  OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
-        let mut writer = BufWriter::new(file);
-        for command in self.commands.iter() {
-            writer.write(command.as_bytes())?;
-            writer.write(&[b'\n'])?;
-        }
-        self.next_index_to_write = if self.commands.len() > 0 {
-            self.commands.len()
-        } else {
-            0
-        };
+        write_commands(BufWriter::new(file), &self.commands, false)?;
+        self.next_index_to_write = self.commands.len();
         Ok(())
     }
 
     pub(crate) fn append(&mut self, command: &str) -> &Self {
         self.commands.push(command.to_string());
+        self.directories.push(None);
+        self
+    }
+
+    pub(crate) fn append_in_directory(&mut self, command: &str, directory: Option<PathBuf>) -> &Self {
+        self.append(command);
+        if let Some(last) = self.directories.last_mut() {
+            *last = directory;
+        }
         self
     }
 
@@ -87,6 +101,64 @@ impl History {
     pub(crate) fn len(&self) -> usize {
         self.commands.len()
     }
+
+    pub(crate) fn get(&self, index: usize) -> Option<&str> {
+        self.commands.get(index).map(|command| command.as_str())
+    }
+
+    /// Returns the index of the newest command at or before `from` that contains `query`.
+    pub(crate) fn search_backward(&self, query: &str, from: usize) -> Option<usize> {
+        self.commands.iter()
+            .enumerate()
+            .take(from.saturating_add(1))
+            .rev()
+            .find(|(_, command)| command.contains(query))
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the newest command that extends `prefix`, preferring commands run in `directory`.
+    pub(crate) fn suggest(&self, prefix: &str, directory: Option<&Path>) -> Option<&str> {
+        if prefix.is_empty() {
+            return None;
+        }
+        let mut candidates = self.commands.iter()
+            .zip(self.directories.iter())
+            .rev()
+            .filter(|(command, _)| command.len() > prefix.len() && command.starts_with(prefix));
+        let in_directory = directory.and_then(|directory| {
+            candidates.clone().find(|(_, command_directory)| command_directory.as_deref() == Some(directory))
+        });
+        in_directory.or_else(|| candidates.next())
+            .map(|(command, _)| command.as_str())
+    }
+
+    /// Returns the index of the oldest command at or after `from` that contains `query`.
+    pub(crate) fn search_forward(&self, query: &str, from: usize) -> Option<usize> {
+        self.commands.iter()
+            .enumerate()
+            .skip(from)
+            .find(|(_, command)| command.contains(query))
+            .map(|(index, _)| index)
+    }
+}
+
+fn is_timestamp(line: &str) -> bool {
+    line.strip_prefix('#').is_some_and(|time| !time.is_empty() && time.chars().all(|ch| ch.is_ascii_digit()))
+}
+
+/// Writes a command per line, or like bash does with `HISTTIMEFORMAT` a timestamp line before each command when
+/// one of them has several lines or the file already has timestamps.
+fn write_commands<W: Write>(mut writer: W, commands: &[String], has_timestamps: bool) -> Result<(), Error> {
+    let with_timestamps = has_timestamps || commands.iter().any(|command| command.contains('\n'));
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+    for command in commands {
+        if with_timestamps {
+            writeln!(writer, "#{}", time)?;
+        }
+        writeln!(writer, "{}", command)?;
+    }
+    writer.flush()?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -168,6 +240,89 @@ mod tests {
         assert_eq!(history.len(), 2);
     }
 
+    fn history_of(commands: &[&str]) -> History {
+        let mut history = History::new();
+        for command in commands {
+            history.append(command);
+        }
+        history
+    }
+
+    #[test]
+    fn test_get() {
+        let history = history_of(&["echo hello", "pwd"]);
+        assert_eq!(history.get(0), Some("echo hello"));
+        assert_eq!(history.get(1), Some("pwd"));
+        assert_eq!(history.get(2), None);
+    }
+
+    #[test]
+    fn test_search_backward_finds_newest_match() {
+        let history = history_of(&["echo hello", "pwd", "echo world", "ls"]);
+        assert_eq!(history.search_backward("echo", 3), Some(2));
+        assert_eq!(history.search_backward("echo", 1), Some(0));
+        assert_eq!(history.search_backward("ls", 3), Some(3));
+    }
+
+    #[test]
+    fn test_search_backward_matches_substring() {
+        let history = history_of(&["git commit -m 'fix'", "cargo test"]);
+        assert_eq!(history.search_backward("mit", 1), Some(0));
+        assert_eq!(history.search_backward("test", 1), Some(1));
+    }
+
+    #[test]
+    fn test_search_backward_no_match() {
+        let history = history_of(&["echo hello", "pwd"]);
+        assert_eq!(history.search_backward("cargo", 1), None);
+        assert_eq!(history.search_backward("pwd", 0), None);
+        assert_eq!(History::new().search_backward("pwd", 0), None);
+    }
+
+    #[test]
+    fn test_search_backward_from_beyond_end() {
+        let history = history_of(&["echo hello", "pwd"]);
+        assert_eq!(history.search_backward("echo", usize::MAX), Some(0));
+    }
+
+    #[test]
+    fn test_search_forward_finds_oldest_match() {
+        let history = history_of(&["echo hello", "pwd", "echo world", "ls"]);
+        assert_eq!(history.search_forward("echo", 0), Some(0));
+        assert_eq!(history.search_forward("echo", 1), Some(2));
+        assert_eq!(history.search_forward("echo", 3), None);
+        assert_eq!(history.search_forward("echo", 10), None);
+    }
+
+    #[test]
+    fn test_suggest_newest_extension_of_prefix() {
+        let history = history_of(&["echo hello", "echo world", "ls", "echo"]);
+        assert_eq!(history.suggest("ec", None), Some("echo"));
+        assert_eq!(history.suggest("echo", None), Some("echo world"));
+        assert_eq!(history.suggest("echo h", None), Some("echo hello"));
+    }
+
+    #[test]
+    fn test_suggest_nothing_for_empty_or_complete_prefix() {
+        let history = history_of(&["echo hello", "ls"]);
+        assert_eq!(history.suggest("", None), None);
+        assert_eq!(history.suggest("ls", None), None);
+        assert_eq!(history.suggest("cargo", None), None);
+    }
+
+    #[test]
+    fn test_suggest_prefers_current_directory() {
+        let mut history = History::new();
+        history.append_in_directory("cargo test", Some(PathBuf::from("/projects/shell")));
+        history.append_in_directory("cargo build", Some(PathBuf::from("/projects/other")));
+        history.append("cargo run");
+
+        assert_eq!(history.suggest("cargo", Some(Path::new("/projects/shell"))), Some("cargo test"));
+        assert_eq!(history.suggest("cargo", Some(Path::new("/projects/other"))), Some("cargo build"));
+        assert_eq!(history.suggest("cargo", Some(Path::new("/tmp"))), Some("cargo run"));
+        assert_eq!(history.suggest("cargo", None), Some("cargo run"));
+    }
+
     #[test]
     fn test_read_from_file_basic() -> Result<(), Box<dyn std::error::Error>> {
         let mut temp_file = NamedTempFile::new()?;
@@ -266,4 +421,37 @@ mod tests {
         assert_eq!(history.get_last_command_by_idx(0), Some("echo \"test\" | grep test"));
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_multi_line_command_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_file = NamedTempFile::new()?;
+        let path = PathBuf::from(temp_file.path());
+        let mut history = history_of(&["echo hello", "for x in a b\ndo\n  echo $x\ndone", "pwd"]);
+        history.write_to_file(&path)?;
+
+        let mut read = History::new();
+        read.read_from_file(&path)?;
+        assert_eq!(read.len(), 3);
+        assert_eq!(read.get(1), Some("for x in a b\ndo\n  echo $x\ndone"));
+        assert_eq!(read.get(2), Some("pwd"));
+
+        history.append("if true\nthen echo yes\nfi");
+        history.append("ls");
+        history.append_to_file(&path)?;
+        let mut read = History::new();
+        read.read_from_file(&path)?;
+        assert_eq!(read.len(), 5);
+        assert_eq!(read.get(3), Some("if true\nthen echo yes\nfi"));
+        assert_eq!(read.get(4), Some("ls"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_line_commands_are_written_one_per_line() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_file = NamedTempFile::new()?;
+        let path = PathBuf::from(temp_file.path());
+        history_of(&["echo hello", "pwd"]).write_to_file(&path)?;
+        assert_eq!(std::fs::read_to_string(&path)?, "echo hello\npwd\n");
+        Ok(())
+    }
+}
@@ -1,8 +1,73 @@
 use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use crate::command::tokenizer::{self, Token, TokenKind};
+use crate::input::completers::{self, Completer, CompleterEnvironment};
+use crate::input::completion_spec::{self, CompletionRequest};
+use crate::input::matcher::Matcher;
+use crate::path;
+
+// Characters that the shell would otherwise read as quotes, operators or separators
+const CHARACTERS_TO_ESCAPE: &str = " \t\n\\'\"$`|&;<>()*?[]{}!#";
+
+/// What the word at the cursor is in the command line, which decides what it completes to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CompletionContext {
+    /// The first word of a command, after a pipe, a separator such as `;` or `&&`, or a reserved word such as `then`
+    Command,
+    Argument,
+    /// The file name after `>`, `>>`, `2>` or `<`
+    RedirectTarget,
+    /// The name after `$`
+    Variable,
+    /// The name after a leading `~`
+    UserName,
+}
+
+/// The word that ends at the cursor, as the shell reads it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CompletionWord {
+    /// Byte position of the word in the line
+    pub(crate) start: usize,
+    /// The word after quote removal
+    pub(crate) value: String,
+    /// The quote that is still open at the cursor
+    pub(crate) open_quote: Option<char>,
+    pub(crate) context: CompletionContext,
+    /// The name of the command that the word belongs to
+    pub(crate) command_name: Option<String>,
+    /// The arguments of the command before the word, without redirections
+    pub(crate) arguments: Vec<String>,
+    pub(crate) previous_word: String,
+}
+
+/// How matches go into the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct CompletionStyle {
+    /// Insert the matches as they are instead of quoting them like file names
+    pub(crate) verbatim: bool,
+    /// No space after a unique match
+    pub(crate) no_space: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Completions {
+    pub(crate) matches: Vec<String>,
+    pub(crate) style: CompletionStyle,
+}
+
+impl From<Vec<String>> for Completions {
+    fn from(matches: Vec<String>) -> Self {
+        Completions { matches, style: CompletionStyle::default() }
+    }
+}
 
 pub struct AutoCompletion {
     candidates: HashSet<String>,
     dynamic_completion: Box<dyn Fn(&str) -> Vec<String>>,
+    completers: Vec<Box<dyn Completer>>,
 }
 
 impl AutoCompletion {
@@ -18,7 +83,8 @@ impl AutoCompletion {
     ) -> Self {
         Self {
             candidates: candidates.into_iter().map(|s| s.to_string()).collect(),
-            dynamic_completion
+            dynamic_completion,
+            completers: completers::builtin_completers(),
         }
     }
 
@@ -27,64 +93,267 @@ impl AutoCompletion {
             return Vec::new();
         }
 
-        let mut seen = HashSet::new();
-        let mut matches: Vec<String> = self
-            .candidates
-            .iter()
-            .filter(|candidate| candidate.starts_with(partial))
-            .filter(|candidate| seen.insert((*candidate).clone()))
-            .cloned()
-            .collect();
-
-        let dynamic_matches = (self.dynamic_completion)(partial);
-        for m in dynamic_matches {
-            if seen.insert(m.clone()) {
-                matches.push(m);
+        let candidates = self.candidates.iter().cloned().chain((self.dynamic_completion)(partial));
+        Matcher::current().rank(partial, candidates)
+    }
+
+    /// Completes the word at the cursor from the source for its context. The arguments of a command complete
+    /// from its spec from the `complete` builtin, or else from the completer shipped for it.
+    pub(crate) fn complete_word(&self, word: &CompletionWord, line: &str, cursor: usize) -> Completions {
+        match word.context {
+            CompletionContext::Command if word.value.contains('/') => complete_filename(&word.value, true).into(),
+            CompletionContext::Command => self.complete(&word.value).into(),
+            CompletionContext::Argument => {
+                let command_name = word.command_name.as_deref().unwrap_or_default();
+                let request = CompletionRequest {
+                    command_name,
+                    word: &word.value,
+                    previous_word: &word.previous_word,
+                    arguments: &word.arguments,
+                    line,
+                    point: line[..cursor].chars().count(),
+                };
+                if let Some(spec) = completion_spec::get(command_name) {
+                    Completions { matches: spec.generate(&request), style: spec.style() }
+                } else if let Some(completer) = self.completers.iter().find(|completer| completer.completes(command_name)) {
+                    completer.complete(&request, &CompleterEnvironment::current())
+                } else {
+                    complete_filename(&word.value, false).into()
+                }
             }
+            CompletionContext::RedirectTarget => complete_filename(&word.value, false).into(),
+            CompletionContext::Variable => complete_variable_name(&word.value).into(),
+            CompletionContext::UserName => complete_user_name(&word.value).into(),
         }
-
-        matches.sort();
-        matches
     }
 
+    #[cfg(test)]
     pub fn find_common_prefix(&self, partial: &str) -> Option<String> {
         let matches = self.complete(partial);
-        let common_len = self.find_common_length(&matches);
-        
-        common_len.into_iter().flat_map(|len| {
-            if len >= partial.len() {
-                matches.first().map(|s| s[..len].to_string())
-            } else {
-                None
-            }
-        }).next()
+        self.common_prefix(&matches, partial).filter(|prefix| prefix.len() >= partial.len())
+    }
+
+    /// The longest prefix shared by all matches, in their casing when case is ignored.
+    pub(crate) fn common_prefix(&self, matches: &[String], typed: &str) -> Option<String> {
+        Matcher::current().common_prefix(matches, typed)
     }
+}
 
-    fn find_common_length(&self, matches: &Vec<String>) -> Option<usize> {
-        if matches.is_empty() {
-            return None;
+/// Finds the word that ends at the cursor and its context with the shell's tokenizer.
+pub(crate) fn word_before_cursor(text_before_cursor: &str) -> CompletionWord {
+    let tokens = tokenizer::tokenize(text_before_cursor);
+    let is_current_word = |token: &Token| token.kind == TokenKind::Word && token.span.end == text_before_cursor.len();
+    let earlier_tokens = match tokens.tokens.split_last() {
+        Some((token, previous)) if is_current_word(token) => previous,
+        _ => &tokens.tokens[..],
+    };
+    // The command words after a pipe or separator start another command, so the nearest one is the current command
+    let command_start = earlier_tokens.iter().rposition(|token| token.is_command || token.kind == TokenKind::Pipe);
+    let command_name = command_start.map(|index| &earlier_tokens[index])
+        .filter(|token| token.is_command)
+        .map(|token| token.value.clone());
+    let mut arguments = Vec::new();
+    if command_name.is_some() {
+        let mut is_redirect_target = false;
+        for token in &earlier_tokens[command_start.unwrap_or(0) + 1..] {
+            if token.kind == TokenKind::Word && !is_redirect_target {
+                arguments.push(token.value.clone());
+            }
+            is_redirect_target = is_redirection(token);
         }
-        if matches.len() == 1 {
-            return matches.first().map(|s| s.len());
+    }
+    let previous_word = earlier_tokens.last().map(|token| token.value.clone()).unwrap_or_default();
+    let (mut start, mut context) = match tokens.tokens.split_last() {
+        Some((token, previous)) if is_current_word(token) => {
+            let context = if token.is_command {
+                CompletionContext::Command
+            } else if previous.last().map(is_redirection).unwrap_or(false) {
+                CompletionContext::RedirectTarget
+            } else {
+                CompletionContext::Argument
+            };
+            (token.span.start, context)
         }
-        let first = &matches[0];
-        let mut common_len = first.len();
-        for match_str in &matches[1..] {
-            common_len = common_len.min(self.find_common_length_of_two_strings(first, match_str));
+        last => {
+            let context = if tokens.next_word_is_command {
+                CompletionContext::Command
+            } else if tokens.next_word_is_redirect_target || last.map(|(token, _)| is_redirection(token)).unwrap_or(false) {
+                CompletionContext::RedirectTarget
+            } else {
+                CompletionContext::Argument
+            };
+            (text_before_cursor.len(), context)
         }
-        Some(common_len)
+    };
+    // The tokenizer leaves input redirections in the word
+    if text_before_cursor[start..].starts_with('<') {
+        start += 1;
+        context = CompletionContext::RedirectTarget;
+    }
+
+    let raw_word = &text_before_cursor[start..];
+    let (value, open_quote, variable_start) = unquote(raw_word);
+    if let Some(variable_start) = variable_start {
+        return CompletionWord {
+            start: start + variable_start,
+            value: raw_word[variable_start..].to_string(),
+            open_quote: None,
+            context: CompletionContext::Variable,
+            command_name,
+            arguments,
+            previous_word,
+        };
+    }
+    if raw_word.starts_with('~') && !raw_word.contains('/') {
+        context = CompletionContext::UserName;
     }
+    CompletionWord { start, value, open_quote, context, command_name, arguments, previous_word }
+}
 
-    fn find_common_length_of_two_strings(&self, s1: &str, s2: &str) -> usize {
-        let mut len = 0;
-        for (c1, c2) in s1.chars().zip(s2.chars()) {
-            if c1 == c2 {
-                len += c1.len_utf8();
+fn is_redirection(token: &Token) -> bool {
+    token.kind == TokenKind::Redirect || (token.kind == TokenKind::Word && token.value == "<")
+}
+
+/// Removes the quotes and backslashes from a word, and finds the quote that is left open and the position of
+/// a variable name that the word ends with.
+fn unquote(raw_word: &str) -> (String, Option<char>, Option<usize>) {
+    let mut value = String::new();
+    let mut open_quote = None;
+    let mut is_escaped_character = false;
+    let mut variable_start = None;
+    for (position, ch) in raw_word.char_indices() {
+        if variable_start.is_some() && !(ch.is_alphanumeric() || ch == '_') {
+            variable_start = None;
+        }
+        if is_escaped_character {
+            is_escaped_character = false;
+            if open_quote == Some('"') && !"\"\\$`".contains(ch) {
+                value.push('\\');
+            }
+            value.push(ch);
+        } else if open_quote == Some('\'') {
+            if ch == '\'' { open_quote = None } else { value.push(ch) }
+        } else if ch == '\\' {
+            is_escaped_character = true;
+        } else if ch == '$' {
+            variable_start = Some(position + 1);
+            value.push(ch);
+        } else if open_quote == Some('"') {
+            if ch == '"' { open_quote = None } else { value.push(ch) }
+        } else if ch == '\'' || ch == '"' {
+            open_quote = Some(ch);
+        } else {
+            value.push(ch);
+        }
+    }
+    (value, open_quote, variable_start)
+}
+
+/// Completes a file name, keeping the directory part and a leading `~` as typed.
+/// Directories end with `/`, and hidden files are only offered when the name starts with a dot.
+pub(crate) fn complete_filename(partial: &str, executables_only: bool) -> Vec<String> {
+    complete_filename_in(Path::new("."), partial, executables_only)
+}
+
+/// Completes a file name like `complete_filename`, with relative names looked up in the given directory.
+pub(crate) fn complete_filename_in(base: &Path, partial: &str, executables_only: bool) -> Vec<String> {
+    matching_filenames(base, partial, executables_only, &Matcher::current())
+}
+
+fn matching_filenames(base: &Path, partial: &str, executables_only: bool, matcher: &Matcher) -> Vec<String> {
+    let (directory, prefix) = match partial.rfind('/') {
+        Some(index) => partial.split_at(index + 1),
+        None => ("", partial),
+    };
+    let Ok(entries) = fs::read_dir(base.join(expand_directory(directory))) else {
+        return Vec::new();
+    };
+    let matches = entries.flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !matcher.matches(prefix, &name) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            // Symbolic links are completed like their targets
+            let metadata = fs::metadata(entry.path()).or_else(|_| entry.metadata()).ok()?;
+            if metadata.is_dir() {
+                Some(format!("{}{}/", directory, name))
+            } else if !executables_only || metadata.permissions().mode() & 0o111 != 0 {
+                Some(format!("{}{}", directory, name))
             } else {
-                break;
+                None
             }
+        });
+    matcher.rank_by(prefix, matches, |path| display_name(path).trim_end_matches('/'))
+}
+
+/// Completes `~user` to the user's home directory.
+fn complete_user_name(partial: &str) -> Vec<String> {
+    let user = partial.strip_prefix('~').unwrap_or(partial);
+    Matcher::current().rank_by(user, path::user_names().into_iter().map(|name| format!("~{}/", name)), |path| {
+        path.trim_start_matches('~').trim_end_matches('/')
+    })
+}
+
+/// Completes the name of an environment variable.
+fn complete_variable_name(partial: &str) -> Vec<String> {
+    Matcher::current().rank(partial, env::vars_os().filter_map(|(name, _)| name.into_string().ok()))
+}
+
+fn expand_directory(directory: &str) -> PathBuf {
+    if directory.is_empty() {
+        return PathBuf::from(".");
+    }
+    if let Some(rest) = directory.strip_prefix('~') {
+        let (user, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        if let Some(home) = path::tilde_directory(user) {
+            return home.join(rest);
         }
-        return len;
+    }
+    PathBuf::from(directory)
+}
+
+/// The text that replaces the word for a match. A unique match is finished with the closing quote and a space,
+/// unless it is a directory or the style asks for no space.
+pub(crate) fn replacement_text(completion: &str, word: &CompletionWord, style: CompletionStyle, is_unique: bool) -> String {
+    let is_finished = is_unique && !completion.ends_with('/');
+    let mut text = if style.verbatim {
+        let quote: String = word.open_quote.into_iter().collect();
+        let closing_quote = if is_finished { quote.as_str() } else { "" };
+        format!("{}{}{}", quote, completion, closing_quote)
+    } else {
+        quote_completion(completion, word.open_quote, is_finished)
+    };
+    if is_finished && !style.no_space {
+        text.push(' ');
+    }
+    text
+}
+
+/// Quotes a completion for the line the way the word was started: inside the open quote,
+/// or with backslashes before special characters. A finished word gets its closing quote.
+pub(crate) fn quote_completion(completion: &str, open_quote: Option<char>, is_finished: bool) -> String {
+    let closing_quote = |quote: char| if is_finished { quote.to_string() } else { String::new() };
+    match open_quote {
+        Some('\'') => format!("'{}{}", completion.replace('\'', "'\\''"), closing_quote('\'')),
+        Some(quote) => {
+            let escaped: String = completion.chars()
+                .flat_map(|ch| if "\"\\$`".contains(ch) { vec!['\\', ch] } else { vec![ch] })
+                .collect();
+            format!("{}{}{}", quote, escaped, closing_quote(quote))
+        }
+        None => completion.chars()
+            .flat_map(|ch| if CHARACTERS_TO_ESCAPE.contains(ch) { vec!['\\', ch] } else { vec![ch] })
+            .collect(),
+    }
+}
+
+/// The part of a completion shown in the list of matches: the file name without its directory.
+pub(crate) fn display_name(completion: &str) -> &str {
+    let name = completion.strip_suffix('/').unwrap_or(completion);
+    match name.rfind('/') {
+        Some(index) => &completion[index + 1..],
+        None => completion,
     }
 }
 
@@ -206,4 +475,105 @@ mod tests {
         );
         assert_eq!(autocomplete.complete(""), Vec::<String>::new());
     }
-}
\ No newline at end of file
+
+    fn context(text: &str) -> (String, CompletionContext) {
+        let word = word_before_cursor(text);
+        (word.value, word.context)
+    }
+
+    #[test]
+    fn test_word_before_cursor() {
+        let word = word_before_cursor("cat my\\ fi");
+        assert_eq!(word, CompletionWord {
+            start: 4,
+            value: "my fi".to_string(),
+            open_quote: None,
+            context: CompletionContext::Argument,
+            command_name: Some("cat".to_string()),
+            arguments: Vec::new(),
+            previous_word: "cat".to_string(),
+        });
+        let word = word_before_cursor("cat >'my fi");
+        assert_eq!((word.start, word.value.as_str(), word.open_quote, word.context), (5, "my fi", Some('\''), CompletionContext::RedirectTarget));
+        let word = word_before_cursor("echo \"$HO");
+        assert_eq!((word.start, word.value.as_str(), word.open_quote, word.context), (7, "HO", None, CompletionContext::Variable));
+        let word = word_before_cursor("ls | deploy >log --env pro");
+        assert_eq!((word.command_name.as_deref(), word.previous_word.as_str()), (Some("deploy"), "--env"));
+        assert_eq!(word.arguments, vec!["--env"]);
+    }
+
+    #[test]
+    fn test_completion_context() {
+        assert_eq!(context(""), (String::new(), CompletionContext::Command));
+        assert_eq!(context("cat ec"), ("ec".to_string(), CompletionContext::Argument));
+        assert_eq!(context("echo hello | ca"), ("ca".to_string(), CompletionContext::Command));
+        assert_eq!(context("cd /tmp; l"), ("l".to_string(), CompletionContext::Command));
+        assert_eq!(context("make && "), (String::new(), CompletionContext::Command));
+        assert_eq!(context("if true; then e"), ("e".to_string(), CompletionContext::Command));
+        assert_eq!(context("ls 2> "), (String::new(), CompletionContext::RedirectTarget));
+        assert_eq!(context("ls >>ou"), ("ou".to_string(), CompletionContext::RedirectTarget));
+        assert_eq!(context("sort <in"), ("in".to_string(), CompletionContext::RedirectTarget));
+        assert_eq!(context("sort < in"), ("in".to_string(), CompletionContext::RedirectTarget));
+        assert_eq!(context("echo '$HO"), ("$HO".to_string(), CompletionContext::Argument));
+        assert_eq!(context("ls ~ro"), ("~ro".to_string(), CompletionContext::UserName));
+        assert_eq!(context("ls ~/"), ("~/".to_string(), CompletionContext::Argument));
+    }
+
+    #[test]
+    fn test_complete_filename() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let prefix = format!("{}/", directory.path().display());
+        std::fs::write(directory.path().join("notes.txt"), "")?;
+        std::fs::write(directory.path().join(".hidden"), "")?;
+        std::fs::create_dir(directory.path().join("nested"))?;
+
+        assert_eq!(complete_filename(&prefix, false), vec![format!("{}nested/", prefix), format!("{}notes.txt", prefix)]);
+        assert_eq!(complete_filename(&format!("{}.", prefix), false), vec![format!("{}.hidden", prefix)]);
+        // Command names only complete to directories and executables
+        assert_eq!(complete_filename(&format!("{}n", prefix), true), vec![format!("{}nested/", prefix)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_filename_ignoring_case_and_fuzzy() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let prefix = format!("{}/", directory.path().display());
+        std::fs::write(directory.path().join("Makefile"), "")?;
+        std::fs::write(directory.path().join("cmake.txt"), "")?;
+        std::fs::write(directory.path().join("main.rs"), "")?;
+
+        let ignore_case = Matcher { ignore_case: true, fuzzy: false };
+        assert_eq!(matching_filenames(Path::new("."), &format!("{}ma", prefix), false, &ignore_case), vec![format!("{}main.rs", prefix), format!("{}Makefile", prefix)]);
+        // Prefix matches come before the names that only contain the typed text
+        let fuzzy = Matcher { ignore_case: true, fuzzy: true };
+        assert_eq!(matching_filenames(Path::new("."), &format!("{}mak", prefix), false, &fuzzy), vec![format!("{}Makefile", prefix), format!("{}cmake.txt", prefix)]);
+        assert_eq!(matching_filenames(Path::new("."), &format!("{}mrs", prefix), false, &fuzzy), vec![format!("{}main.rs", prefix)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_user_names() {
+        assert!(complete_user_name("~roo").contains(&"~root/".to_string()));
+    }
+
+    #[test]
+    fn test_complete_variable_name() {
+        env::set_var("COMPLETION_TEST_VARIABLE", "1");
+        assert_eq!(complete_variable_name("COMPLETION_TEST_V"), vec!["COMPLETION_TEST_VARIABLE"]);
+    }
+
+    #[test]
+    fn test_quote_completion() {
+        assert_eq!(quote_completion("my file's", None, true), "my\\ file\\'s");
+        assert_eq!(quote_completion("~/a&b", None, true), "~/a\\&b");
+        assert_eq!(quote_completion("my file's", Some('\''), true), "'my file'\\''s'");
+        assert_eq!(quote_completion("a \"b\"", Some('"'), false), "\"a \\\"b\\\"");
+    }
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name("src/input/"), "input/");
+        assert_eq!(display_name("src/main.rs"), "main.rs");
+        assert_eq!(display_name("echo"), "echo");
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::path::Path;
+use crate::input::autocompletion::Completions;
+use crate::input::completers::{matching_words, subcommand, Completer, CompleterEnvironment};
+use crate::input::completion_spec::CompletionRequest;
+
+const SUBCOMMANDS: [&str; 22] = [
+    "add", "bench", "build", "check", "clean", "clippy", "doc", "fix", "fmt", "init", "install", "metadata",
+    "new", "publish", "remove", "run", "search", "test", "tree", "uninstall", "update", "vendor",
+];
+const OPTIONS: [&str; 14] = [
+    "--all-features", "--all-targets", "--bin", "--bins", "--example", "--examples", "--features",
+    "--help", "--manifest-path", "--package", "--quiet", "--release", "--verbose", "--workspace",
+];
+
+/// Completes cargo subcommands and options, and the targets of `--bin` and `--example` from the manifest.
+pub(crate) struct CargoCompleter;
+
+impl Completer for CargoCompleter {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn complete(&self, request: &CompletionRequest, environment: &CompleterEnvironment) -> Completions {
+        match request.previous_word {
+            "--bin" | "--example" => {
+                let targets = environment.find_upwards("Cargo.toml")
+                    .map(|manifest| Targets::read(&manifest))
+                    .unwrap_or_default();
+                let names = if request.previous_word == "--bin" { targets.binaries } else { targets.examples };
+                matching_words(&names, request.word)
+            }
+            _ if request.word.starts_with('-') => matching_words(&OPTIONS, request.word),
+            _ if subcommand(request).is_none() => matching_words(&SUBCOMMANDS, request.word),
+            _ => environment.complete_filename(request.word).into(),
+        }
+    }
+}
+
+/// The binary and example targets of a package: those declared in `Cargo.toml` and those found by the
+/// conventional layout, `src/main.rs`, `src/bin/*.rs`, `src/bin/*/main.rs` and `examples/*.rs`.
+#[derive(Debug, Default, PartialEq)]
+struct Targets {
+    binaries: Vec<String>,
+    examples: Vec<String>,
+}
+
+impl Targets {
+    fn read(manifest: &Path) -> Self {
+        let mut targets = Targets::default();
+        let mut package_name = None;
+        let mut section = String::new();
+        for line in fs::read_to_string(manifest).unwrap_or_default().lines() {
+            let line = line.trim();
+            if line.starts_with('[') {
+                section = line.trim_matches(|ch| ch == '[' || ch == ']').trim().to_string();
+            } else if let Some(name) = string_value(line, "name") {
+                match section.as_str() {
+                    "package" => package_name = Some(name),
+                    "bin" => targets.binaries.push(name),
+                    "example" => targets.examples.push(name),
+                    _ => (),
+                }
+            }
+        }
+
+        let Some(root) = manifest.parent() else {
+            return targets;
+        };
+        if let Some(package_name) = package_name.filter(|_| root.join("src/main.rs").exists()) {
+            targets.binaries.push(package_name);
+        }
+        targets.binaries.extend(target_names(&root.join("src/bin")));
+        targets.examples.extend(target_names(&root.join("examples")));
+        targets
+    }
+}
+
+/// The value of a `key = "value"` line.
+fn string_value(line: &str, key: &str) -> Option<String> {
+    let (name, value) = line.split_once('=')?;
+    if name.trim() != key {
+        return None;
+    }
+    let value = value.trim();
+    let quote = value.chars().next().filter(|ch| *ch == '"' || *ch == '\'')?;
+    value[1..].split(quote).next().map(|value| value.to_string())
+}
+
+/// Targets in a directory: `name.rs` files and `name/main.rs` directories.
+fn target_names(directory: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries.flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.is_dir() && path.join("main.rs").exists() {
+                entry.file_name().into_string().ok()
+            } else if path.extension().map(|extension| extension == "rs").unwrap_or(false) {
+                path.file_stem()?.to_str().map(|name| name.to_string())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::completers::test_environment;
+
+    fn create_package() -> Result<tempfile::TempDir, anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        fs::write(directory.path().join("Cargo.toml"), concat!(
+            "[package]\n",
+            "name = \"shell\"\n",
+            "\n",
+            "[[bin]]\n",
+            "name = 'admin'\n",
+            "path = \"tools/admin.rs\"\n",
+            "\n",
+            "[[example]]\n",
+            "name = \"demo\"\n",
+            "\n",
+            "[dependencies]\n",
+            "name = \"not-a-target\"\n",
+        ))?;
+        fs::create_dir_all(directory.path().join("src/bin/server"))?;
+        fs::create_dir_all(directory.path().join("examples"))?;
+        fs::write(directory.path().join("src/main.rs"), "")?;
+        fs::write(directory.path().join("src/bin/client.rs"), "")?;
+        fs::write(directory.path().join("src/bin/server/main.rs"), "")?;
+        fs::write(directory.path().join("examples/walkthrough.rs"), "")?;
+        Ok(directory)
+    }
+
+    #[test]
+    fn test_read_targets() -> Result<(), anyhow::Error> {
+        let directory = create_package()?;
+        let mut targets = Targets::read(&directory.path().join("Cargo.toml"));
+        targets.binaries.sort();
+        targets.examples.sort();
+        assert_eq!(targets, Targets {
+            binaries: vec!["admin".to_string(), "client".to_string(), "server".to_string(), "shell".to_string()],
+            examples: vec!["demo".to_string(), "walkthrough".to_string()],
+        });
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_bin_targets() -> Result<(), anyhow::Error> {
+        let directory = create_package()?;
+        let environment = test_environment(&directory.path().join("src"));
+        let arguments = vec!["run".to_string(), "--bin".to_string()];
+        let request = CompletionRequest { command_name: "cargo", word: "s", previous_word: "--bin", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(CargoCompleter.complete(&request, &environment).matches, vec!["server", "shell"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_subcommands_and_options() {
+        let environment = CompleterEnvironment::current();
+        let request = CompletionRequest { command_name: "cargo", word: "b", ..CompletionRequest::default() };
+        assert_eq!(CargoCompleter.complete(&request, &environment).matches, vec!["bench", "build"]);
+
+        let arguments = vec!["build".to_string()];
+        let request = CompletionRequest { command_name: "cargo", word: "--rel", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(CargoCompleter.complete(&request, &environment).matches, vec!["--release"]);
+    }
+
+    #[test]
+    fn test_complete_files_in_current_directory() -> Result<(), anyhow::Error> {
+        let directory = create_package()?;
+        let environment = test_environment(&directory.path().join("src"));
+        let arguments = vec!["run".to_string(), "--".to_string()];
+        let request = CompletionRequest { command_name: "cargo", word: "bin/c", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(CargoCompleter.complete(&request, &environment).matches, vec!["bin/client.rs"]);
+        Ok(())
+    }
+}
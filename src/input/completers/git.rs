@@ -0,0 +1,159 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::input::autocompletion::Completions;
+use crate::input::completers::{matching_words, subcommand, Completer, CompleterEnvironment};
+use crate::input::completion_spec::CompletionRequest;
+
+const SUBCOMMANDS: [&str; 31] = [
+    "add", "bisect", "blame", "branch", "checkout", "cherry-pick", "clean", "clone", "commit", "config", "diff",
+    "fetch", "grep", "init", "log", "merge", "mv", "pull", "push", "rebase", "remote", "reset", "restore",
+    "revert", "rm", "show", "stash", "status", "switch", "tag", "worktree",
+];
+// Subcommands whose arguments are mostly branch or tag names rather than files
+const REF_SUBCOMMANDS: [&str; 13] = [
+    "branch", "checkout", "cherry-pick", "diff", "log", "merge", "push", "rebase", "reset", "revert", "show", "switch", "tag",
+];
+const REF_DIRECTORIES: [&str; 3] = ["refs/heads", "refs/tags", "refs/remotes"];
+
+/// Completes git subcommands, and branch and tag names for the subcommands that take them.
+pub(crate) struct GitCompleter;
+
+impl Completer for GitCompleter {
+    fn name(&self) -> &'static str {
+        "git"
+    }
+
+    fn complete(&self, request: &CompletionRequest, environment: &CompleterEnvironment) -> Completions {
+        match subcommand(request) {
+            None => matching_words(&SUBCOMMANDS, request.word),
+            Some(subcommand) if REF_SUBCOMMANDS.contains(&subcommand) && !request.word.starts_with('-') => {
+                let refs = find_git_directory(environment).map(|git_directory| ref_names(&git_directory)).unwrap_or_default();
+                matching_words(&refs, request.word)
+            }
+            Some(_) => environment.complete_filename(request.word).into(),
+        }
+    }
+}
+
+/// The `.git` directory of the repository, following the `gitdir:` file of a worktree or submodule.
+fn find_git_directory(environment: &CompleterEnvironment) -> Option<PathBuf> {
+    let dot_git = environment.find_upwards(".git")?;
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let contents = fs::read_to_string(&dot_git).ok()?;
+    let git_directory = contents.trim().strip_prefix("gitdir:")?.trim();
+    Some(dot_git.parent()?.join(git_directory))
+}
+
+/// Branch, tag and remote branch names from the loose refs and `packed-refs`; worktrees keep them in the common directory.
+fn ref_names(git_directory: &Path) -> Vec<String> {
+    let common_directory = fs::read_to_string(git_directory.join("commondir"))
+        .map(|common_directory| git_directory.join(common_directory.trim()))
+        .unwrap_or_else(|_| git_directory.to_path_buf());
+    let mut names = Vec::new();
+    for directory in REF_DIRECTORIES {
+        collect_loose_refs(&common_directory.join(directory), "", &mut names);
+    }
+    if let Ok(packed_refs) = fs::read_to_string(common_directory.join("packed-refs")) {
+        names.extend(packed_refs.lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .filter_map(|line| line.split_whitespace().nth(1))
+            .filter_map(|name| REF_DIRECTORIES.iter().find_map(|directory| name.strip_prefix(directory)?.strip_prefix('/')))
+            .map(|name| name.to_string()));
+    }
+    names.retain(|name| !name.ends_with("/HEAD"));
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn collect_loose_refs(directory: &Path, prefix: &str, names: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if entry.path().is_dir() {
+            collect_loose_refs(&entry.path(), &format!("{}{}/", prefix, name), names);
+        } else {
+            names.push(format!("{}{}", prefix, name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::completers::test_environment;
+
+    fn request<'a>(arguments: &'a [String], word: &'a str) -> CompletionRequest<'a> {
+        CompletionRequest { command_name: "git", word, arguments, ..CompletionRequest::default() }
+    }
+
+    fn create_repository() -> Result<tempfile::TempDir, anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let git_directory = directory.path().join(".git");
+        fs::create_dir_all(git_directory.join("refs/heads/feature"))?;
+        fs::create_dir_all(git_directory.join("refs/tags"))?;
+        fs::create_dir_all(git_directory.join("refs/remotes/origin"))?;
+        fs::write(git_directory.join("refs/heads/main"), "")?;
+        fs::write(git_directory.join("refs/heads/feature/login"), "")?;
+        fs::write(git_directory.join("refs/remotes/origin/HEAD"), "")?;
+        fs::write(git_directory.join("refs/remotes/origin/main"), "")?;
+        fs::write(git_directory.join("packed-refs"), concat!(
+            "# pack-refs with: peeled fully-peeled sorted\n",
+            "1111 refs/heads/fix-typo\n",
+            "2222 refs/tags/v1.0\n",
+            "^3333\n",
+        ))?;
+        fs::create_dir(directory.path().join("src"))?;
+        Ok(directory)
+    }
+
+    #[test]
+    fn test_complete_subcommands() {
+        let environment = CompleterEnvironment::current();
+        assert_eq!(GitCompleter.complete(&request(&[], "che"), &environment).matches, vec!["checkout", "cherry-pick"]);
+    }
+
+    #[test]
+    fn test_complete_refs() -> Result<(), anyhow::Error> {
+        let directory = create_repository()?;
+        let environment = test_environment(&directory.path().join("src"));
+        let checkout = vec!["checkout".to_string()];
+        assert_eq!(
+            GitCompleter.complete(&request(&checkout, ""), &environment).matches,
+            vec!["feature/login", "fix-typo", "main", "origin/main", "v1.0"],
+        );
+        assert_eq!(GitCompleter.complete(&request(&checkout, "f"), &environment).matches, vec!["feature/login", "fix-typo"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_refs_of_worktree() -> Result<(), anyhow::Error> {
+        let repository = create_repository()?;
+        let worktree = tempfile::tempdir()?;
+        let worktree_git_directory = repository.path().join(".git/worktrees/other");
+        fs::create_dir_all(&worktree_git_directory)?;
+        fs::write(worktree_git_directory.join("commondir"), "../..\n")?;
+        fs::write(worktree.path().join(".git"), format!("gitdir: {}\n", worktree_git_directory.display()))?;
+
+        let switch = vec!["switch".to_string()];
+        let completions = GitCompleter.complete(&request(&switch, "ma"), &test_environment(worktree.path()));
+        assert_eq!(completions.matches, vec!["main"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_files_in_current_directory() -> Result<(), anyhow::Error> {
+        let directory = create_repository()?;
+        fs::write(directory.path().join("src/main.rs"), "")?;
+        let environment = test_environment(&directory.path().join("src"));
+        let add = vec!["add".to_string()];
+        assert_eq!(GitCompleter.complete(&request(&add, "ma"), &environment).matches, vec!["main.rs"]);
+        Ok(())
+    }
+}
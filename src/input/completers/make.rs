@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::input::autocompletion::Completions;
+use crate::input::completers::{matching_words, Completer, CompleterEnvironment};
+use crate::input::completion_spec::CompletionRequest;
+
+// The makefiles GNU make reads when none is given with -f, in its order
+const MAKEFILE_NAMES: [&str; 3] = ["GNUmakefile", "makefile", "Makefile"];
+
+/// Completes the targets of the makefile, honoring `-C directory` and `-f file`.
+pub(crate) struct MakeCompleter;
+
+impl Completer for MakeCompleter {
+    fn name(&self) -> &'static str {
+        "make"
+    }
+
+    fn complete(&self, request: &CompletionRequest, environment: &CompleterEnvironment) -> Completions {
+        // Make reads its makefile and names its files from the -C directory
+        let environment = CompleterEnvironment { current_directory: make_directory(request, environment), ..environment.clone() };
+        if matches!(request.previous_word, "-C" | "-f" | "--directory" | "--file") || request.word.contains('/') {
+            return environment.complete_filename(request.word).into();
+        }
+        let targets = find_makefile(request, &environment)
+            .and_then(|makefile| fs::read_to_string(makefile).ok())
+            .map(|contents| targets(&contents))
+            .unwrap_or_default();
+        matching_words(&targets, request.word)
+    }
+}
+
+/// The directory make changes to, each `-C` relative to the one before.
+fn make_directory(request: &CompletionRequest, environment: &CompleterEnvironment) -> PathBuf {
+    let mut directory = environment.current_directory.clone();
+    let mut arguments = request.arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if matches!(argument.as_str(), "-C" | "--directory") {
+            let Some(next) = arguments.next() else {
+                break;
+            };
+            directory = directory.join(next);
+        }
+    }
+    directory
+}
+
+fn find_makefile(request: &CompletionRequest, environment: &CompleterEnvironment) -> Option<PathBuf> {
+    let mut makefile = None;
+    let mut arguments = request.arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if matches!(argument.as_str(), "-f" | "--file") {
+            makefile = arguments.next().cloned();
+        }
+    }
+    match makefile {
+        Some(makefile) => Some(environment.resolve(&makefile)),
+        None => MAKEFILE_NAMES.iter().map(|name| environment.resolve(name)).find(|path| path.exists()),
+    }
+}
+
+/// The explicit targets of rule lines, leaving out special targets like `.PHONY` and pattern rules.
+fn targets(contents: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    for line in contents.lines() {
+        if line.starts_with('\t') || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let Some((before, after)) = line.split_once(':') else {
+            continue;
+        };
+        // Variable assignments such as `CC := gcc` or `URL = http://...`
+        if after.starts_with('=') || before.contains('=') {
+            continue;
+        }
+        targets.extend(before.split_whitespace()
+            .filter(|target| !target.starts_with('.') && !target.contains(['%', '$']))
+            .map(|target| target.to_string()));
+    }
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::completers::test_environment;
+
+    const MAKEFILE: &str = concat!(
+        "CC := gcc\n",
+        "URL = https://example.com\n",
+        ".PHONY: all clean\n",
+        "\n",
+        "all: build docs\n",
+        "build docs: $(SOURCES)\n",
+        "\t$(CC) -o app main.c\n",
+        "%.o: %.c\n",
+        "clean::\n",
+        "# deploy: not a target\n",
+    );
+
+    #[test]
+    fn test_targets() {
+        assert_eq!(targets(MAKEFILE), vec!["all", "build", "docs", "clean"]);
+    }
+
+    #[test]
+    fn test_complete_targets() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        fs::write(directory.path().join("Makefile"), MAKEFILE)?;
+        fs::create_dir(directory.path().join("web"))?;
+        fs::write(directory.path().join("web/Makefile"), "serve:\nstop:\n")?;
+        let environment = test_environment(directory.path());
+
+        let request = CompletionRequest { command_name: "make", word: "b", ..CompletionRequest::default() };
+        assert_eq!(MakeCompleter.complete(&request, &environment).matches, vec!["build"]);
+
+        let arguments = vec!["-C".to_string(), "web".to_string()];
+        let request = CompletionRequest { command_name: "make", word: "s", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(MakeCompleter.complete(&request, &environment).matches, vec!["serve", "stop"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_files_in_make_directory() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        fs::write(directory.path().join("Makefile"), MAKEFILE)?;
+        fs::create_dir(directory.path().join("web"))?;
+        fs::write(directory.path().join("web/release.mk"), "publish:\n")?;
+        let environment = test_environment(directory.path());
+
+        let arguments = vec!["-C".to_string(), "web".to_string(), "-f".to_string()];
+        let request = CompletionRequest { command_name: "make", word: "re", previous_word: "-f", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(MakeCompleter.complete(&request, &environment).matches, vec!["release.mk"]);
+
+        let arguments = vec!["-C".to_string(), "web".to_string(), "-f".to_string(), "release.mk".to_string()];
+        let request = CompletionRequest { command_name: "make", word: "p", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(MakeCompleter.complete(&request, &environment).matches, vec!["publish"]);
+
+        let arguments = vec!["-C".to_string()];
+        let request = CompletionRequest { command_name: "make", word: "w", previous_word: "-C", arguments: &arguments, ..CompletionRequest::default() };
+        assert_eq!(MakeCompleter.complete(&request, &environment).matches, vec!["web/"]);
+        Ok(())
+    }
+}
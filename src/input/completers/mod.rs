@@ -0,0 +1,112 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use crate::input::autocompletion::{self, CompletionStyle, Completions};
+use crate::input::completion_spec::CompletionRequest;
+use crate::input::matcher::Matcher;
+use crate::path;
+
+mod cargo;
+mod git;
+mod make;
+mod ssh;
+
+/// A completion source shipped with the shell for the arguments of a command; a spec defined with the
+/// `complete` builtin for the same command takes precedence.
+pub(crate) trait Completer {
+    /// Name of the source, which is the command it completes unless `completes` says otherwise
+    fn name(&self) -> &'static str;
+
+    fn completes(&self, command_name: &str) -> bool {
+        command_name == self.name()
+    }
+
+    fn complete(&self, request: &CompletionRequest, environment: &CompleterEnvironment) -> Completions;
+}
+
+/// Where the completers look for their files, so that tests can point them to fixture directories.
+#[derive(Debug, Clone)]
+pub(crate) struct CompleterEnvironment {
+    pub(crate) current_directory: PathBuf,
+    pub(crate) home_directory: Option<PathBuf>,
+}
+
+impl CompleterEnvironment {
+    pub(crate) fn current() -> Self {
+        CompleterEnvironment {
+            current_directory: env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            home_directory: path::home_directory(""),
+        }
+    }
+
+    /// The nearest file with the given name in the current directory or one of its parents.
+    fn find_upwards(&self, name: &str) -> Option<PathBuf> {
+        self.current_directory.ancestors()
+            .map(|directory| directory.join(name))
+            .find(|candidate| candidate.exists())
+    }
+
+    /// A path typed on the command line, relative to the current directory.
+    fn resolve(&self, path: &str) -> PathBuf {
+        self.current_directory.join(Path::new(path))
+    }
+
+    /// The file names a word typed on the command line completes to, relative ones in the current directory.
+    fn complete_filename(&self, word: &str) -> Vec<String> {
+        autocompletion::complete_filename_in(&self.current_directory, word, false)
+    }
+}
+
+pub(crate) fn builtin_completers() -> Vec<Box<dyn Completer>> {
+    vec![
+        Box::new(cargo::CargoCompleter),
+        Box::new(git::GitCompleter),
+        Box::new(make::MakeCompleter),
+        Box::new(ssh::SshCompleter),
+    ]
+}
+
+/// The words that match the typed prefix, ranked and inserted as they are.
+fn matching_words<S: AsRef<str>>(words: &[S], prefix: &str) -> Completions {
+    let matches = Matcher::current().rank(prefix, words.iter().map(|word| word.as_ref().to_string()));
+    Completions { matches, style: CompletionStyle { verbatim: true, no_space: false } }
+}
+
+/// The first argument that is not an option, e.g. `commit` in `git -p commit`.
+fn subcommand<'a>(request: &CompletionRequest<'a>) -> Option<&'a str> {
+    request.arguments.iter().map(|argument| argument.as_str()).find(|argument| !argument.starts_with('-'))
+}
+
+#[cfg(test)]
+fn test_environment(directory: &Path) -> CompleterEnvironment {
+    CompleterEnvironment { current_directory: directory.to_path_buf(), home_directory: Some(directory.to_path_buf()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_completers_are_named_after_their_commands() {
+        let completers = builtin_completers();
+        let names: Vec<&str> = completers.iter().map(|completer| completer.name()).collect();
+        assert_eq!(names, vec!["cargo", "git", "make", "ssh"]);
+        assert!(completers.iter().any(|completer| completer.completes("scp")));
+    }
+
+    #[test]
+    fn test_matching_words() {
+        let completions = matching_words(&["build", "bench", "check"], "b");
+        assert_eq!(completions.matches, vec!["bench", "build"]);
+        assert!(completions.style.verbatim);
+    }
+
+    #[test]
+    fn test_find_upwards() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("Cargo.toml"), "")?;
+        std::fs::create_dir_all(directory.path().join("src/bin"))?;
+        let environment = test_environment(&directory.path().join("src/bin"));
+        assert_eq!(environment.find_upwards("Cargo.toml"), Some(directory.path().join("Cargo.toml")));
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use std::fs;
+use crate::input::autocompletion::{CompletionStyle, Completions};
+use crate::input::completers::{matching_words, Completer, CompleterEnvironment};
+use crate::input::completion_spec::CompletionRequest;
+
+/// Completes host names from `~/.ssh/config` and `~/.ssh/known_hosts` for ssh, and for scp followed by
+/// a colon, keeping a `user@` prefix.
+pub(crate) struct SshCompleter;
+
+impl Completer for SshCompleter {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn completes(&self, command_name: &str) -> bool {
+        matches!(command_name, "ssh" | "scp")
+    }
+
+    fn complete(&self, request: &CompletionRequest, environment: &CompleterEnvironment) -> Completions {
+        let is_scp = request.command_name == "scp";
+        if matches!(request.previous_word, "-i" | "-F") || (is_scp && is_local_path(request.word)) {
+            return environment.complete_filename(request.word).into();
+        }
+        if is_scp && request.word.contains(':') {
+            // Remote paths are not completed
+            return Completions::default();
+        }
+
+        let (user, host) = match request.word.split_once('@') {
+            Some((user, host)) => (format!("{}@", user), host),
+            None => (String::new(), request.word),
+        };
+        let mut completions = matching_words(&hosts(environment), host);
+        for host in &mut completions.matches {
+            host.insert_str(0, &user);
+            if is_scp {
+                host.push(':');
+            }
+        }
+        if !is_scp {
+            return completions;
+        }
+        let files = if user.is_empty() { environment.complete_filename(request.word) } else { Vec::new() };
+        if files.is_empty() {
+            // The remote path follows the colon
+            completions.style = CompletionStyle { verbatim: true, no_space: true };
+        } else {
+            // Local files are quoted and finished like any file name
+            completions.matches.extend(files);
+            completions.matches.sort();
+            completions.style = CompletionStyle::default();
+        }
+        completions
+    }
+}
+
+fn is_local_path(word: &str) -> bool {
+    word.starts_with(['/', '.', '~']) || word.split(':').next().unwrap_or_default().contains('/')
+}
+
+/// The hosts of the `Host` lines of the ssh config and of the known hosts that are not hashed, without patterns.
+fn hosts(environment: &CompleterEnvironment) -> Vec<String> {
+    let Some(ssh_directory) = environment.home_directory.as_ref().map(|home| home.join(".ssh")) else {
+        return Vec::new();
+    };
+    let mut hosts = Vec::new();
+    for line in fs::read_to_string(ssh_directory.join("config")).unwrap_or_default().lines() {
+        let mut words = line.split_whitespace();
+        if words.next().map(|keyword| keyword.eq_ignore_ascii_case("host")).unwrap_or(false) {
+            hosts.extend(words
+                .filter(|host| !host.contains(['*', '?', '!']))
+                .map(|host| host.to_string()));
+        }
+    }
+    for line in fs::read_to_string(ssh_directory.join("known_hosts")).unwrap_or_default().lines() {
+        let mut fields = line.split_whitespace().skip_while(|field| field.starts_with('@'));
+        let Some(names) = fields.next().filter(|names| !names.starts_with(['#', '|'])) else {
+            continue;
+        };
+        // Hosts on another port are written as [host]:port
+        hosts.extend(names.split(',')
+            .map(|name| name.strip_prefix('[').and_then(|name| name.split(']').next()).unwrap_or(name))
+            .filter(|name| !name.contains(['*', '?', '!']))
+            .map(|name| name.to_string()));
+    }
+    hosts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::completers::test_environment;
+
+    fn create_home() -> Result<tempfile::TempDir, anyhow::Error> {
+        let home = tempfile::tempdir()?;
+        fs::create_dir(home.path().join(".ssh"))?;
+        fs::write(home.path().join(".ssh/config"), concat!(
+            "Host build-server staging\n",
+            "    HostName 10.0.0.5\n",
+            "host *.internal !bastion\n",
+        ))?;
+        fs::write(home.path().join(".ssh/known_hosts"), concat!(
+            "github.com,140.82.121.4 ssh-ed25519 AAAA\n",
+            "[backup.example.com]:2222 ssh-rsa AAAA\n",
+            "|1|hashed= ssh-rsa AAAA\n",
+            "@cert-authority *.example.com ssh-rsa AAAA\n",
+        ))?;
+        Ok(home)
+    }
+
+    #[test]
+    fn test_hosts() -> Result<(), anyhow::Error> {
+        let home = create_home()?;
+        let mut hosts = hosts(&test_environment(home.path()));
+        hosts.sort();
+        assert_eq!(hosts, vec!["140.82.121.4", "backup.example.com", "build-server", "github.com", "staging"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_ssh_hosts() -> Result<(), anyhow::Error> {
+        let home = create_home()?;
+        let environment = test_environment(home.path());
+        let request = CompletionRequest { command_name: "ssh", word: "deploy@st", ..CompletionRequest::default() };
+        assert_eq!(SshCompleter.complete(&request, &environment).matches, vec!["deploy@staging"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_scp_hosts() -> Result<(), anyhow::Error> {
+        let home = create_home()?;
+        let environment = test_environment(home.path());
+        let request = CompletionRequest { command_name: "scp", word: "bu", ..CompletionRequest::default() };
+        let completions = SshCompleter.complete(&request, &environment);
+        assert_eq!(completions.matches, vec!["build-server:"]);
+        assert!(completions.style.no_space);
+
+        let request = CompletionRequest { command_name: "scp", word: "staging:/var", ..CompletionRequest::default() };
+        assert!(SshCompleter.complete(&request, &environment).matches.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_scp_local_files() -> Result<(), anyhow::Error> {
+        let home = create_home()?;
+        fs::write(home.path().join("build notes.txt"), "")?;
+        fs::create_dir(home.path().join("keys"))?;
+        fs::write(home.path().join("keys/id_deploy"), "")?;
+        let environment = test_environment(home.path());
+
+        // Local files are quoted and finished like file names, even next to hosts
+        let request = CompletionRequest { command_name: "scp", word: "bu", ..CompletionRequest::default() };
+        let completions = SshCompleter.complete(&request, &environment);
+        assert_eq!(completions.matches, vec!["build notes.txt", "build-server:"]);
+        assert_eq!(completions.style, CompletionStyle::default());
+
+        let request = CompletionRequest { command_name: "scp", word: "build ", ..CompletionRequest::default() };
+        assert_eq!(SshCompleter.complete(&request, &environment), vec!["build notes.txt".to_string()].into());
+
+        let request = CompletionRequest { command_name: "ssh", word: "keys/", previous_word: "-i", ..CompletionRequest::default() };
+        assert_eq!(SshCompleter.complete(&request, &environment).matches, vec!["keys/id_deploy"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use std::io::{Read, Write};
+use crate::input::autocompletion::{self, CompletionWord, Completions};
+use crate::input::line_buffer::LineBuffer;
+use crate::input::screen::display_width;
+
+const HIGHLIGHT_SEQUENCE: &str = "\x1b[7m";
+const RESET_ATTRIBUTES_SEQUENCE: &str = "\x1b[0m";
+const CLEAR_LINE_SEQUENCE: &str = "\r\x1b[K";
+const MORE_PROMPT: &str = "--More--";
+const COLUMN_SEPARATOR_WIDTH: usize = 2;
+const BEEP: &str = "\x07";
+
+/// Matches laid out in columns that are sorted downwards, the way readline lists completions.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Grid {
+    pub(crate) rows: usize,
+    columns: usize,
+    column_width: usize,
+}
+
+impl Grid {
+    pub(crate) fn new<S: AsRef<str>>(names: &[S], terminal_columns: usize) -> Self {
+        let column_width = names.iter().map(|name| display_width(name.as_ref())).max().unwrap_or(0) + COLUMN_SEPARATOR_WIDTH;
+        // The last column needs no separator
+        let columns = ((terminal_columns + COLUMN_SEPARATOR_WIDTH) / column_width).max(1);
+        Grid { rows: names.len().div_ceil(columns), columns, column_width }
+    }
+
+    /// The rows of the grid, with the selected name in reverse video.
+    pub(crate) fn lines<S: AsRef<str>>(&self, names: &[S], selected: Option<usize>) -> Vec<String> {
+        (0..self.rows)
+            .map(|row| {
+                let mut line = String::new();
+                let mut width = 0;
+                for column in 0..self.columns {
+                    let index = column * self.rows + row;
+                    let Some(name) = names.get(index).map(|name| name.as_ref()) else {
+                        break;
+                    };
+                    line.push_str(&" ".repeat(column * self.column_width - width));
+                    if selected == Some(index) {
+                        line.push_str(&format!("{}{}{}", HIGHLIGHT_SEQUENCE, name, RESET_ATTRIBUTES_SEQUENCE));
+                    } else {
+                        line.push_str(name);
+                    }
+                    width = column * self.column_width + display_width(name);
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Whether every row fits on the terminal without wrapping.
+    pub(crate) fn fits_width(&self, terminal_columns: usize) -> bool {
+        self.columns * self.column_width - COLUMN_SEPARATOR_WIDTH <= terminal_columns
+    }
+}
+
+/// Asks the question readline asks before listing many matches. y or Space answers yes, and n, Delete,
+/// Ctrl-C or Ctrl-G answers no.
+pub(crate) fn confirm_display<R: Read, W: Write>(reader: &mut R, writer: &mut W, count: usize) -> Result<bool, anyhow::Error> {
+    write!(writer, "Display all {} possibilities? (y or n)", count)?;
+    writer.flush()?;
+    loop {
+        match read_byte(reader)? {
+            b'y' | b'Y' | b' ' => {
+                writeln!(writer)?;
+                return Ok(true);
+            }
+            b'n' | b'N' | 0x7f | 0x03 | 0x07 => {
+                writeln!(writer)?;
+                return Ok(false);
+            }
+            _ => {
+                write!(writer, "{}", BEEP)?;
+                writer.flush()?;
+            }
+        }
+    }
+}
+
+/// Prints lines a screenful at a time, waiting at a `--More--` prompt: Space shows the next screenful,
+/// Enter the next line, and q or n stops the listing.
+pub(crate) fn print_paged<R: Read, W: Write>(reader: &mut R, writer: &mut W, lines: &[String], page_height: usize) -> Result<(), anyhow::Error> {
+    let mut shown_until = page_height.max(1);
+    for (index, line) in lines.iter().enumerate() {
+        while index == shown_until {
+            write!(writer, "{}", MORE_PROMPT)?;
+            writer.flush()?;
+            let key = read_byte(reader)?;
+            write!(writer, "{}", CLEAR_LINE_SEQUENCE)?;
+            match key {
+                b' ' | b'y' | b'Y' => shown_until += page_height.max(1),
+                b'\r' | b'\n' | b'j' => shown_until += 1,
+                b'q' | b'Q' | b'n' | b'N' | 0x03 | 0x07 | 0x1b => return Ok(()),
+                _ => write!(writer, "{}", BEEP)?,
+            }
+        }
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(())
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> Result<u8, anyhow::Error> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// How a key moves the selection in the menu.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MenuMove {
+    Next,
+    Previous,
+    /// To the match in the same row of the next column
+    Right,
+    Left,
+}
+
+/// Menu completion: the matches of a word are cycled through, each one inserted in place of the word
+/// while the grid below the line highlights it.
+pub(crate) struct CompletionMenu {
+    word: CompletionWord,
+    completions: Completions,
+    /// The matches as they are listed, file names without their directories
+    names: Vec<String>,
+    selected: Option<usize>,
+    /// The word as it was typed, restored when the menu is cancelled
+    original: String,
+    /// End of the text inserted for the selected match
+    inserted_end: usize,
+}
+
+impl CompletionMenu {
+    pub(crate) fn new(input: &LineBuffer, word: CompletionWord, completions: Completions) -> Self {
+        let names = completions.matches.iter()
+            .map(|completion| if completions.style.verbatim { completion.as_str() } else { autocompletion::display_name(completion) })
+            .map(|name| name.to_string())
+            .collect();
+        let original = input.text_before_cursor()[word.start..].to_string();
+        CompletionMenu { word, completions, names, selected: None, original, inserted_end: input.cursor() }
+    }
+
+    pub(crate) fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub(crate) fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Selects another match and inserts it; before the first selection every move selects the first or last match.
+    pub(crate) fn select(&mut self, direction: MenuMove, rows: usize, input: &mut LineBuffer) {
+        let count = self.names.len();
+        let rows = rows.max(1);
+        let selected = match (self.selected, direction) {
+            (None, MenuMove::Previous) => count - 1,
+            (None, _) => 0,
+            (Some(index), MenuMove::Next) => (index + 1) % count,
+            (Some(index), MenuMove::Previous) => (index + count - 1) % count,
+            (Some(index), MenuMove::Right) if index + rows < count => index + rows,
+            // From the last column to the first column of the next row
+            (Some(index), MenuMove::Right) => (index % rows + 1) % rows.min(count),
+            (Some(index), MenuMove::Left) if index >= rows => index - rows,
+            (Some(index), MenuMove::Left) => {
+                let row = (index % rows + rows - 1) % rows;
+                (0..count).rev().find(|candidate| candidate % rows == row).unwrap_or(0)
+            }
+        };
+        self.selected = Some(selected);
+        let text = autocompletion::replacement_text(&self.completions.matches[selected], &self.word, self.completions.style, true);
+        input.replace_range(self.word.start, self.inserted_end, &text);
+        self.inserted_end = self.word.start + text.len();
+    }
+
+    /// Puts back the word as it was typed.
+    pub(crate) fn cancel(&self, input: &mut LineBuffer) {
+        input.replace_range(self.word.start, self.inserted_end, &self.original);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_grid_sorts_down_columns() {
+        let names = ["alpha", "beta", "gamma", "delta", "eps"];
+        let grid = Grid::new(&names, 16);
+        assert_eq!(grid, Grid { rows: 3, columns: 2, column_width: 7 });
+        assert_eq!(grid.lines(&names, None), vec!["alpha  delta", "beta   eps", "gamma"]);
+        assert_eq!(grid.lines(&names, Some(4))[1], "beta   \x1b[7meps\x1b[0m");
+        assert!(grid.fits_width(16));
+    }
+
+    #[test]
+    fn test_grid_with_wide_names() {
+        let names = ["漢字漢字", "ab"];
+        let grid = Grid::new(&names, 80);
+        assert_eq!(grid.lines(&names, None), vec!["漢字漢字  ab"]);
+        assert!(!Grid::new(&["a-very-long-name"], 10).fits_width(10));
+    }
+
+    #[test]
+    fn test_confirm_display() -> Result<(), anyhow::Error> {
+        let mut output = Vec::new();
+        assert!(confirm_display(&mut Cursor::new(b"xy".to_vec()), &mut output, 312)?);
+        assert_eq!(String::from_utf8(output)?, "Display all 312 possibilities? (y or n)\x07\n");
+        assert!(!confirm_display(&mut Cursor::new(b"n".to_vec()), &mut Vec::new(), 312)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_print_paged() -> Result<(), anyhow::Error> {
+        let lines = strings(&["1", "2", "3", "4", "5", "6"]);
+        let mut output = Vec::new();
+        // A page, then one more line, then stop
+        print_paged(&mut Cursor::new(b"\rq".to_vec()), &mut output, &lines, 2)?;
+        assert_eq!(String::from_utf8(output)?, "1\n2\n--More--\r\x1b[K3\n--More--\r\x1b[K");
+
+        let mut output = Vec::new();
+        print_paged(&mut Cursor::new(b"  ".to_vec()), &mut output, &lines, 3)?;
+        assert_eq!(String::from_utf8(output)?.matches('\n').count(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_menu_cycles_and_cancels() {
+        let mut input = LineBuffer::from_str_with_cursor("cat sr", 6);
+        let word = autocompletion::word_before_cursor(input.text_before_cursor());
+        let completions = Completions::from(strings(&["src/", "srv.txt"]));
+        let mut menu = CompletionMenu::new(&input, word, completions);
+
+        menu.select(MenuMove::Next, 2, &mut input);
+        assert_eq!(input.as_str(), "cat src/");
+        menu.select(MenuMove::Next, 2, &mut input);
+        assert_eq!(input.as_str(), "cat srv.txt ");
+        menu.select(MenuMove::Next, 2, &mut input);
+        assert_eq!((input.as_str(), menu.selected()), ("cat src/", Some(0)));
+        menu.cancel(&mut input);
+        assert_eq!(input.as_str(), "cat sr");
+    }
+
+    #[test]
+    fn test_menu_moves_between_columns() {
+        let mut input = LineBuffer::from_str_with_cursor("x", 1);
+        let word = autocompletion::word_before_cursor(input.text_before_cursor());
+        let mut menu = CompletionMenu::new(&input, word, Completions::from(strings(&["x0", "x1", "x2", "x3", "x4"])));
+        // Three rows: x0 x3 / x1 x4 / x2
+        menu.select(MenuMove::Next, 3, &mut input);
+        menu.select(MenuMove::Right, 3, &mut input);
+        assert_eq!(menu.selected(), Some(3));
+        menu.select(MenuMove::Right, 3, &mut input);
+        assert_eq!(menu.selected(), Some(1));
+        menu.select(MenuMove::Left, 3, &mut input);
+        assert_eq!(menu.selected(), Some(3));
+        menu.select(MenuMove::Next, 3, &mut input);
+        menu.select(MenuMove::Left, 3, &mut input);
+        assert_eq!(menu.selected(), Some(1));
+        // From the first column to the last match of the previous row
+        menu.select(MenuMove::Previous, 3, &mut input);
+        menu.select(MenuMove::Left, 3, &mut input);
+        assert_eq!(menu.selected(), Some(2));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+use crate::command::ParsedCommand;
+use crate::input::autocompletion::{self, CompletionStyle};
+use crate::input::matcher::Matcher;
+
+lazy_static! {
+    static ref COMPLETION_SPECS: RwLock<HashMap<String, CompletionSpec>> = RwLock::new(HashMap::new());
+}
+
+/// How the arguments of a command complete, defined with the `complete` builtin like in bash.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct CompletionSpec {
+    /// `-W`: a whitespace-separated list of words
+    pub(crate) words: Option<String>,
+    /// `-F`: the shell has no functions, so this names a program that is run like a `-C` command
+    pub(crate) function: Option<String>,
+    /// `-C`: a command that prints the completions one per line
+    pub(crate) command: Option<String>,
+    /// `-f`: file names
+    pub(crate) files: bool,
+    /// `-d`: directory names
+    pub(crate) directories: bool,
+    /// `-o nospace`: no space after a unique match
+    pub(crate) no_space: bool,
+    /// `-o filenames`: the matches are file names and are quoted as such
+    pub(crate) filenames: bool,
+}
+
+/// The word being completed and where it is, as passed to `-F` and `-C` commands.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompletionRequest<'a> {
+    pub(crate) command_name: &'a str,
+    pub(crate) word: &'a str,
+    pub(crate) previous_word: &'a str,
+    /// The arguments of the command before the word
+    pub(crate) arguments: &'a [String],
+    /// The whole line, exported as `COMP_LINE`
+    pub(crate) line: &'a str,
+    /// The cursor position in characters, exported as `COMP_POINT`
+    pub(crate) point: usize,
+}
+
+impl CompletionSpec {
+    /// Applies an option shared by `complete` and `compgen`, taking its argument from `args`.
+    /// Returns false when the option is not one of them.
+    pub(crate) fn parse_option<'a>(&mut self, builtin: &str, option: &str, args: &mut impl Iterator<Item = &'a &'a str>) -> Result<bool, anyhow::Error> {
+        let mut argument = || args.next().map(|argument| argument.to_string())
+            .ok_or_else(|| anyhow::anyhow!("{}: {}: option requires an argument", builtin, option));
+        match option {
+            "-W" => self.words = Some(argument()?),
+            "-F" => self.function = Some(argument()?),
+            "-C" => self.command = Some(argument()?),
+            "-f" => self.files = true,
+            "-d" => self.directories = true,
+            "-o" => match argument()?.as_str() {
+                "nospace" => self.no_space = true,
+                "filenames" => self.filenames = true,
+                name => return Err(anyhow::anyhow!("{}: {}: invalid option name", builtin, name)),
+            },
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Generates the matches for a word, sorted and without duplicates.
+    pub(crate) fn generate(&self, request: &CompletionRequest) -> Vec<String> {
+        let mut matches = Vec::new();
+        if self.files || self.directories {
+            matches.extend(autocompletion::complete_filename(request.word, false).into_iter()
+                .filter(|name| self.files || name.ends_with('/')));
+        }
+        if let Some(words) = &self.words {
+            let matcher = Matcher::current();
+            matches.extend(words.split_whitespace()
+                .filter(|word| matcher.matches(request.word, word))
+                .map(|word| word.to_string()));
+        }
+        for command_line in self.function.iter().chain(&self.command) {
+            matches.extend(run_completion_command(command_line, request));
+        }
+        matches.sort();
+        matches.dedup();
+        matches
+    }
+
+    pub(crate) fn style(&self) -> CompletionStyle {
+        CompletionStyle {
+            verbatim: !(self.filenames || self.files || self.directories),
+            no_space: self.no_space,
+        }
+    }
+
+    /// Formats the spec as the `complete` command that defines it, the way `complete -p` prints it.
+    pub(crate) fn format(&self, name: &str) -> String {
+        let mut command = String::from("complete");
+        if self.filenames {
+            command.push_str(" -o filenames");
+        }
+        if self.no_space {
+            command.push_str(" -o nospace");
+        }
+        if self.files {
+            command.push_str(" -f");
+        }
+        if self.directories {
+            command.push_str(" -d");
+        }
+        if let Some(words) = &self.words {
+            command.push_str(&format!(" -W '{}'", words));
+        }
+        if let Some(function) = &self.function {
+            command.push_str(&format!(" -F {}", function));
+        }
+        if let Some(command_line) = &self.command {
+            command.push_str(&format!(" -C '{}'", command_line));
+        }
+        format!("{} {}", command, name)
+    }
+}
+
+/// Runs a `-F` or `-C` command with the command name, the word and the previous word as arguments,
+/// like bash does, and reads the matches from its output lines.
+fn run_completion_command(command_line: &str, request: &CompletionRequest) -> Vec<String> {
+    let Ok(Some(parsed_command)) = ParsedCommand::parse_command(command_line) else {
+        return Vec::new();
+    };
+    let output = Command::new(&parsed_command.command)
+        .args(&parsed_command.args)
+        .args([request.command_name, request.word, request.previous_word])
+        .env("COMP_LINE", request.line)
+        .env("COMP_POINT", request.point.to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub(crate) fn get(name: &str) -> Option<CompletionSpec> {
+    COMPLETION_SPECS.read().ok().and_then(|specs| specs.get(name).cloned())
+}
+
+pub(crate) fn set(name: &str, spec: CompletionSpec) -> Result<(), anyhow::Error> {
+    let mut specs = COMPLETION_SPECS.write()
+        .map_err(|_| anyhow::anyhow!("Completion specs are unavailable"))?;
+    specs.insert(name.to_string(), spec);
+    Ok(())
+}
+
+/// Removes the spec of a command, returning whether there was one.
+pub(crate) fn remove(name: &str) -> Result<bool, anyhow::Error> {
+    let mut specs = COMPLETION_SPECS.write()
+        .map_err(|_| anyhow::anyhow!("Completion specs are unavailable"))?;
+    Ok(specs.remove(name).is_some())
+}
+
+pub(crate) fn clear() -> Result<(), anyhow::Error> {
+    let mut specs = COMPLETION_SPECS.write()
+        .map_err(|_| anyhow::anyhow!("Completion specs are unavailable"))?;
+    specs.clear();
+    Ok(())
+}
+
+/// All specs sorted by command name.
+pub(crate) fn all() -> Vec<(String, CompletionSpec)> {
+    let mut specs: Vec<(String, CompletionSpec)> = COMPLETION_SPECS.read()
+        .map(|specs| specs.iter().map(|(name, spec)| (name.clone(), spec.clone())).collect())
+        .unwrap_or_default();
+    specs.sort_by(|a, b| a.0.cmp(&b.0));
+    specs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(word: &str) -> CompletionRequest<'_> {
+        CompletionRequest { command_name: "deploy", word, ..CompletionRequest::default() }
+    }
+
+    #[test]
+    fn test_parse_options() -> Result<(), anyhow::Error> {
+        let mut spec = CompletionSpec::default();
+        let args = ["-W", "start stop", "-o", "nospace"];
+        let mut args = args.iter();
+        while let Some(option) = args.next() {
+            assert!(spec.parse_option("complete", option, &mut args)?);
+        }
+        assert_eq!(spec.words.as_deref(), Some("start stop"));
+        assert!(spec.no_space);
+        assert!(!spec.parse_option("complete", "-x", &mut [].iter())?);
+        assert!(spec.parse_option("complete", "-o", &mut ["bogus"].iter()).is_err());
+        assert!(spec.parse_option("complete", "-W", &mut [].iter()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_from_word_list() {
+        let spec = CompletionSpec { words: Some("start status stop".to_string()), ..CompletionSpec::default() };
+        assert_eq!(spec.generate(&request("st")), vec!["start", "status", "stop"]);
+        assert_eq!(spec.generate(&request("sta")), vec!["start", "status"]);
+        assert_eq!(spec.style(), CompletionStyle { verbatim: true, no_space: false });
+    }
+
+    #[test]
+    fn test_generate_from_command() {
+        let spec = CompletionSpec { command: Some("echo production".to_string()), ..CompletionSpec::default() };
+        // The command gets the command name, word and previous word as further arguments
+        assert_eq!(spec.generate(&request("pro")), vec!["production deploy pro "]);
+    }
+
+    #[test]
+    fn test_generate_directories() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        std::fs::create_dir(directory.path().join("logs"))?;
+        std::fs::write(directory.path().join("log.txt"), "")?;
+        let spec = CompletionSpec { directories: true, ..CompletionSpec::default() };
+        let word = format!("{}/lo", directory.path().display());
+        assert_eq!(spec.generate(&request(&word)), vec![format!("{}/logs/", directory.path().display())]);
+        assert!(!spec.style().verbatim);
+        Ok(())
+    }
+
+    #[test]
+    fn test_format() {
+        let spec = CompletionSpec { words: Some("a b".to_string()), no_space: true, ..CompletionSpec::default() };
+        assert_eq!(spec.format("deploy"), "complete -o nospace -W 'a b' deploy");
+    }
+}
@@ -0,0 +1,130 @@
+use std::env;
+use std::path::Path;
+use crate::command::builtin;
+use crate::command::tokenizer::{self, Token, TokenKind};
+use crate::path;
+
+const COMMAND_COLOR: &str = "\x1b[32m";
+const UNKNOWN_COMMAND_COLOR: &str = "\x1b[31m";
+const STRING_COLOR: &str = "\x1b[33m";
+const VARIABLE_COLOR: &str = "\x1b[35m";
+const REDIRECT_COLOR: &str = "\x1b[36m";
+const PIPE_COLOR: &str = "\x1b[1;36m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+const UNDERLINE_SEQUENCE: &str = "\x1b[4m";
+const RESET_ATTRIBUTES_SEQUENCE: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Style {
+    color: Option<&'static str>,
+    underline: bool,
+}
+
+/// Colors a command line with the shell's own tokenizer, so that the colors match how the line is parsed.
+pub(crate) fn highlight(text: &str) -> String {
+    let path = path::Path::parse(&env::var("PATH").unwrap_or_default()).ok();
+    highlight_with(text, |name| is_command(name, path.as_ref()))
+}
+
+fn highlight_with(text: &str, is_command: impl Fn(&str) -> bool) -> String {
+    let tokens = tokenizer::tokenize(text);
+    let mut styles = vec![Style::default(); text.len()];
+    for token in &tokens.tokens {
+        let style = token_style(token, &is_command);
+        styles[token.span.clone()].fill(style);
+    }
+    for (spans, color) in [(&tokens.strings, STRING_COLOR), (&tokens.variables, VARIABLE_COLOR)] {
+        for span in spans {
+            styles[span.clone()].iter_mut().for_each(|style| style.color = Some(color));
+        }
+    }
+
+    let mut highlighted = String::new();
+    let mut current = Style::default();
+    for (position, ch) in text.char_indices() {
+        // Continuation prompts are drawn after newlines, so no style carries over them
+        let style = if ch == '\n' { Style::default() } else { styles[position] };
+        if style != current {
+            if current != Style::default() {
+                highlighted.push_str(RESET_ATTRIBUTES_SEQUENCE);
+            }
+            if style.underline {
+                highlighted.push_str(UNDERLINE_SEQUENCE);
+            }
+            if let Some(color) = style.color {
+                highlighted.push_str(color);
+            }
+            current = style;
+        }
+        highlighted.push(ch);
+    }
+    if current != Style::default() {
+        highlighted.push_str(RESET_ATTRIBUTES_SEQUENCE);
+    }
+    highlighted
+}
+
+fn token_style(token: &Token, is_command: impl Fn(&str) -> bool) -> Style {
+    match token.kind {
+        TokenKind::Word if token.is_command => {
+            let color = if is_command(&token.value) { COMMAND_COLOR } else { UNKNOWN_COMMAND_COLOR };
+            Style { color: Some(color), underline: false }
+        }
+        TokenKind::Word => Style { color: None, underline: Path::new(&token.value).exists() },
+        TokenKind::Pipe => Style { color: Some(PIPE_COLOR), underline: false },
+        TokenKind::Redirect => Style { color: Some(REDIRECT_COLOR), underline: false },
+        TokenKind::Comment => Style { color: Some(COMMENT_COLOR), underline: false },
+    }
+}
+
+fn is_command(name: &str, path: Option<&path::Path>) -> bool {
+    if builtin::is_builtin(name) {
+        return true;
+    }
+    match path {
+        Some(path) if name.contains('/') => path.is_executable_file(Path::new(name)),
+        Some(path) => path.has_executable(name),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlight_known(text: &str) -> String {
+        highlight_with(text, |name| name == "echo" || name == "wc")
+    }
+
+    #[test]
+    fn test_highlight_commands() {
+        assert_eq!(highlight_known("echo hi"), "\x1b[32mecho\x1b[0m hi");
+        assert_eq!(highlight_known("ehco hi"), "\x1b[31mehco\x1b[0m hi");
+        assert_eq!(highlight_known("echo hi | wc"), "\x1b[32mecho\x1b[0m hi \x1b[1;36m|\x1b[0m \x1b[32mwc\x1b[0m");
+    }
+
+    #[test]
+    fn test_highlight_strings_variables_and_redirects() {
+        assert_eq!(highlight_known("echo 'a' \"$?\" >x"), concat!(
+            "\x1b[32mecho\x1b[0m ",
+            "\x1b[33m'a'\x1b[0m ",
+            "\x1b[33m\"\x1b[0m\x1b[35m$?\x1b[0m\x1b[33m\"\x1b[0m ",
+            "\x1b[36m>\x1b[0mx",
+        ));
+    }
+
+    #[test]
+    fn test_highlight_comment() {
+        assert_eq!(highlight_known("echo # note\necho"), "\x1b[32mecho\x1b[0m \x1b[90m# note\x1b[0m\n\x1b[32mecho\x1b[0m");
+    }
+
+    #[test]
+    fn test_underline_existing_paths() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        let file = directory.path().join("notes.txt");
+        std::fs::write(&file, "")?;
+        let text = format!("echo {}", file.display());
+        assert_eq!(highlight_known(&text), format!("\x1b[32mecho\x1b[0m \x1b[4m{}\x1b[0m", file.display()));
+        Ok(())
+    }
+}
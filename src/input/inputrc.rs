@@ -0,0 +1,458 @@
+use std::env;
+use std::fs;
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+use crate::input::keymap::{self, EditorAction, KeyBinding, KeymapName};
+use crate::input::settings::{self, EditingMode};
+
+// Guards against files including each other
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// One non-empty line of an inputrc file or a `bind` argument.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Directive {
+    Set(String, String),
+    Bind(String, EditorAction),
+    If(String),
+    Else,
+    EndIf,
+    Include(String),
+}
+
+/// Parses a line, returning `None` for blank lines and comments.
+pub(crate) fn parse_line(line: &str) -> Result<Option<Directive>, anyhow::Error> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    if let Some(conditional) = line.strip_prefix('$') {
+        let (name, argument) = conditional.split_once(char::is_whitespace).unwrap_or((conditional, ""));
+        return match name {
+            "if" => Ok(Some(Directive::If(argument.trim().to_string()))),
+            "else" => Ok(Some(Directive::Else)),
+            "endif" => Ok(Some(Directive::EndIf)),
+            "include" => Ok(Some(Directive::Include(argument.trim().to_string()))),
+            _ => Err(anyhow::anyhow!("${}: unknown parser directive", name)),
+        };
+    }
+    if let Some(("set", variable)) = line.split_once(char::is_whitespace) {
+        let variable = variable.trim();
+        let (name, value) = variable.split_once(char::is_whitespace).unwrap_or((variable, ""));
+        return Ok(Some(Directive::Set(name.to_string(), value.trim().to_string())));
+    }
+    let (sequence, function) = parse_key_binding(line)?;
+    if function.starts_with('"') || function.starts_with('\'') {
+        return Err(anyhow::anyhow!("{}: keyboard macros are not supported", function));
+    }
+    let name = function.split_whitespace().next().unwrap_or("");
+    let action = EditorAction::from_name(name)
+        .ok_or_else(|| anyhow::anyhow!("{}: unknown function name", name))?;
+    Ok(Some(Directive::Bind(sequence, action)))
+}
+
+/// Splits `"keyseq": value` or `keyname: value` into the decoded key sequence and the trimmed value.
+pub(crate) fn parse_key_binding(line: &str) -> Result<(String, &str), anyhow::Error> {
+    let line = line.trim();
+    let (sequence, rest) = if let Some(quoted) = line.strip_prefix('"') {
+        let end = closing_quote_position(quoted)
+            .ok_or_else(|| anyhow::anyhow!("{}: no closing `\"' in key binding", line))?;
+        (parse_key_sequence(&quoted[..end])?, &quoted[end + 1..])
+    } else {
+        let colon = line.find(':')
+            .ok_or_else(|| anyhow::anyhow!("{}: no `:' in key binding", line))?;
+        (parse_key_name(line[..colon].trim())?, &line[colon..])
+    };
+    let value = rest.trim_start().strip_prefix(':')
+        .ok_or_else(|| anyhow::anyhow!("{}: no `:' in key binding", line))?;
+    Ok((sequence, value.trim()))
+}
+
+/// Finds the quote ending a key sequence, skipping backslash-escaped quotes.
+fn closing_quote_position(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            '"' if !escaped => return Some(idx),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Decodes readline's key sequence escapes such as `\C-a`, `\M-f`, `\e[A` and `\x7f`.
+pub(crate) fn parse_key_sequence(text: &str) -> Result<String, anyhow::Error> {
+    let mut chars = text.chars().peekable();
+    let mut sequence = String::new();
+    while chars.peek().is_some() {
+        sequence.push_str(&parse_key(&mut chars)?);
+    }
+    if sequence.is_empty() {
+        return Err(anyhow::anyhow!("empty key sequence"));
+    }
+    Ok(sequence)
+}
+
+fn parse_key(chars: &mut Peekable<Chars>) -> Result<String, anyhow::Error> {
+    let ch = match chars.next() {
+        Some('\\') => chars.next().ok_or_else(|| anyhow::anyhow!("key sequence ends with a backslash"))?,
+        Some(ch) => return Ok(ch.to_string()),
+        None => return Err(anyhow::anyhow!("missing key after modifier")),
+    };
+    let key = match ch {
+        'C' | 'M' if chars.peek() == Some(&'-') => {
+            chars.next();
+            let key = parse_key(chars)?;
+            return Ok(if ch == 'M' { format!("\x1b{}", key) } else { control_key(&key) });
+        }
+        'e' => '\x1b',
+        'a' => '\x07',
+        'b' => '\x08',
+        'd' => '\x7f',
+        'f' => '\x0c',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\x0b',
+        '0'..='7' => parse_numeric_escape(chars, ch.to_string(), 8, 3)?,
+        'x' => parse_numeric_escape(chars, String::new(), 16, 2)?,
+        other => other,
+    };
+    Ok(key.to_string())
+}
+
+fn parse_numeric_escape(chars: &mut Peekable<Chars>, mut digits: String, radix: u32, max_digits: usize) -> Result<char, anyhow::Error> {
+    while digits.len() < max_digits {
+        match chars.peek() {
+            Some(digit) if digit.is_digit(radix) => digits.push(*digit),
+            _ => break,
+        }
+        chars.next();
+    }
+    u32::from_str_radix(&digits, radix).ok()
+        .and_then(char::from_u32)
+        .ok_or_else(|| anyhow::anyhow!("\\{}: invalid numeric escape", digits))
+}
+
+/// Applies the control modifier to the last key of a sequence, `\C-?` being Rubout.
+fn control_key(key: &str) -> String {
+    let mut chars: Vec<char> = key.chars().collect();
+    if let Some(last) = chars.last_mut() {
+        *last = match *last {
+            '?' => '\x7f',
+            ch if ch.is_ascii() => ((ch.to_ascii_uppercase() as u8) & 0x1f) as char,
+            ch => ch,
+        };
+    }
+    chars.into_iter().collect()
+}
+
+/// Decodes key names such as `Control-u`, `M-DEL` or `Return` on the left of an unquoted binding.
+fn parse_key_name(name: &str) -> Result<String, anyhow::Error> {
+    let lowercase = name.to_lowercase();
+    for (prefix, meta) in [("control-", false), ("c-", false), ("meta-", true), ("m-", true)] {
+        if lowercase.starts_with(prefix) && name.len() > prefix.len() {
+            let key = parse_key_name(&name[prefix.len()..])?;
+            return Ok(if meta { format!("\x1b{}", key) } else { control_key(&key) });
+        }
+    }
+    let key = match lowercase.as_str() {
+        "rubout" | "del" => "\x7f",
+        "escape" | "esc" => "\x1b",
+        "newline" | "lfd" => "\n",
+        "return" | "ret" => "\r",
+        "space" | "spc" => " ",
+        "tab" => "\t",
+        _ if name.chars().count() == 1 => name,
+        _ => return Err(anyhow::anyhow!("{}: unknown key name", name)),
+    };
+    Ok(key.to_string())
+}
+
+/// Encodes a key sequence the way `bind -p` prints it, e.g. "\C-a" or "\e[A".
+pub(crate) fn format_key_sequence(sequence: &str) -> String {
+    let mut formatted = String::new();
+    for ch in sequence.chars() {
+        match ch {
+            '\x1b' => formatted.push_str("\\e"),
+            '\x7f' => formatted.push_str("\\C-?"),
+            '"' => formatted.push_str("\\\""),
+            '\\' => formatted.push_str("\\\\"),
+            '\x01'..='\x1a' => formatted.push_str(&format!("\\C-{}", (ch as u8 + 0x60) as char)),
+            '\x00'..='\x1f' => {
+                formatted.push_str("\\C-");
+                match (ch as u8 + 0x40) as char {
+                    '\\' => formatted.push_str("\\\\"),
+                    control => formatted.push(control),
+                }
+            }
+            _ => formatted.push(ch),
+        }
+    }
+    formatted
+}
+
+/// Applies inputrc lines one after another, tracking the keymap selected with `set keymap`
+/// and the `$if` conditions around them.
+pub(crate) struct InputrcReader {
+    keymap: KeymapName,
+    conditions: Vec<bool>,
+    include_depth: usize,
+}
+
+impl InputrcReader {
+    pub(crate) fn new(keymap: KeymapName) -> Self {
+        InputrcReader { keymap, conditions: Vec::new(), include_depth: 0 }
+    }
+
+    /// Starts with the keymap of the current editing mode, like readline does.
+    pub(crate) fn for_current_mode() -> Self {
+        InputrcReader::new(keymap_for_mode(settings::current().editing_mode))
+    }
+
+    pub(crate) fn keymap(&self) -> KeymapName {
+        self.keymap
+    }
+
+    fn is_active(&self) -> bool {
+        self.conditions.last().copied().unwrap_or(true)
+    }
+
+    pub(crate) fn apply_line(&mut self, line: &str) -> Result<(), anyhow::Error> {
+        let directive = match parse_line(line)? {
+            Some(directive) => directive,
+            None => return Ok(()),
+        };
+        match directive {
+            Directive::If(condition) => {
+                let active = self.is_active() && evaluate_condition(&condition);
+                self.conditions.push(active);
+            }
+            Directive::Else => {
+                let branch_taken = self.conditions.pop()
+                    .ok_or_else(|| anyhow::anyhow!("$else found without matching $if"))?;
+                let active = self.is_active() && !branch_taken;
+                self.conditions.push(active);
+            }
+            Directive::EndIf => {
+                self.conditions.pop()
+                    .ok_or_else(|| anyhow::anyhow!("$endif without matching $if"))?;
+            }
+            _ if !self.is_active() => (),
+            Directive::Set(name, value) => match name.as_str() {
+                "keymap" => self.keymap = KeymapName::parse(&value)?,
+                "editing-mode" => {
+                    settings::set_variable(&name, &value)?;
+                    self.keymap = keymap_for_mode(settings::current().editing_mode);
+                }
+                _ => settings::set_variable(&name, &value)?,
+            },
+            Directive::Bind(sequence, action) => keymap::bind(self.keymap, &sequence, KeyBinding::Action(action))?,
+            Directive::Include(path) => {
+                let errors = self.load_file(&expand_tilde(&path))?;
+                if !errors.is_empty() {
+                    let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+                    return Err(anyhow::anyhow!(messages.join("\n")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies every line of the file, returning the errors of lines that could not be applied.
+    pub(crate) fn load_file(&mut self, path: &Path) -> Result<Vec<anyhow::Error>, anyhow::Error> {
+        if self.include_depth >= MAX_INCLUDE_DEPTH {
+            return Err(anyhow::anyhow!("{}: too many nested includes", path.display()));
+        }
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("{}: {}", path.display(), e))?;
+        self.include_depth += 1;
+        let errors = contents.lines()
+            .enumerate()
+            .filter_map(|(idx, line)| {
+                self.apply_line(line).err()
+                    .map(|e| anyhow::anyhow!("{}: line {}: {}", path.display(), idx + 1, e))
+            })
+            .collect();
+        self.include_depth -= 1;
+        Ok(errors)
+    }
+}
+
+pub(crate) fn keymap_for_mode(editing_mode: EditingMode) -> KeymapName {
+    match editing_mode {
+        EditingMode::Emacs => KeymapName::Emacs,
+        EditingMode::Vi => KeymapName::ViInsert,
+    }
+}
+
+/// Supports the `mode=` and `term=` tests of readline; application names never match.
+fn evaluate_condition(condition: &str) -> bool {
+    if let Some(mode) = condition.strip_prefix("mode=") {
+        let editing_mode = settings::current().editing_mode;
+        return matches!((mode.trim(), editing_mode), ("emacs", EditingMode::Emacs) | ("vi", EditingMode::Vi));
+    }
+    if let Some(term) = condition.strip_prefix("term=") {
+        let current = env::var("TERM").unwrap_or_default();
+        let term = term.trim();
+        return current == term || current.split('-').next() == Some(term);
+    }
+    false
+}
+
+fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The file named by INPUTRC, or ~/.inputrc.
+pub(crate) fn startup_file() -> Option<PathBuf> {
+    match env::var("INPUTRC") {
+        Ok(path) => Some(PathBuf::from(path)),
+        Err(_) => env::var("HOME").ok().map(|home| Path::new(&home).join(".inputrc")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_parse_key_sequence_escapes() -> Result<(), anyhow::Error> {
+        assert_eq!(parse_key_sequence("\\C-a")?, "\x01");
+        assert_eq!(parse_key_sequence("\\C-?")?, "\x7f");
+        assert_eq!(parse_key_sequence("\\M-f")?, "\x1bf");
+        assert_eq!(parse_key_sequence("\\M-\\C-h")?, "\x1b\x08");
+        assert_eq!(parse_key_sequence("\\e[1;5C")?, "\x1b[1;5C");
+        assert_eq!(parse_key_sequence("\\C-x\\C-e")?, "\x18\x05");
+        assert_eq!(parse_key_sequence("\\x7f")?, "\x7f");
+        assert_eq!(parse_key_sequence("\\033[A")?, "\x1b[A");
+        assert_eq!(parse_key_sequence("\\\"\\\\")?, "\"\\");
+        assert_eq!(parse_key_sequence("\\t")?, "\t");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_key_sequence_errors() {
+        assert!(parse_key_sequence("").is_err());
+        assert!(parse_key_sequence("\\").is_err());
+        assert!(parse_key_sequence("\\C-").is_err());
+    }
+
+    #[test]
+    fn test_parse_key_name() -> Result<(), anyhow::Error> {
+        assert_eq!(parse_key_name("Control-u")?, "\x15");
+        assert_eq!(parse_key_name("C-w")?, "\x17");
+        assert_eq!(parse_key_name("Meta-Rubout")?, "\x1b\x7f");
+        assert_eq!(parse_key_name("M-DEL")?, "\x1b\x7f");
+        assert_eq!(parse_key_name("Return")?, "\r");
+        assert_eq!(parse_key_name("x")?, "x");
+        assert!(parse_key_name("Hyper-x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_key_sequence() {
+        assert_eq!(format_key_sequence("\x01"), "\\C-a");
+        assert_eq!(format_key_sequence("\x7f"), "\\C-?");
+        assert_eq!(format_key_sequence("\x1b[1;5C"), "\\e[1;5C");
+        assert_eq!(format_key_sequence("\x1f"), "\\C-_");
+        assert_eq!(format_key_sequence("\"\\"), "\\\"\\\\");
+    }
+
+    #[test]
+    fn test_format_and_parse_round_trip() -> Result<(), anyhow::Error> {
+        for sequence in ["\x01", "\x7f", "\x1b[A", "\x1bb", "\t", "\x1c", "\"x\""] {
+            assert_eq!(parse_key_sequence(&format_key_sequence(sequence))?, sequence);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_binding_line() -> Result<(), anyhow::Error> {
+        assert_eq!(parse_line("\"\\C-a\": beginning-of-line")?, Some(Directive::Bind("\x01".to_string(), EditorAction::BeginningOfLine)));
+        assert_eq!(parse_line("\"\\e[1;5C\":forward-word")?, Some(Directive::Bind("\x1b[1;5C".to_string(), EditorAction::ForwardWord)));
+        assert_eq!(parse_line("Control-w: backward-kill-word")?, Some(Directive::Bind("\x17".to_string(), EditorAction::BackwardKillWord)));
+        assert_eq!(parse_line("\"\\\"\": self-insert")?, Some(Directive::Bind("\"".to_string(), EditorAction::SelfInsert)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_binding_lines() {
+        assert!(parse_line("\"\\C-a\": no-such-function").is_err());
+        assert!(parse_line("\"\\C-a\": \"macro text\"").is_err());
+        assert!(parse_line("\"\\C-a beginning-of-line").is_err());
+        assert!(parse_line("beginning-of-line").is_err());
+    }
+
+    #[test]
+    fn test_parse_set_and_directives() -> Result<(), anyhow::Error> {
+        assert_eq!(parse_line("  set  history-prefix-search   on ")?, Some(Directive::Set("history-prefix-search".to_string(), "on".to_string())));
+        assert_eq!(parse_line("set history-prefix-search")?, Some(Directive::Set("history-prefix-search".to_string(), "".to_string())));
+        assert_eq!(parse_line("$if mode=vi")?, Some(Directive::If("mode=vi".to_string())));
+        assert_eq!(parse_line("$else")?, Some(Directive::Else));
+        assert_eq!(parse_line("$endif")?, Some(Directive::EndIf));
+        assert_eq!(parse_line("$include ~/.inputrc.local")?, Some(Directive::Include("~/.inputrc.local".to_string())));
+        assert_eq!(parse_line("# comment")?, None);
+        assert_eq!(parse_line("   ")?, None);
+        assert!(parse_line("$unless").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_key_binding_keeps_shell_command() -> Result<(), anyhow::Error> {
+        assert_eq!(parse_key_binding("\"\\C-l\": clear")?, ("\x0c".to_string(), "clear"));
+        assert_eq!(parse_key_binding("\"\\C-x\\C-r\" : \"ls -la\"")?, ("\x18\x12".to_string(), "\"ls -la\""));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditionals_skip_inactive_lines() -> Result<(), anyhow::Error> {
+        let mut reader = InputrcReader::new(KeymapName::Emacs);
+        reader.apply_line("$if term=no-such-terminal")?;
+        reader.apply_line("set no-such-variable on")?;
+        reader.apply_line("$if mode=emacs")?;
+        reader.apply_line("set no-such-variable on")?;
+        reader.apply_line("$endif")?;
+        reader.apply_line("$else")?;
+        assert!(reader.apply_line("set no-such-variable on").is_err());
+        reader.apply_line("$endif")?;
+        assert!(reader.apply_line("$endif").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_keymap() -> Result<(), anyhow::Error> {
+        let mut reader = InputrcReader::new(KeymapName::Emacs);
+        reader.apply_line("set keymap vi-insert")?;
+        assert_eq!(reader.keymap(), KeymapName::ViInsert);
+        assert!(reader.apply_line("set keymap vi-command").is_err());
+        assert_eq!(reader.keymap(), KeymapName::ViInsert);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_file_reports_bad_lines() -> Result<(), anyhow::Error> {
+        let mut file = NamedTempFile::new()?;
+        writeln!(file, "# bindings")?;
+        writeln!(file, "\"\\C-x\\C-a\": beginning-of-line")?;
+        writeln!(file, "\"\\C-x\\C-b\": no-such-function")?;
+        let mut reader = InputrcReader::new(KeymapName::ViInsert);
+        let errors = reader.load_file(file.path())?;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("line 3"));
+        assert_eq!(keymap::current(KeymapName::ViInsert).get("\x18\x01"), Some(&KeyBinding::Action(EditorAction::BeginningOfLine)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let mut reader = InputrcReader::new(KeymapName::Emacs);
+        assert!(reader.load_file(Path::new("/no/such/inputrc")).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+
+/// Named line editor functions that keys can be bound to, using readline's names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EditorAction {
+    Abort,
+    AcceptLine,
+    BackwardChar,
+    BackwardDeleteChar,
+    BackwardKillWord,
+    BackwardWord,
+    BeginningOfLine,
+    BracketedPasteBegin,
+    ClearScreen,
+    Complete,
+    DeleteChar,
+    EndOfLine,
+    ForwardChar,
+    ForwardSearchHistory,
+    ForwardWord,
+    HistorySearchBackward,
+    HistorySearchForward,
+    KillLine,
+    KillWord,
+    MenuComplete,
+    MenuCompleteBackward,
+    NextHistory,
+    PreviousHistory,
+    Redo,
+    ReverseSearchHistory,
+    SelfInsert,
+    Undo,
+    UnixLineDiscard,
+    UnixWordRubout,
+    ViMovementMode,
+    Yank,
+}
+
+const ACTION_NAMES: [(&str, EditorAction); 31] = [
+    ("abort", EditorAction::Abort),
+    ("accept-line", EditorAction::AcceptLine),
+    ("backward-char", EditorAction::BackwardChar),
+    ("backward-delete-char", EditorAction::BackwardDeleteChar),
+    ("backward-kill-word", EditorAction::BackwardKillWord),
+    ("backward-word", EditorAction::BackwardWord),
+    ("beginning-of-line", EditorAction::BeginningOfLine),
+    ("bracketed-paste-begin", EditorAction::BracketedPasteBegin),
+    ("clear-screen", EditorAction::ClearScreen),
+    ("complete", EditorAction::Complete),
+    ("delete-char", EditorAction::DeleteChar),
+    ("end-of-line", EditorAction::EndOfLine),
+    ("forward-char", EditorAction::ForwardChar),
+    ("forward-search-history", EditorAction::ForwardSearchHistory),
+    ("forward-word", EditorAction::ForwardWord),
+    ("history-search-backward", EditorAction::HistorySearchBackward),
+    ("history-search-forward", EditorAction::HistorySearchForward),
+    ("kill-line", EditorAction::KillLine),
+    ("kill-word", EditorAction::KillWord),
+    ("menu-complete", EditorAction::MenuComplete),
+    ("menu-complete-backward", EditorAction::MenuCompleteBackward),
+    ("next-history", EditorAction::NextHistory),
+    ("previous-history", EditorAction::PreviousHistory),
+    ("redo", EditorAction::Redo),
+    ("reverse-search-history", EditorAction::ReverseSearchHistory),
+    ("self-insert", EditorAction::SelfInsert),
+    ("undo", EditorAction::Undo),
+    ("unix-line-discard", EditorAction::UnixLineDiscard),
+    ("unix-word-rubout", EditorAction::UnixWordRubout),
+    ("vi-movement-mode", EditorAction::ViMovementMode),
+    ("yank", EditorAction::Yank),
+];
+
+impl EditorAction {
+    pub(crate) fn name(self) -> &'static str {
+        ACTION_NAMES.iter()
+            .find(|(_, action)| *action == self)
+            .map(|(name, _)| *name)
+            .unwrap_or("")
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        ACTION_NAMES.iter()
+            .find(|(action_name, _)| *action_name == name)
+            .map(|(_, action)| *action)
+    }
+
+    /// All function names in alphabetical order, as listed by `bind -l`.
+    pub(crate) fn names() -> impl Iterator<Item = &'static str> {
+        ACTION_NAMES.iter().map(|(name, _)| *name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum KeyBinding {
+    Action(EditorAction),
+    /// `bind -x`: a command line run by the shell with the edited line in READLINE_LINE
+    ShellCommand(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum KeymapName {
+    Emacs,
+    ViInsert,
+}
+
+impl KeymapName {
+    /// Accepts readline's keymap names; vi's command keymap is built into the vi state machine.
+    pub(crate) fn parse(name: &str) -> Result<Self, anyhow::Error> {
+        match name {
+            "emacs" | "emacs-standard" => Ok(KeymapName::Emacs),
+            "vi-insert" => Ok(KeymapName::ViInsert),
+            "vi" | "vi-command" | "vi-move" => Err(anyhow::anyhow!("{}: keymap cannot be changed", name)),
+            _ => Err(anyhow::anyhow!("{}: invalid keymap name", name)),
+        }
+    }
+}
+
+/// Key sequences, such as "\x01" or "\x1b[1;5C", mapped to what they do.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Keymap {
+    bindings: BTreeMap<String, KeyBinding>,
+}
+
+impl Keymap {
+    pub(crate) fn emacs() -> Self {
+        let mut keymap = Keymap::with_common_bindings();
+        keymap.bind_action("\x1bb", EditorAction::BackwardWord);
+        keymap.bind_action("\x1bf", EditorAction::ForwardWord);
+        keymap.bind_action("\x1bd", EditorAction::KillWord);
+        keymap.bind_action("\x1b\x7f", EditorAction::BackwardKillWord);
+        keymap
+    }
+
+    pub(crate) fn vi_insert() -> Self {
+        let mut keymap = Keymap::with_common_bindings();
+        keymap.bind_action("\x1b", EditorAction::ViMovementMode);
+        keymap
+    }
+
+    fn with_common_bindings() -> Self {
+        let mut keymap = Keymap::default();
+        let bindings = [
+            ("\r", EditorAction::AcceptLine),
+            ("\n", EditorAction::AcceptLine),
+            ("\x7f", EditorAction::BackwardDeleteChar),
+            ("\x08", EditorAction::BackwardDeleteChar),
+            ("\t", EditorAction::Complete),
+            ("\x01", EditorAction::BeginningOfLine),
+            ("\x02", EditorAction::BackwardChar),
+            ("\x04", EditorAction::DeleteChar),
+            ("\x05", EditorAction::EndOfLine),
+            ("\x06", EditorAction::ForwardChar),
+            ("\x07", EditorAction::Abort),
+            ("\x0b", EditorAction::KillLine),
+            ("\x0c", EditorAction::ClearScreen),
+            ("\x0e", EditorAction::NextHistory),
+            ("\x10", EditorAction::PreviousHistory),
+            ("\x12", EditorAction::ReverseSearchHistory),
+            ("\x13", EditorAction::ForwardSearchHistory),
+            ("\x15", EditorAction::UnixLineDiscard),
+            ("\x17", EditorAction::UnixWordRubout),
+            ("\x19", EditorAction::Yank),
+            ("\x1f", EditorAction::Undo),
+            ("\x18\x15", EditorAction::Undo),
+            // Meta-Ctrl-_ redoes, like in Emacs
+            ("\x1b\x1f", EditorAction::Redo),
+            // Cursor keys send CSI sequences in normal mode and SS3 sequences in application mode
+            ("\x1b[A", EditorAction::PreviousHistory),
+            ("\x1bOA", EditorAction::PreviousHistory),
+            ("\x1b[B", EditorAction::NextHistory),
+            ("\x1bOB", EditorAction::NextHistory),
+            ("\x1b[C", EditorAction::ForwardChar),
+            ("\x1bOC", EditorAction::ForwardChar),
+            ("\x1b[D", EditorAction::BackwardChar),
+            ("\x1bOD", EditorAction::BackwardChar),
+            ("\x1b[H", EditorAction::BeginningOfLine),
+            ("\x1bOH", EditorAction::BeginningOfLine),
+            ("\x1b[1~", EditorAction::BeginningOfLine),
+            ("\x1b[7~", EditorAction::BeginningOfLine),
+            ("\x1b[F", EditorAction::EndOfLine),
+            ("\x1bOF", EditorAction::EndOfLine),
+            ("\x1b[4~", EditorAction::EndOfLine),
+            ("\x1b[8~", EditorAction::EndOfLine),
+            ("\x1b[3~", EditorAction::DeleteChar),
+            ("\x1b[1;5C", EditorAction::ForwardWord),
+            ("\x1b[1;5D", EditorAction::BackwardWord),
+            // Shift-Tab
+            ("\x1b[Z", EditorAction::MenuCompleteBackward),
+            // Sent by the terminal before pasted text once bracketed paste mode is on
+            ("\x1b[200~", EditorAction::BracketedPasteBegin),
+        ];
+        for (sequence, action) in bindings {
+            keymap.bind_action(sequence, action);
+        }
+        keymap
+    }
+
+    fn bind_action(&mut self, sequence: &str, action: EditorAction) {
+        self.bind(sequence, KeyBinding::Action(action));
+    }
+
+    pub(crate) fn bind(&mut self, sequence: &str, binding: KeyBinding) {
+        self.bindings.insert(sequence.to_string(), binding);
+    }
+
+    pub(crate) fn unbind(&mut self, sequence: &str) -> bool {
+        self.bindings.remove(sequence).is_some()
+    }
+
+    pub(crate) fn get(&self, sequence: &str) -> Option<&KeyBinding> {
+        self.bindings.get(sequence)
+    }
+
+    /// Whether some longer key sequence starts with `sequence`, so that more keys have to be read.
+    pub(crate) fn is_prefix(&self, sequence: &str) -> bool {
+        self.bindings.range::<str, _>((Bound::Excluded(sequence), Bound::Unbounded))
+            .next()
+            .map(|(bound_sequence, _)| bound_sequence.starts_with(sequence))
+            .unwrap_or(false)
+    }
+
+    pub(crate) fn bindings(&self) -> impl Iterator<Item = (&str, &KeyBinding)> {
+        self.bindings.iter().map(|(sequence, binding)| (sequence.as_str(), binding))
+    }
+
+    /// Key sequences bound to the action, in sorted order.
+    pub(crate) fn sequences_for(&self, action: EditorAction) -> Vec<&str> {
+        self.bindings()
+            .filter(|(_, binding)| **binding == KeyBinding::Action(action))
+            .map(|(sequence, _)| sequence)
+            .collect()
+    }
+}
+
+struct Keymaps {
+    emacs: Keymap,
+    vi_insert: Keymap,
+}
+
+impl Keymaps {
+    fn get_mut(&mut self, name: KeymapName) -> &mut Keymap {
+        match name {
+            KeymapName::Emacs => &mut self.emacs,
+            KeymapName::ViInsert => &mut self.vi_insert,
+        }
+    }
+}
+
+lazy_static! {
+    static ref KEYMAPS: RwLock<Keymaps> = RwLock::new(Keymaps { emacs: Keymap::emacs(), vi_insert: Keymap::vi_insert() });
+}
+
+pub(crate) fn current(name: KeymapName) -> Keymap {
+    match KEYMAPS.read() {
+        Ok(keymaps) => match name {
+            KeymapName::Emacs => keymaps.emacs.clone(),
+            KeymapName::ViInsert => keymaps.vi_insert.clone(),
+        },
+        Err(_) => match name {
+            KeymapName::Emacs => Keymap::emacs(),
+            KeymapName::ViInsert => Keymap::vi_insert(),
+        },
+    }
+}
+
+pub(crate) fn bind(name: KeymapName, sequence: &str, binding: KeyBinding) -> Result<(), anyhow::Error> {
+    let mut keymaps = KEYMAPS.write()
+        .map_err(|_| anyhow::anyhow!("Key bindings are unavailable"))?;
+    keymaps.get_mut(name).bind(sequence, binding);
+    Ok(())
+}
+
+pub(crate) fn unbind(name: KeymapName, sequence: &str) -> Result<bool, anyhow::Error> {
+    let mut keymaps = KEYMAPS.write()
+        .map_err(|_| anyhow::anyhow!("Key bindings are unavailable"))?;
+    Ok(keymaps.get_mut(name).unbind(sequence))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_names_round_trip() {
+        for name in EditorAction::names() {
+            let action = EditorAction::from_name(name).unwrap();
+            assert_eq!(action.name(), name);
+        }
+        assert_eq!(EditorAction::from_name("no-such-function"), None);
+    }
+
+    #[test]
+    fn test_action_names_are_sorted() {
+        let names: Vec<&str> = EditorAction::names().collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn test_default_emacs_bindings() {
+        let keymap = Keymap::emacs();
+        assert_eq!(keymap.get("\x01"), Some(&KeyBinding::Action(EditorAction::BeginningOfLine)));
+        assert_eq!(keymap.get("\x1b[1;5C"), Some(&KeyBinding::Action(EditorAction::ForwardWord)));
+        assert_eq!(keymap.get("\x1bd"), Some(&KeyBinding::Action(EditorAction::KillWord)));
+        assert_eq!(keymap.get("\x1b"), None);
+    }
+
+    #[test]
+    fn test_vi_insert_binds_escape() {
+        let keymap = Keymap::vi_insert();
+        assert_eq!(keymap.get("\x1b"), Some(&KeyBinding::Action(EditorAction::ViMovementMode)));
+        assert_eq!(keymap.get("\x1bb"), None);
+        assert!(keymap.is_prefix("\x1b"));
+    }
+
+    #[test]
+    fn test_is_prefix() {
+        let keymap = Keymap::emacs();
+        assert!(keymap.is_prefix("\x1b"));
+        assert!(keymap.is_prefix("\x1b["));
+        assert!(keymap.is_prefix("\x1b[1;"));
+        assert!(!keymap.is_prefix("\x1b[A"));
+        assert!(!keymap.is_prefix("\x01"));
+        assert!(!keymap.is_prefix("a"));
+    }
+
+    #[test]
+    fn test_bind_and_unbind() {
+        let mut keymap = Keymap::emacs();
+        keymap.bind("\x1d\x05", KeyBinding::ShellCommand("ls".to_string()));
+        assert!(keymap.is_prefix("\x1d"));
+        assert_eq!(keymap.get("\x1d\x05"), Some(&KeyBinding::ShellCommand("ls".to_string())));
+        assert!(keymap.unbind("\x1d\x05"));
+        assert!(!keymap.unbind("\x1d\x05"));
+        assert!(!keymap.is_prefix("\x1d"));
+    }
+
+    #[test]
+    fn test_sequences_for() {
+        let keymap = Keymap::emacs();
+        assert_eq!(keymap.sequences_for(EditorAction::ReverseSearchHistory), vec!["\x12"]);
+        assert!(keymap.sequences_for(EditorAction::ViMovementMode).is_empty());
+    }
+
+    #[test]
+    fn test_parse_keymap_name() {
+        assert_eq!(KeymapName::parse("emacs-standard").ok(), Some(KeymapName::Emacs));
+        assert_eq!(KeymapName::parse("vi-insert").ok(), Some(KeymapName::ViInsert));
+        assert!(KeymapName::parse("vi-command").is_err());
+        assert!(KeymapName::parse("nano").is_err());
+    }
+}
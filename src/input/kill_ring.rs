@@ -0,0 +1,62 @@
+const KILL_RING_CAPACITY: usize = 16;
+
+pub(crate) struct KillRing {
+    entries: Vec<String>
+}
+
+impl KillRing {
+    pub(crate) fn new() -> Self {
+        KillRing { entries: Vec::new() }
+    }
+
+    pub(crate) fn push(&mut self, killed: String) {
+        if killed.is_empty() {
+            return;
+        }
+        if self.entries.len() == KILL_RING_CAPACITY {
+            self.entries.remove(0);
+        }
+        self.entries.push(killed);
+    }
+
+    pub(crate) fn yank(&self) -> Option<&str> {
+        self.entries.last().map(|s| s.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yank_returns_most_recent_kill() {
+        let mut kill_ring = KillRing::new();
+        kill_ring.push("first".to_string());
+        kill_ring.push("second".to_string());
+        assert_eq!(kill_ring.yank(), Some("second"));
+    }
+
+    #[test]
+    fn test_yank_empty_ring() {
+        let kill_ring = KillRing::new();
+        assert_eq!(kill_ring.yank(), None);
+    }
+
+    #[test]
+    fn test_empty_kills_are_ignored() {
+        let mut kill_ring = KillRing::new();
+        kill_ring.push("text".to_string());
+        kill_ring.push(String::new());
+        assert_eq!(kill_ring.yank(), Some("text"));
+    }
+
+    #[test]
+    fn test_capacity_is_bounded() {
+        let mut kill_ring = KillRing::new();
+        for i in 0..KILL_RING_CAPACITY + 5 {
+            kill_ring.push(i.to_string());
+        }
+        assert_eq!(kill_ring.entries.len(), KILL_RING_CAPACITY);
+        assert_eq!(kill_ring.entries[0], "5");
+    }
+}
@@ -0,0 +1,681 @@
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EditKind {
+    Typing,
+    DeletingBackward,
+    DeletingForward,
+    Other,
+}
+
+/// One change of the text: `removed` was replaced with `inserted` at byte position `position`.
+#[derive(Debug, Clone)]
+struct Edit {
+    position: usize,
+    removed: String,
+    inserted: String,
+    cursor_before: usize,
+    cursor_after: usize,
+    kind: EditKind,
+}
+
+impl Edit {
+    /// Extends the edit with the next one when both belong to the same undo step,
+    /// so that a typed word or a run of deleted characters is undone at once.
+    fn merge(&mut self, next: &Edit) -> bool {
+        let merged = match (self.kind, next.kind) {
+            (EditKind::Typing, EditKind::Typing) => {
+                let starts_new_word = next.inserted.starts_with(char::is_whitespace)
+                    && !self.inserted.ends_with(char::is_whitespace);
+                if next.position != self.position + self.inserted.len() || starts_new_word {
+                    return false;
+                }
+                self.inserted.push_str(&next.inserted);
+                true
+            }
+            (EditKind::DeletingBackward, EditKind::DeletingBackward) if next.position + next.removed.len() == self.position => {
+                self.removed.insert_str(0, &next.removed);
+                self.position = next.position;
+                true
+            }
+            (EditKind::DeletingForward, EditKind::DeletingForward) if next.position == self.position => {
+                self.removed.push_str(&next.removed);
+                true
+            }
+            _ => false,
+        };
+        if merged {
+            self.cursor_after = next.cursor_after;
+        }
+        merged
+    }
+}
+
+pub(crate) struct LineBuffer {
+    text: String,
+    cursor: usize,
+    /// Undo steps, each holding the edits it reverts at once
+    undo_log: Vec<Vec<Edit>>,
+    redo_log: Vec<Vec<Edit>>,
+    /// Set while edits are collected into a single undo step, e.g. during a vi insert
+    grouping: bool,
+    /// Whether the next edit may extend the last undo step
+    can_merge: bool,
+}
+
+impl LineBuffer {
+    pub(crate) fn new() -> Self {
+        LineBuffer::from_str_with_cursor("", 0)
+    }
+
+    pub(crate) fn from_str_with_cursor(text: &str, cursor: usize) -> Self {
+        LineBuffer {
+            text: text.to_string(),
+            cursor,
+            undo_log: Vec::new(),
+            redo_log: Vec::new(),
+            grouping: false,
+            can_merge: false,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub(crate) fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub(crate) fn set_cursor(&mut self, cursor: usize) {
+        if self.text.is_char_boundary(cursor) {
+            self.cursor = cursor;
+        }
+    }
+
+    pub(crate) fn is_cursor_at_end(&self) -> bool {
+        self.cursor == self.text.len()
+    }
+
+    pub(crate) fn text_before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    #[cfg(test)]
+    pub(crate) fn text_after_cursor(&self) -> &str {
+        &self.text[self.cursor..]
+    }
+
+    pub(crate) fn insert_char(&mut self, ch: char) {
+        let cursor = self.cursor;
+        self.edit(cursor, cursor, &ch.to_string(), cursor + ch.len_utf8(), EditKind::Typing);
+    }
+
+    pub(crate) fn insert_str(&mut self, s: &str) {
+        let cursor = self.cursor;
+        self.edit(cursor, cursor, s, cursor + s.len(), EditKind::Other);
+    }
+
+    /// Replaces the whole line, e.g. when recalling a history entry, and puts the cursor at the end.
+    pub(crate) fn replace(&mut self, text: &str) {
+        self.edit(0, self.text.len(), text, text.len(), EditKind::Other);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.replace("");
+    }
+
+    pub(crate) fn delete_before_cursor(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        let start = self.previous_boundary(self.cursor);
+        self.edit(start, self.cursor, "", start, EditKind::DeletingBackward);
+        true
+    }
+
+    pub(crate) fn delete_at_cursor(&mut self) -> bool {
+        if self.is_cursor_at_end() {
+            return false;
+        }
+        let end = self.next_boundary(self.cursor);
+        self.edit(self.cursor, end, "", self.cursor, EditKind::DeletingForward);
+        true
+    }
+
+    /// Collects the following edits into one undo step until `end_undo_group` is called.
+    pub(crate) fn begin_undo_group(&mut self) {
+        self.end_undo_group();
+        self.undo_log.push(Vec::new());
+        self.grouping = true;
+    }
+
+    pub(crate) fn end_undo_group(&mut self) {
+        if self.grouping && self.undo_log.last().map(|step| step.is_empty()).unwrap_or(false) {
+            self.undo_log.pop();
+        }
+        self.grouping = false;
+        self.can_merge = false;
+    }
+
+    /// Reverts the last undo step, returning false when there is nothing to undo.
+    pub(crate) fn undo(&mut self) -> bool {
+        self.end_undo_group();
+        let step = match self.undo_log.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+        for edit in step.iter().rev() {
+            self.text.replace_range(edit.position..edit.position + edit.inserted.len(), &edit.removed);
+            self.cursor = edit.cursor_before;
+        }
+        self.redo_log.push(step);
+        true
+    }
+
+    /// Applies the last undone step again, returning false when there is nothing to redo.
+    pub(crate) fn redo(&mut self) -> bool {
+        self.end_undo_group();
+        let step = match self.redo_log.pop() {
+            Some(step) => step,
+            None => return false,
+        };
+        for edit in step.iter() {
+            self.text.replace_range(edit.position..edit.position + edit.removed.len(), &edit.inserted);
+            self.cursor = edit.cursor_after;
+        }
+        self.undo_log.push(step);
+        true
+    }
+
+    /// Replaces the text between two byte positions, moves the cursor and records the change in the undo log.
+    fn edit(&mut self, start: usize, end: usize, inserted: &str, cursor_after: usize, kind: EditKind) -> String {
+        let removed = self.text[start..end].to_string();
+        let cursor_before = self.cursor;
+        self.cursor = cursor_after;
+        if removed == inserted {
+            return removed;
+        }
+        self.text.replace_range(start..end, inserted);
+        let edit = Edit { position: start, removed: removed.clone(), inserted: inserted.to_string(), cursor_before, cursor_after, kind };
+        self.redo_log.clear();
+        match self.undo_log.last_mut() {
+            Some(step) if self.grouping => step.push(edit),
+            Some(step) if self.can_merge && step.len() == 1 => {
+                if !step[0].merge(&edit) {
+                    self.undo_log.push(vec![edit]);
+                }
+            }
+            _ => {
+                self.undo_log.push(vec![edit]);
+                self.can_merge = true;
+            }
+        }
+        removed
+    }
+
+    pub(crate) fn move_left(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor = self.previous_boundary(self.cursor);
+        true
+    }
+
+    pub(crate) fn move_right(&mut self) -> bool {
+        if self.is_cursor_at_end() {
+            return false;
+        }
+        self.cursor = self.next_boundary(self.cursor);
+        true
+    }
+
+    pub(crate) fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub(crate) fn move_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    /// Moves to the same column on the previous line of a multi-line buffer, returning false on the first line.
+    pub(crate) fn move_line_up(&mut self) -> bool {
+        let line_start = self.line_start(self.cursor);
+        if line_start == 0 {
+            return false;
+        }
+        let column = self.text[line_start..self.cursor].graphemes(true).count();
+        self.cursor = self.position_in_line(self.line_start(line_start - 1), column);
+        true
+    }
+
+    /// Moves to the same column on the next line of a multi-line buffer, returning false on the last line.
+    pub(crate) fn move_line_down(&mut self) -> bool {
+        let line_end = match self.text[self.cursor..].find('\n') {
+            Some(offset) => self.cursor + offset,
+            None => return false,
+        };
+        let column = self.text[self.line_start(self.cursor)..self.cursor].graphemes(true).count();
+        self.cursor = self.position_in_line(line_end + 1, column);
+        true
+    }
+
+    fn line_start(&self, position: usize) -> usize {
+        self.text[..position].rfind('\n').map(|newline| newline + 1).unwrap_or(0)
+    }
+
+    /// The byte position of a column on the line starting at `line_start`, or of the line end for a shorter line.
+    fn position_in_line(&self, line_start: usize, column: usize) -> usize {
+        let line = self.text[line_start..].split('\n').next().unwrap_or("");
+        line_start + line.grapheme_indices(true).nth(column).map(|(index, _)| index).unwrap_or(line.len())
+    }
+
+    /// Moves to the start of the current or previous word, words being runs of alphanumeric characters (Alt-B).
+    pub(crate) fn move_word_left(&mut self) {
+        self.cursor = self.previous_word_start(self.cursor, |ch| !ch.is_alphanumeric());
+    }
+
+    /// Moves to the end of the current or next word (Alt-F).
+    pub(crate) fn move_word_right(&mut self) {
+        self.cursor = self.next_word_end(self.cursor);
+    }
+
+    /// Kills the whitespace-delimited word before the cursor (Ctrl-W).
+    pub(crate) fn kill_word_before_cursor(&mut self) -> String {
+        let start = self.previous_word_start(self.cursor, char::is_whitespace);
+        self.delete_range(start, self.cursor)
+    }
+
+    /// Kills up to the end of the current or next alphanumeric word (Alt-D).
+    pub(crate) fn kill_word_after_cursor(&mut self) -> String {
+        let end = self.next_word_end(self.cursor);
+        self.edit(self.cursor, end, "", self.cursor, EditKind::Other)
+    }
+
+    /// Kills back to the start of the current or previous alphanumeric word (Alt-Backspace).
+    pub(crate) fn kill_alphanumeric_word_before_cursor(&mut self) -> String {
+        let start = self.previous_word_start(self.cursor, |ch| !ch.is_alphanumeric());
+        self.delete_range(start, self.cursor)
+    }
+
+    /// Kills from the start of the line up to the cursor (Ctrl-U).
+    pub(crate) fn kill_to_start(&mut self) -> String {
+        self.delete_range(0, self.cursor)
+    }
+
+    /// Kills from the cursor to the end of the line (Ctrl-K).
+    pub(crate) fn kill_to_end(&mut self) -> String {
+        self.edit(self.cursor, self.text.len(), "", self.cursor, EditKind::Other)
+    }
+
+    /// Replaces the text between two byte positions, e.g. a word with its completion, and puts the cursor after it.
+    pub(crate) fn replace_range(&mut self, start: usize, end: usize, text: &str) {
+        self.edit(start, end, text, start + text.len(), EditKind::Other);
+    }
+
+    /// Removes the text between two byte positions and puts the cursor where it started.
+    pub(crate) fn delete_range(&mut self, start: usize, end: usize) -> String {
+        self.edit(start, end, "", start, EditKind::Other)
+    }
+
+    fn previous_word_start(&self, from: usize, is_separator: impl Fn(char) -> bool) -> usize {
+        let before = &self.text[..from];
+        let trimmed = before.trim_end_matches(|ch: char| is_separator(ch));
+        match trimmed.rfind(|ch: char| is_separator(ch)) {
+            Some(idx) => idx + trimmed[idx..].chars().next().map(|ch| ch.len_utf8()).unwrap_or(1),
+            None => 0,
+        }
+    }
+
+    fn next_word_end(&self, from: usize) -> usize {
+        let after = &self.text[from..];
+        let word_start = after.find(|ch: char| ch.is_alphanumeric()).unwrap_or(after.len());
+        let word_length = after[word_start..].find(|ch: char| !ch.is_alphanumeric()).unwrap_or(after.len() - word_start);
+        from + word_start + word_length
+    }
+
+    /// Returns the byte index of the grapheme cluster boundary before `from`, so that e.g. "e\u{301}" is deleted at once.
+    pub(crate) fn previous_boundary(&self, from: usize) -> usize {
+        let mut cursor = GraphemeCursor::new(from, self.text.len(), true);
+        cursor.prev_boundary(&self.text, 0).ok().flatten().unwrap_or(0)
+    }
+
+    pub(crate) fn next_boundary(&self, from: usize) -> usize {
+        let mut cursor = GraphemeCursor::new(from, self.text.len(), true);
+        cursor.next_boundary(&self.text, 0).ok().flatten().unwrap_or(self.text.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_between_lines() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo 'a\nb\nlonger'", 12);
+        assert!(buffer.move_line_up());
+        assert_eq!(buffer.cursor(), 9);
+        assert!(buffer.move_line_up());
+        assert_eq!(buffer.cursor(), 1);
+        assert!(!buffer.move_line_up());
+        assert!(buffer.move_line_down());
+        assert!(buffer.move_line_down());
+        assert_eq!(buffer.cursor(), 11);
+        assert!(!buffer.move_line_down());
+    }
+
+    #[test]
+    fn test_insert_in_the_middle() {
+        let mut buffer = LineBuffer::from_str_with_cursor("eho", 1);
+        buffer.insert_char('c');
+        assert_eq!(buffer.as_str(), "echo");
+        assert_eq!(buffer.cursor(), 2);
+    }
+
+    #[test]
+    fn test_insert_str_at_cursor() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo world", 5);
+        buffer.insert_str("hello ");
+        assert_eq!(buffer.as_str(), "echo hello world");
+        assert_eq!(buffer.cursor(), 11);
+    }
+
+    #[test]
+    fn test_delete_before_cursor_in_the_middle() {
+        let mut buffer = LineBuffer::from_str_with_cursor("ecxho", 3);
+        assert!(buffer.delete_before_cursor());
+        assert_eq!(buffer.as_str(), "echo");
+        assert_eq!(buffer.cursor(), 2);
+    }
+
+    #[test]
+    fn test_delete_before_cursor_at_start() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo", 0);
+        assert!(!buffer.delete_before_cursor());
+        assert_eq!(buffer.as_str(), "echo");
+    }
+
+    #[test]
+    fn test_delete_at_cursor() {
+        let mut buffer = LineBuffer::from_str_with_cursor("xecho", 0);
+        assert!(buffer.delete_at_cursor());
+        assert_eq!(buffer.as_str(), "echo");
+        assert_eq!(buffer.cursor(), 0);
+    }
+
+    #[test]
+    fn test_delete_at_cursor_at_end() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo", 4);
+        assert!(!buffer.delete_at_cursor());
+        assert_eq!(buffer.as_str(), "echo");
+    }
+
+    #[test]
+    fn test_move_left_and_right() {
+        let mut buffer = LineBuffer::from_str_with_cursor("ab", 0);
+        assert!(!buffer.move_left());
+        assert!(buffer.move_right());
+        assert!(buffer.move_right());
+        assert!(!buffer.move_right());
+        assert_eq!(buffer.cursor(), 2);
+        assert!(buffer.move_left());
+        assert_eq!(buffer.cursor(), 1);
+    }
+
+    #[test]
+    fn test_move_home_and_end() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo hello", 4);
+        buffer.move_home();
+        assert_eq!(buffer.cursor(), 0);
+        buffer.move_end();
+        assert_eq!(buffer.cursor(), 10);
+    }
+
+    #[test]
+    fn test_move_word_left() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cat /tmp/file.txt", 17);
+        buffer.move_word_left();
+        assert_eq!(buffer.cursor(), 14);
+        buffer.move_word_left();
+        assert_eq!(buffer.cursor(), 9);
+        buffer.move_word_left();
+        assert_eq!(buffer.cursor(), 5);
+        buffer.move_word_left();
+        assert_eq!(buffer.cursor(), 0);
+    }
+
+    #[test]
+    fn test_move_word_right() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cat /tmp/file.txt", 0);
+        buffer.move_word_right();
+        assert_eq!(buffer.cursor(), 3);
+        buffer.move_word_right();
+        assert_eq!(buffer.cursor(), 8);
+        buffer.move_word_right();
+        assert_eq!(buffer.cursor(), 13);
+        buffer.move_word_right();
+        assert_eq!(buffer.cursor(), 17);
+        buffer.move_word_right();
+        assert_eq!(buffer.cursor(), 17);
+    }
+
+    #[test]
+    fn test_kill_word_before_cursor() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cat /tmp/file.txt  ", 19);
+        assert_eq!(buffer.kill_word_before_cursor(), "/tmp/file.txt  ");
+        assert_eq!(buffer.as_str(), "cat ");
+        assert_eq!(buffer.cursor(), 4);
+    }
+
+    #[test]
+    fn test_kill_word_before_cursor_in_the_middle() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo hello world", 10);
+        assert_eq!(buffer.kill_word_before_cursor(), "hello");
+        assert_eq!(buffer.as_str(), "echo  world");
+        assert_eq!(buffer.cursor(), 5);
+    }
+
+    #[test]
+    fn test_kill_word_after_cursor() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cat /tmp/file.txt", 3);
+        assert_eq!(buffer.kill_word_after_cursor(), " /tmp");
+        assert_eq!(buffer.as_str(), "cat/file.txt");
+        assert_eq!(buffer.cursor(), 3);
+    }
+
+    #[test]
+    fn test_kill_alphanumeric_word_before_cursor() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cat /tmp/file.txt", 13);
+        assert_eq!(buffer.kill_alphanumeric_word_before_cursor(), "file");
+        assert_eq!(buffer.as_str(), "cat /tmp/.txt");
+        assert_eq!(buffer.cursor(), 9);
+    }
+
+    #[test]
+    fn test_kill_to_start() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo hello", 5);
+        assert_eq!(buffer.kill_to_start(), "echo ");
+        assert_eq!(buffer.as_str(), "hello");
+        assert_eq!(buffer.cursor(), 0);
+    }
+
+    #[test]
+    fn test_kill_to_end() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo hello", 4);
+        assert_eq!(buffer.kill_to_end(), " hello");
+        assert_eq!(buffer.as_str(), "echo");
+        assert_eq!(buffer.cursor(), 4);
+    }
+
+    #[test]
+    fn test_delete_before_cursor_multi_byte_character() {
+        let mut buffer = LineBuffer::from_str_with_cursor("päivä", 7);
+        assert!(buffer.delete_before_cursor());
+        assert_eq!(buffer.as_str(), "päiv");
+        assert!(buffer.move_left());
+        assert!(buffer.move_left());
+        assert!(buffer.delete_before_cursor());
+        assert_eq!(buffer.as_str(), "piv");
+        assert_eq!(buffer.cursor(), 1);
+    }
+
+    #[test]
+    fn test_delete_before_cursor_grapheme_cluster() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cafe\u{301}", 6);
+        assert!(buffer.delete_before_cursor());
+        assert_eq!(buffer.as_str(), "caf");
+    }
+
+    #[test]
+    fn test_move_over_emoji_with_modifiers() {
+        let text = "a👍🏽b";
+        let mut buffer = LineBuffer::from_str_with_cursor(text, 1);
+        assert!(buffer.move_right());
+        assert_eq!(buffer.text_before_cursor(), "a👍🏽");
+        assert!(buffer.delete_at_cursor());
+        assert!(buffer.move_left());
+        assert_eq!(buffer.cursor(), 1);
+        assert!(buffer.delete_at_cursor());
+        assert_eq!(buffer.as_str(), "a");
+    }
+
+    #[test]
+    fn test_move_word_over_non_ascii_words() {
+        let mut buffer = LineBuffer::from_str_with_cursor("git commit -m 'päivitä yö'", 0);
+        buffer.move_end();
+        buffer.move_word_left();
+        assert_eq!(buffer.text_after_cursor(), "yö'");
+        buffer.move_word_left();
+        assert_eq!(buffer.text_after_cursor(), "päivitä yö'");
+    }
+
+    #[test]
+    fn test_set_cursor_ignores_position_inside_character() {
+        let mut buffer = LineBuffer::from_str_with_cursor("yö", 0);
+        buffer.set_cursor(2);
+        assert_eq!(buffer.cursor(), 0);
+        buffer.set_cursor(1);
+        assert_eq!(buffer.cursor(), 1);
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo hello world", 16);
+        assert_eq!(buffer.delete_range(5, 11), "hello ");
+        assert_eq!(buffer.as_str(), "echo world");
+        assert_eq!(buffer.cursor(), 5);
+    }
+
+    #[test]
+    fn test_replace_moves_cursor_to_end() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo", 0);
+        buffer.replace("pwd");
+        assert_eq!(buffer.as_str(), "pwd");
+        assert_eq!(buffer.cursor(), 3);
+    }
+
+    fn type_text(buffer: &mut LineBuffer, text: &str) {
+        for ch in text.chars() {
+            buffer.insert_char(ch);
+        }
+    }
+
+    #[test]
+    fn test_undo_typed_words_one_at_a_time() {
+        let mut buffer = LineBuffer::new();
+        type_text(&mut buffer, "echo hello world");
+        assert!(buffer.undo());
+        assert_eq!(buffer.as_str(), "echo hello");
+        assert!(buffer.undo());
+        assert_eq!(buffer.as_str(), "echo");
+        assert!(buffer.undo());
+        assert_eq!(buffer.as_str(), "");
+        assert!(!buffer.undo());
+    }
+
+    #[test]
+    fn test_redo() {
+        let mut buffer = LineBuffer::new();
+        type_text(&mut buffer, "ls -la");
+        buffer.undo();
+        buffer.undo();
+        assert!(buffer.redo());
+        assert_eq!(buffer.as_str(), "ls");
+        assert!(buffer.redo());
+        assert_eq!(buffer.as_str(), "ls -la");
+        assert_eq!(buffer.cursor(), 6);
+        assert!(!buffer.redo());
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo() {
+        let mut buffer = LineBuffer::new();
+        type_text(&mut buffer, "ls -la");
+        buffer.undo();
+        type_text(&mut buffer, " -R");
+        assert!(!buffer.redo());
+        assert_eq!(buffer.as_str(), "ls -R");
+    }
+
+    #[test]
+    fn test_undo_restores_cursor() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo  world", 5);
+        type_text(&mut buffer, "hello");
+        buffer.move_end();
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "echo  world");
+        assert_eq!(buffer.cursor(), 5);
+    }
+
+    #[test]
+    fn test_undo_run_of_deletions() {
+        let mut buffer = LineBuffer::from_str_with_cursor("cargo build", 11);
+        for _ in 0..5 {
+            buffer.delete_before_cursor();
+        }
+        buffer.move_home();
+        buffer.delete_at_cursor();
+        buffer.delete_at_cursor();
+        assert_eq!(buffer.as_str(), "rgo ");
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "cargo ");
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "cargo build");
+    }
+
+    #[test]
+    fn test_undo_inserted_and_replaced_text() {
+        let mut buffer = LineBuffer::new();
+        type_text(&mut buffer, "ec");
+        buffer.insert_str("ho ");
+        buffer.replace("cargo test");
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "echo ");
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "ec");
+    }
+
+    #[test]
+    fn test_undo_group() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo hello", 5);
+        buffer.begin_undo_group();
+        buffer.kill_to_end();
+        type_text(&mut buffer, "bye now");
+        buffer.end_undo_group();
+        assert_eq!(buffer.as_str(), "echo bye now");
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "echo hello");
+        buffer.redo();
+        assert_eq!(buffer.as_str(), "echo bye now");
+    }
+
+    #[test]
+    fn test_empty_undo_group_is_dropped() {
+        let mut buffer = LineBuffer::new();
+        type_text(&mut buffer, "ls");
+        buffer.begin_undo_group();
+        buffer.end_undo_group();
+        buffer.undo();
+        assert_eq!(buffer.as_str(), "");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::input::settings;
+
+/// How a candidate matches the typed text, the preferred kinds first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum MatchKind {
+    Prefix,
+    PrefixIgnoringCase,
+    Substring,
+    /// The typed characters appear in order, with others in between
+    Subsequence,
+}
+
+/// Ranks a match: by kind, then by how far the typed characters are spread out in the candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Score {
+    pub(crate) kind: MatchKind,
+    penalty: usize,
+}
+
+/// Matches candidates against the typed text by prefix, and depending on the `completion-ignore-case` and
+/// `completion-fuzzy` settings also regardless of case, by substring and by subsequence.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Matcher {
+    pub(crate) ignore_case: bool,
+    pub(crate) fuzzy: bool,
+}
+
+impl Matcher {
+    pub(crate) fn current() -> Self {
+        let settings = settings::current();
+        Matcher { ignore_case: settings.completion_ignore_case, fuzzy: settings.completion_fuzzy }
+    }
+
+    pub(crate) fn score(&self, typed: &str, candidate: &str) -> Option<Score> {
+        if candidate.starts_with(typed) {
+            return Some(Score { kind: MatchKind::Prefix, penalty: 0 });
+        }
+        let (typed, candidate) = if self.ignore_case {
+            (typed.to_lowercase(), candidate.to_lowercase())
+        } else {
+            (typed.to_string(), candidate.to_string())
+        };
+        if self.ignore_case && candidate.starts_with(&typed) {
+            return Some(Score { kind: MatchKind::PrefixIgnoringCase, penalty: 0 });
+        }
+        if !self.fuzzy {
+            return None;
+        }
+        if let Some(index) = candidate.find(&typed) {
+            return Some(Score { kind: MatchKind::Substring, penalty: candidate[..index].chars().count() });
+        }
+        subsequence_penalty(&typed, &candidate).map(|penalty| Score { kind: MatchKind::Subsequence, penalty })
+    }
+
+    pub(crate) fn matches(&self, typed: &str, candidate: &str) -> bool {
+        self.score(typed, candidate).is_some()
+    }
+
+    /// Keeps the candidates that match, best first and then alphabetically, without duplicates.
+    pub(crate) fn rank<I: IntoIterator<Item = String>>(&self, typed: &str, candidates: I) -> Vec<String> {
+        self.rank_by(typed, candidates, |candidate| candidate)
+    }
+
+    /// Like `rank`, matching the typed text against the part of each candidate that `key` returns.
+    pub(crate) fn rank_by<I, F>(&self, typed: &str, candidates: I, key: F) -> Vec<String>
+    where
+        I: IntoIterator<Item = String>,
+        F: Fn(&str) -> &str,
+    {
+        let mut scored: Vec<(Score, String)> = candidates.into_iter()
+            .filter_map(|candidate| Some((self.score(typed, key(&candidate))?, candidate)))
+            .collect();
+        scored.sort();
+        let mut ranked: Vec<String> = Vec::with_capacity(scored.len());
+        for (_, candidate) in scored {
+            if !ranked.contains(&candidate) {
+                ranked.push(candidate);
+            }
+        }
+        ranked
+    }
+
+    /// The longest prefix shared by all matches. Ignoring case, letters that differ only in case are taken
+    /// from the typed text and end the prefix after it, so that the word gets the casing of the matches
+    /// wherever they agree.
+    pub(crate) fn common_prefix(&self, matches: &[String], typed: &str) -> Option<String> {
+        let (first, others) = matches.split_first()?;
+        let mut others: Vec<_> = others.iter().map(|other| other.chars()).collect();
+        let mut typed = typed.chars();
+        let mut prefix = String::new();
+        for ch in first.chars() {
+            let typed_ch = typed.next();
+            let mut same_case = true;
+            for other in &mut others {
+                match other.next() {
+                    Some(other_ch) if other_ch == ch => (),
+                    Some(other_ch) if self.ignore_case && other_ch.to_lowercase().eq(ch.to_lowercase()) => same_case = false,
+                    _ => return Some(prefix),
+                }
+            }
+            match typed_ch {
+                _ if same_case => prefix.push(ch),
+                Some(typed_ch) if typed_ch.to_lowercase().eq(ch.to_lowercase()) => prefix.push(typed_ch),
+                _ => break,
+            }
+        }
+        Some(prefix)
+    }
+}
+
+/// How many characters of the candidate are skipped before and between the typed ones.
+fn subsequence_penalty(typed: &str, candidate: &str) -> Option<usize> {
+    let mut candidate_chars = candidate.chars();
+    let mut penalty = 0;
+    for typed_ch in typed.chars() {
+        loop {
+            let ch = candidate_chars.next()?;
+            if ch == typed_ch {
+                break;
+            }
+            penalty += 1;
+        }
+    }
+    Some(penalty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Matcher = Matcher { ignore_case: false, fuzzy: false };
+    const IGNORE_CASE: Matcher = Matcher { ignore_case: true, fuzzy: false };
+    const FUZZY: Matcher = Matcher { ignore_case: true, fuzzy: true };
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_prefix_matching() {
+        assert!(PLAIN.matches("ec", "echo"));
+        assert!(!PLAIN.matches("EC", "echo"));
+        assert!(!PLAIN.matches("ch", "echo"));
+        assert!(IGNORE_CASE.matches("EC", "echo"));
+        assert!(!IGNORE_CASE.matches("ch", "echo"));
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        assert_eq!(FUZZY.score("ch", "echo").map(|score| score.kind), Some(MatchKind::Substring));
+        assert_eq!(FUZZY.score("gco", "git-checkout").map(|score| score.kind), Some(MatchKind::Subsequence));
+        assert!(!FUZZY.matches("oc", "echo"));
+        assert!(!Matcher { ignore_case: false, fuzzy: true }.matches("CH", "echo"));
+    }
+
+    #[test]
+    fn test_rank_prefers_prefixes() {
+        let candidates = strings(&["xmake", "Makefile", "make", "m-a-k-e", "cmake", "other"]);
+        assert_eq!(FUZZY.rank("make", candidates.clone()), vec!["make", "Makefile", "cmake", "xmake", "m-a-k-e"]);
+        assert_eq!(PLAIN.rank("make", candidates), vec!["make"]);
+    }
+
+    #[test]
+    fn test_rank_by_key() {
+        let candidates = strings(&["src/Main.rs", "src/lib.rs", "src/main.rs"]);
+        assert_eq!(IGNORE_CASE.rank_by("ma", candidates, |path| &path[4..]), vec!["src/main.rs", "src/Main.rs"]);
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        assert_eq!(PLAIN.common_prefix(&strings(&["echo", "exit"]), "e"), Some("e".to_string()));
+        assert_eq!(PLAIN.common_prefix(&strings(&["Makefile", "makefile"]), "m"), Some("".to_string()));
+        assert_eq!(PLAIN.common_prefix(&[], "m"), None);
+    }
+
+    #[test]
+    fn test_common_prefix_ignoring_case() {
+        // The typed word is corrected to the casing the matches agree on
+        assert_eq!(IGNORE_CASE.common_prefix(&strings(&["Makefile", "Makefile.am"]), "mak"), Some("Makefile".to_string()));
+        // Where they disagree, the typed letters are kept
+        assert_eq!(IGNORE_CASE.common_prefix(&strings(&["Makefile", "makefile.am"]), "mak"), Some("makefile".to_string()));
+        assert_eq!(IGNORE_CASE.common_prefix(&strings(&["README", "readme.txt"]), "r"), Some("r".to_string()));
+    }
+}
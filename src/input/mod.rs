@@ -1,207 +1,1069 @@
+use std::env;
 use std::io::{self, Write, Read};
-use termios::{Termios, tcsetattr, TCSANOW, ECHO, ICANON, IEXTEN, ISIG, VMIN, VTIME};
-use crate::input::autocompletion::AutoCompletion;
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+use termios::{Termios, tcsetattr, TCSANOW, ECHO, ICANON, IEXTEN, ISIG, IXON, VMIN, VTIME};
+use crate::input::autocompletion::{AutoCompletion, CompletionStyle, CompletionWord, Completions};
+use crate::input::completion_menu::{CompletionMenu, Grid, MenuMove};
+use crate::input::highlight::highlight;
+use crate::input::inputrc::InputrcReader;
+use crate::input::keymap::{EditorAction, KeyBinding, Keymap};
+use crate::input::kill_ring::KillRing;
+use crate::input::line_buffer::LineBuffer;
+use crate::input::matcher::Matcher;
+use crate::input::screen::{display_width, terminal_columns, terminal_rows, Layout, ScreenPosition};
+use crate::input::search::{IncrementalSearch, SearchDirection};
+use crate::input::settings::EditingMode;
+use crate::input::vi::{ViMode, ViOutcome, ViState};
+use crate::command::ParsedCommand;
 use crate::history::History;
+use crate::signals;
 
 pub mod autocompletion;
+mod completers;
+mod completion_menu;
+pub(crate) mod completion_spec;
+mod highlight;
+pub(crate) mod inputrc;
+pub(crate) mod keymap;
+mod kill_ring;
+mod line_buffer;
+pub(crate) mod matcher;
+mod screen;
+mod search;
+pub(crate) mod settings;
+mod vi;
 
 // Terminal control sequences
-const BACKSPACE_ERASE_SEQUENCE: &str = "\x08 \x08";
+const CLEAR_TO_END_OF_LINE_SEQUENCE: &str = "\x1b[K";
+const CLEAR_TO_END_OF_SCREEN_SEQUENCE: &str = "\x1b[J";
+const CLEAR_SCREEN_SEQUENCE: &str = "\x1b[H\x1b[2J";
+const DIM_SEQUENCE: &str = "\x1b[2m";
+const RESET_ATTRIBUTES_SEQUENCE: &str = "\x1b[0m";
+const ENABLE_BRACKETED_PASTE_SEQUENCE: &str = "\x1b[?2004h";
+const DISABLE_BRACKETED_PASTE_SEQUENCE: &str = "\x1b[?2004l";
+const BRACKETED_PASTE_END_SEQUENCE: &str = "\x1b[201~";
 const PROMPT: &str = "$ ";
+const CONTINUATION_PROMPT: &str = "> ";
+const STDIN_FILE_DESCRIPTOR: i32 = 0;
+const ESCAPE_SEQUENCE_TIMEOUT_MS: i32 = 50;
 
 // Special characters
 const NEWLINE: char = '\n';
 const CARRIAGE_RETURN: char = '\r';
-const TAB: char = '\t';
 const BACKSPACE: char = '\u{7f}';
 const DELETE: char = '\u{0008}';
 const BEEP: char = '\x07';
 const CTRL_C: char = '\u{0003}';
+const CTRL_D: char = '\u{0004}';
+const CTRL_G: char = '\u{0007}';
 const ESC: char = '\u{001b}';
 
-pub fn read_line_with_completion(autocomplete: &AutoCompletion, history: &History) -> Result<String, anyhow::Error> {
+lazy_static! {
+    /// Prompt drawn by `refresh_line`, including the editing mode indicator
+    static ref CURRENT_PROMPT: RwLock<String> = RwLock::new(PROMPT.to_string());
+    /// Row of the terminal cursor counted from the prompt row, non-zero while editing a multi-line buffer
+    static ref CURSOR_ROW: RwLock<usize> = RwLock::new(0);
+}
+
+pub enum ReadLine {
+    Line(String),
+    /// Ctrl-C discarded the line being edited
+    Interrupted,
+    /// Ctrl-D on an empty line
+    EndOfFile,
+    /// A key bound with `bind -x` was pressed; editing resumes with the line after the command ran
+    BoundCommand(String, EditedLine),
+}
+
+/// The text and the cursor byte position of a line whose editing is suspended.
+pub struct EditedLine {
+    pub text: String,
+    pub cursor: usize,
+}
+
+pub fn read_line_with_completion(autocomplete: &AutoCompletion, history: &History, initial_line: Option<EditedLine>) -> Result<ReadLine, anyhow::Error> {
     let raw_mode = RawMode::enable()?;
-    let mut input = String::new();
+    let mut input = LineBuffer::new();
+    let mut kill_ring = KillRing::new();
     let mut stdin = io::stdin();
-    let mut buffer = [0; 1];
     let mut last_tab_input: Option<String> = None;
+    let mut menu: Option<CompletionMenu> = None;
     let mut history_index: Option<usize> = None;
+    let mut displayed_suggestion: Option<String> = None;
+    let editing_mode = settings::current().editing_mode;
+    let keymap = keymap::current(inputrc::keymap_for_mode(editing_mode));
+    let mut vi = match editing_mode {
+        EditingMode::Vi => Some(ViState::new(&mut input)),
+        EditingMode::Emacs => None,
+    };
+    let mut vi_search: Option<(String, SearchDirection)> = None;
+    // A key that ended an incremental search, handled as if it was typed afterwards
+    let mut pending_key: Option<(String, Option<KeyBinding>)> = None;
+    set_current_prompt(prompt());
+    set_cursor_row(0);
+    if let Some(initial_line) = initial_line {
+        input.replace(&initial_line.text);
+        input.set_cursor(initial_line.cursor);
+        refresh_line(&input)?;
+    }
 
     loop {
-        stdin.read_exact(&mut buffer)?;
-        let ch = buffer[0] as char;
+        let (sequence, binding) = match pending_key.take() {
+            Some(key) => key,
+            None if !wait_for_key() => {
+                // Terminals rewrap the rows for the new width, so the cursor row is recomputed before drawing again
+                set_cursor_row(cursor_screen_position(&input).row);
+                match menu.as_ref() {
+                    Some(menu) => draw_menu(&input, menu)?,
+                    None => refresh_line(&input)?,
+                }
+                displayed_suggestion = None;
+                update_suggestion(&input, history, &mut displayed_suggestion)?;
+                continue;
+            }
+            None => read_key_sequence(&mut stdin, &keymap)?,
+        };
+
+        if sequence.starts_with(CTRL_C) {
+            clear_suggestion(&mut displayed_suggestion)?;
+            move_to_last_row(&input);
+            println!("^C");
+            drop(raw_mode);
+            return Ok(ReadLine::Interrupted);
+        }
+        let action = match binding {
+            Some(KeyBinding::Action(action)) => Some(action),
+            Some(KeyBinding::ShellCommand(command)) => {
+                clear_suggestion(&mut displayed_suggestion)?;
+                move_to_last_row(&input);
+                println!();
+                drop(raw_mode);
+                let line = EditedLine { text: input.as_str().to_string(), cursor: input.cursor() };
+                return Ok(ReadLine::BoundCommand(command, line));
+            }
+            None => None,
+        };
+
+        if let Some(vi) = vi.as_mut().filter(|vi| vi.mode() == ViMode::Normal) {
+            let key = match action {
+                Some(EditorAction::AcceptLine) if needs_continuation(&input) => {
+                    continue_on_next_line(&mut input)?;
+                    None
+                }
+                Some(EditorAction::AcceptLine) => {
+                    move_to_last_row(&input);
+                    println!();
+                    drop(raw_mode);
+                    return Ok(ReadLine::Line(input.as_str().to_string()));
+                }
+                Some(EditorAction::DeleteChar) if input.as_str().is_empty() && sequence == CTRL_D.to_string() => {
+                    println!();
+                    drop(raw_mode);
+                    return Ok(ReadLine::EndOfFile);
+                }
+                Some(EditorAction::ViMovementMode) => {
+                    if !vi.cancel_pending() {
+                        print_and_flush(&BEEP.to_string())?;
+                    }
+                    None
+                }
+                Some(EditorAction::BracketedPasteBegin) => {
+                    handle_bracketed_paste(&mut stdin, &mut input)?;
+                    None
+                }
+                // Single keys are vi commands, longer sequences such as arrow keys act like their vi equivalents
+                _ if sequence.chars().count() == 1 => sequence.chars().next(),
+                Some(action) => vi_key_for_action(action),
+                None => None,
+            };
+            if let Some(key) = key {
+                let outcome = vi.handle_key(key, &mut input);
+                handle_vi_outcome(outcome, vi, &mut input, &mut history_index, history, &mut vi_search, &mut stdin)?;
+            }
+            continue;
+        }
 
-        match ch {
-            NEWLINE | CARRIAGE_RETURN => {
+        if let Some(active_menu) = menu.as_mut() {
+            let movement = match action {
+                Some(EditorAction::Complete | EditorAction::MenuComplete | EditorAction::NextHistory | EditorAction::HistorySearchForward) => Some(MenuMove::Next),
+                Some(EditorAction::MenuCompleteBackward | EditorAction::PreviousHistory | EditorAction::HistorySearchBackward) => Some(MenuMove::Previous),
+                Some(EditorAction::ForwardChar) => Some(MenuMove::Right),
+                Some(EditorAction::BackwardChar) => Some(MenuMove::Left),
+                _ => None,
+            };
+            if let Some(movement) = movement {
+                active_menu.select(movement, menu_grid(active_menu).rows, &mut input);
+                draw_menu(&input, active_menu)?;
+                continue;
+            }
+            // Ctrl-G or a lone Esc puts back the typed word, Enter keeps the selected match without running the line,
+            // and any other key keeps it and is handled as usual
+            let is_cancel = action == Some(EditorAction::Abort) || (action.is_none() && sequence == ESC.to_string());
+            let is_accept = action == Some(EditorAction::AcceptLine) && active_menu.selected().is_some();
+            if is_cancel {
+                active_menu.cancel(&mut input);
+            }
+            menu = None;
+            last_tab_input = None;
+            refresh_line(&input)?;
+            if is_cancel || is_accept {
+                continue;
+            }
+        }
+
+        let action = match action {
+            Some(action) => action,
+            None if is_self_inserting(&sequence) => EditorAction::SelfInsert,
+            None => {
+                print_and_flush(&BEEP.to_string())?;
+                continue;
+            }
+        };
+        match action {
+            EditorAction::AcceptLine if needs_continuation(&input) => {
+                last_tab_input = None;
+                history_index = None;
+                clear_suggestion(&mut displayed_suggestion)?;
+                continue_on_next_line(&mut input)?;
+            }
+            EditorAction::AcceptLine => {
+                clear_suggestion(&mut displayed_suggestion)?;
+                move_to_last_row(&input);
                 println!();
                 drop(raw_mode);
-                return Ok(input);
+                return Ok(ReadLine::Line(input.as_str().to_string()));
             }
-            CTRL_C => {
-                println!("^C");
+            EditorAction::DeleteChar if input.as_str().is_empty() && sequence == CTRL_D.to_string() => {
+                println!();
                 drop(raw_mode);
-                std::process::exit(0);
+                return Ok(ReadLine::EndOfFile);
+            }
+            EditorAction::DeleteChar => {
+                last_tab_input = None;
+                history_index = None;
+                if input.delete_at_cursor() {
+                    refresh_line(&input)?;
+                }
             }
-            BACKSPACE | DELETE => {
+            EditorAction::BackwardDeleteChar => {
                 last_tab_input = None;
                 history_index = None;
+                if let Some(vi) = vi.as_mut() {
+                    vi.record_insert_key(BACKSPACE);
+                }
                 handle_backspace(&mut input)?;
             }
-            TAB => {
+            EditorAction::Complete => {
+                history_index = None;
+                clear_suggestion(&mut displayed_suggestion)?;
+                menu = handle_tab_completion(&mut input, autocomplete, &mut last_tab_input)?;
+            }
+            EditorAction::MenuComplete | EditorAction::MenuCompleteBackward => {
                 history_index = None;
-                handle_tab_completion(&mut input, autocomplete, &mut last_tab_input)?;
-            }
-            ESC => {
-                if let Some(arrow) = read_arrow_sequence(&mut stdin)? {
-                    match arrow {
-                        ArrowKey::Up => {
-                            handle_history_up(&mut input, &mut history_index, history)?;
-                        }
-                        ArrowKey::Down => {
-                            handle_history_down(&mut input, &mut history_index, history)?;
-                        }
+                clear_suggestion(&mut displayed_suggestion)?;
+                let direction = if action == EditorAction::MenuComplete { MenuMove::Next } else { MenuMove::Previous };
+                menu = start_menu_completion(&mut input, autocomplete, direction)?;
+            }
+            EditorAction::EndOfLine | EditorAction::ForwardChar | EditorAction::ForwardWord => {
+                if !accept_suggestion(&mut input, action, displayed_suggestion.as_deref())? {
+                    handle_cursor_movement(&mut input, action)?;
+                }
+            }
+            EditorAction::BeginningOfLine | EditorAction::BackwardChar | EditorAction::BackwardWord => {
+                handle_cursor_movement(&mut input, action)?;
+            }
+            EditorAction::KillLine | EditorAction::UnixLineDiscard | EditorAction::UnixWordRubout
+                | EditorAction::KillWord | EditorAction::BackwardKillWord => {
+                last_tab_input = None;
+                history_index = None;
+                handle_kill(&mut input, &mut kill_ring, action)?;
+            }
+            EditorAction::Yank => {
+                last_tab_input = None;
+                history_index = None;
+                handle_yank(&mut input, &kill_ring)?;
+            }
+            EditorAction::ReverseSearchHistory | EditorAction::ForwardSearchHistory => {
+                last_tab_input = None;
+                history_index = None;
+                let direction = if action == EditorAction::ReverseSearchHistory { SearchDirection::Backward } else { SearchDirection::Forward };
+                match handle_incremental_search(&mut stdin, &mut input, history, direction, &keymap)? {
+                    SearchOutcome::Execute => {
+                        refresh_line(&input)?;
+                        move_to_last_row(&input);
+                        println!();
+                        drop(raw_mode);
+                        return Ok(ReadLine::Line(input.as_str().to_string()));
+                    }
+                    SearchOutcome::Interrupt => {
+                        move_to_last_row(&input);
+                        println!("^C");
+                        drop(raw_mode);
+                        return Ok(ReadLine::Interrupted);
                     }
+                    SearchOutcome::Accept(key) => {
+                        refresh_line(&input)?;
+                        pending_key = key;
+                    }
+                    SearchOutcome::Abort => refresh_line(&input)?,
+                }
+            }
+            EditorAction::PreviousHistory | EditorAction::NextHistory
+                | EditorAction::HistorySearchBackward | EditorAction::HistorySearchForward => {
+                handle_history_action(&mut input, action, &mut history_index, history)?;
+            }
+            EditorAction::Undo | EditorAction::Redo => {
+                last_tab_input = None;
+                history_index = None;
+                let changed = if action == EditorAction::Undo { input.undo() } else { input.redo() };
+                if changed {
+                    refresh_line(&input)?;
+                } else {
+                    print_and_flush(&BEEP.to_string())?;
+                }
+            }
+            EditorAction::ClearScreen => {
+                print!("{}", CLEAR_SCREEN_SEQUENCE);
+                set_cursor_row(0);
+                refresh_line(&input)?;
+            }
+            EditorAction::ViMovementMode if vi.is_some() => {
+                last_tab_input = None;
+                clear_suggestion(&mut displayed_suggestion)?;
+                if let Some(vi) = vi.as_mut() {
+                    vi.enter_normal_mode(&mut input);
+                    set_current_prompt(mode_prompt(vi.mode()));
                 }
+                refresh_line(&input)?;
             }
-            _ => {
+            EditorAction::Abort | EditorAction::ViMovementMode => print_and_flush(&BEEP.to_string())?,
+            EditorAction::BracketedPasteBegin => {
                 last_tab_input = None;
                 history_index = None;
-                handle_regular_char(&mut input, ch)?;
+                let pasted = handle_bracketed_paste(&mut stdin, &mut input)?;
+                if let Some(vi) = vi.as_mut() {
+                    pasted.chars().for_each(|ch| vi.record_insert_key(ch));
+                }
             }
+            EditorAction::SelfInsert => {
+                last_tab_input = None;
+                history_index = None;
+                if let Some(ch) = sequence.chars().last() {
+                    if let Some(vi) = vi.as_mut() {
+                        vi.record_insert_key(ch);
+                    }
+                    handle_regular_char(&mut input, ch)?;
+                }
+            }
+        }
+        update_suggestion(&input, history, &mut displayed_suggestion)?;
+    }
+}
+
+/// Unbound printable characters insert themselves, readline binds them to `self-insert` one by one.
+fn is_self_inserting(sequence: &str) -> bool {
+    let mut chars = sequence.chars();
+    matches!((chars.next(), chars.next()), (Some(ch), None) if !ch.is_control())
+}
+
+/// Reads keys until they form a bound sequence or cannot start one any more.
+fn read_key_sequence<R: Read>(reader: &mut R, keymap: &Keymap) -> Result<(String, Option<KeyBinding>), anyhow::Error> {
+    loop {
+        if let Some(ch) = read_char(reader)? {
+            return complete_key_sequence(reader, keymap, ch);
+        }
+    }
+}
+
+fn complete_key_sequence<R: Read>(reader: &mut R, keymap: &Keymap, first: char) -> Result<(String, Option<KeyBinding>), anyhow::Error> {
+    let mut sequence = first.to_string();
+    loop {
+        let binding = keymap.get(&sequence).cloned();
+        let needs_more = if binding.is_some() {
+            // A bound prefix such as Esc in vi mode only continues when the rest of a sequence follows at once
+            keymap.is_prefix(&sequence) && is_input_pending(ESCAPE_SEQUENCE_TIMEOUT_MS)
+        } else {
+            keymap.is_prefix(&sequence) || is_incomplete_control_sequence(&sequence)
+        };
+        if !needs_more {
+            return Ok((sequence, binding));
+        }
+        match read_char(reader)? {
+            Some(ch) => sequence.push(ch),
+            None => return Ok((sequence, None)),
+        }
+    }
+}
+
+/// Unbound terminal sequences like "\x1b[2~" are consumed up to their final byte instead of inserting the rest.
+fn is_incomplete_control_sequence(sequence: &str) -> bool {
+    match sequence.strip_prefix("\x1b[") {
+        Some(parameters) => !parameters.ends_with(|ch: char| ('@'..='~').contains(&ch)),
+        None => false,
+    }
+}
+
+/// Whether Enter should start a continuation line because the command is not complete yet, e.g. after a trailing pipe.
+fn needs_continuation(input: &LineBuffer) -> bool {
+    matches!(ParsedCommand::parse_command(input.as_str()), Err(error) if ParsedCommand::is_incomplete_input(&error))
+}
+
+fn continue_on_next_line(input: &mut LineBuffer) -> Result<(), anyhow::Error> {
+    input.move_end();
+    input.insert_char(NEWLINE);
+    refresh_line(input)
+}
+
+/// Inserts pasted text literally, so that its tabs do not complete and its newlines do not run a partial command.
+fn handle_bracketed_paste<R: Read>(reader: &mut R, input: &mut LineBuffer) -> Result<String, anyhow::Error> {
+    let pasted = read_bracketed_paste(reader)?;
+    input.insert_str(&pasted);
+    refresh_line(input)?;
+    Ok(pasted)
+}
+
+/// Reads up to the sequence the terminal sends after pasted text, turning line endings into newlines.
+fn read_bracketed_paste<R: Read>(reader: &mut R) -> Result<String, anyhow::Error> {
+    let mut pasted = String::new();
+    while !pasted.ends_with(BRACKETED_PASTE_END_SEQUENCE) {
+        if let Some(ch) = read_char(reader)? {
+            pasted.push(ch);
         }
     }
+    pasted.truncate(pasted.len() - BRACKETED_PASTE_END_SEQUENCE.len());
+    Ok(pasted.replace("\r\n", "\n").replace('\r', "\n"))
 }
 
-fn handle_backspace(input: &mut String) -> Result<(), anyhow::Error> {
-    if !input.is_empty() {
-        input.pop();
-        print_and_flush(BACKSPACE_ERASE_SEQUENCE)?;
+/// Applies the readline startup file, reporting the lines that cannot be applied without giving up on the rest.
+pub fn load_inputrc() {
+    let path = match inputrc::startup_file() {
+        Some(path) if path.exists() => path,
+        _ => return,
+    };
+    match InputrcReader::for_current_mode().load_file(&path) {
+        Ok(errors) => errors.iter().for_each(|error| eprintln!("{}", error)),
+        Err(error) => eprintln!("{}", error),
     }
+}
+
+/// The prompt for a new line, prefixed with the editing mode when `show-mode-in-prompt` is on.
+pub fn prompt() -> String {
+    let settings = settings::current();
+    match settings.editing_mode {
+        EditingMode::Emacs if settings.show_mode_in_prompt => format!("{}{}", settings.emacs_mode_string, PROMPT),
+        EditingMode::Vi => mode_prompt(ViMode::Insert),
+        EditingMode::Emacs => PROMPT.to_string(),
+    }
+}
+
+fn mode_prompt(vi_mode: ViMode) -> String {
+    let settings = settings::current();
+    if !settings.show_mode_in_prompt {
+        return PROMPT.to_string();
+    }
+    match vi_mode {
+        ViMode::Insert => format!("{}{}", settings.vi_ins_mode_string, PROMPT),
+        ViMode::Normal => format!("{}{}", settings.vi_cmd_mode_string, PROMPT),
+    }
+}
+
+fn set_current_prompt(prompt: String) {
+    if let Ok(mut current) = CURRENT_PROMPT.write() {
+        *current = prompt;
+    }
+}
+
+fn current_prompt() -> String {
+    CURRENT_PROMPT.read().map(|prompt| prompt.clone()).unwrap_or_else(|_| PROMPT.to_string())
+}
+
+/// The `PS2` prompt drawn before each continuation line of a multi-line buffer.
+fn continuation_prompt() -> String {
+    env::var("PS2").unwrap_or_else(|_| CONTINUATION_PROMPT.to_string())
+}
+
+fn set_cursor_row(row: usize) {
+    if let Ok(mut current) = CURSOR_ROW.write() {
+        *current = row;
+    }
+}
+
+fn cursor_row() -> usize {
+    CURSOR_ROW.read().map(|row| *row).unwrap_or(0)
+}
+
+/// Arrow keys and friends keep working in vi normal mode as their vi equivalents.
+fn vi_key_for_action(action: EditorAction) -> Option<char> {
+    match action {
+        EditorAction::PreviousHistory | EditorAction::HistorySearchBackward => Some('k'),
+        EditorAction::NextHistory | EditorAction::HistorySearchForward => Some('j'),
+        EditorAction::BackwardChar => Some('h'),
+        EditorAction::ForwardChar => Some('l'),
+        EditorAction::BeginningOfLine => Some('0'),
+        EditorAction::EndOfLine => Some('$'),
+        EditorAction::DeleteChar => Some('x'),
+        EditorAction::BackwardWord => Some('b'),
+        EditorAction::ForwardWord => Some('w'),
+        _ => None,
+    }
+}
+
+fn handle_vi_outcome<R: Read>(
+    outcome: ViOutcome,
+    vi: &mut ViState,
+    input: &mut LineBuffer,
+    history_index: &mut Option<usize>,
+    history: &History,
+    vi_search: &mut Option<(String, SearchDirection)>,
+    reader: &mut R,
+) -> Result<(), anyhow::Error> {
+    match outcome {
+        ViOutcome::Pending => return Ok(()),
+        ViOutcome::Beep => return print_and_flush(&BEEP.to_string()),
+        ViOutcome::Changed => (),
+        ViOutcome::HistoryPrevious(count) | ViOutcome::HistoryNext(count) => {
+            let previous = matches!(outcome, ViOutcome::HistoryPrevious(_));
+            for _ in 0..count.min(history.len()) {
+                let shown = *history_index;
+                if previous {
+                    handle_history_up(input, history_index, history)?;
+                } else {
+                    handle_history_down(input, history_index, history)?;
+                }
+                if *history_index == shown {
+                    break;
+                }
+            }
+            input.move_home();
+        }
+        ViOutcome::SearchHistory(direction) => {
+            let pattern = match read_vi_search_pattern(reader, direction)? {
+                Some(pattern) if pattern.is_empty() => vi_search.as_ref().map(|(pattern, _)| pattern.clone()),
+                pattern => pattern,
+            };
+            if let Some(pattern) = pattern {
+                if !handle_vi_history_search(input, history_index, history, &pattern, direction)? {
+                    print_and_flush(&BEEP.to_string())?;
+                }
+                *vi_search = Some((pattern, direction));
+            }
+        }
+        ViOutcome::RepeatSearch { reverse } => match vi_search {
+            Some((pattern, direction)) => {
+                let direction = match (*direction, reverse) {
+                    (direction, false) => direction,
+                    (SearchDirection::Backward, true) => SearchDirection::Forward,
+                    (SearchDirection::Forward, true) => SearchDirection::Backward,
+                };
+                if !handle_vi_history_search(input, history_index, history, pattern, direction)? {
+                    print_and_flush(&BEEP.to_string())?;
+                }
+            }
+            None => print_and_flush(&BEEP.to_string())?,
+        },
+    }
+    set_current_prompt(mode_prompt(vi.mode()));
+    refresh_line(input)
+}
+
+/// Reads the pattern of a vi `/` or `?` history search on the prompt line; Esc or Ctrl-C cancel it.
+fn read_vi_search_pattern<R: Read>(reader: &mut R, direction: SearchDirection) -> Result<Option<String>, anyhow::Error> {
+    let leader = match direction {
+        SearchDirection::Backward => '/',
+        SearchDirection::Forward => '?',
+    };
+    let mut pattern = String::new();
+    loop {
+        draw(&leader.to_string(), &pattern, &pattern, pattern.len())?;
+        let ch = match read_char(reader)? {
+            Some(ch) => ch,
+            None => continue,
+        };
+        match ch {
+            NEWLINE | CARRIAGE_RETURN => return Ok(Some(pattern)),
+            ESC | CTRL_C | CTRL_G => return Ok(None),
+            BACKSPACE | DELETE if pattern.is_empty() => return Ok(None),
+            BACKSPACE | DELETE => { pattern.pop(); }
+            ch if ch.is_control() => (),
+            _ => pattern.push(ch),
+        }
+    }
+}
+
+/// Recalls the next older (`/`) or newer (`?`) history entry containing the pattern, counting from the entry shown.
+fn handle_vi_history_search(
+    input: &mut LineBuffer,
+    history_index: &mut Option<usize>,
+    history: &History,
+    pattern: &str,
+    direction: SearchDirection,
+) -> Result<bool, anyhow::Error> {
+    // `history_index` counts from the newest entry while the history searches use positions from the oldest
+    let shown = history_index.map(|idx| history.len() - 1 - idx);
+    let found = match (direction, shown) {
+        (SearchDirection::Backward, Some(0)) => None,
+        (SearchDirection::Backward, Some(position)) => history.search_backward(pattern, position - 1),
+        (SearchDirection::Backward, None) => history.len().checked_sub(1).and_then(|newest| history.search_backward(pattern, newest)),
+        (SearchDirection::Forward, Some(position)) => history.search_forward(pattern, position + 1),
+        (SearchDirection::Forward, None) => None,
+    };
+    match found.and_then(|position| history.get(position).map(|command| (position, command))) {
+        Some((position, command)) => {
+            input.replace(command);
+            input.move_home();
+            *history_index = Some(history.len() - 1 - position);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Finds the rest of the newest history entry extending the line, shown only while the cursor is at the end.
+fn find_suggestion(input: &LineBuffer, history: &History) -> Option<String> {
+    if !input.is_cursor_at_end() {
+        return None;
+    }
+    let current_directory = env::current_dir().ok();
+    history.suggest(input.as_str(), current_directory.as_deref())
+        .map(|command| command[input.as_str().len()..].to_string())
+        // The suggestion is drawn in place on the cursor row
+        .filter(|suggestion| !suggestion.contains(NEWLINE))
+}
+
+/// Draws the autosuggestion as dimmed text after the cursor, erasing a previously shown one.
+fn update_suggestion(input: &LineBuffer, history: &History, displayed_suggestion: &mut Option<String>) -> Result<(), anyhow::Error> {
+    // A suggestion is only shown when it fits on the cursor row, so that the cursor can move back over it
+    let suggestion = find_suggestion(input, history)
+        .filter(|suggestion| cursor_screen_position(input).column + display_width(suggestion) < terminal_columns());
+    if suggestion.is_none() && displayed_suggestion.is_none() {
+        return Ok(());
+    }
+    if let Some(suggestion) = &suggestion {
+        print!("{}{}{}{}", DIM_SEQUENCE, suggestion, RESET_ATTRIBUTES_SEQUENCE, CLEAR_TO_END_OF_LINE_SEQUENCE);
+        print!("\x1b[{}D", display_width(suggestion));
+    } else if input.is_cursor_at_end() {
+        print!("{}", CLEAR_TO_END_OF_LINE_SEQUENCE);
+    }
+    io::stdout().flush()?;
+    *displayed_suggestion = suggestion;
     Ok(())
 }
 
-fn handle_regular_char(input: &mut String, ch: char) -> Result<(), anyhow::Error> {
-    print_and_flush(&ch.to_string())?;
-    input.push(ch);
+fn clear_suggestion(displayed_suggestion: &mut Option<String>) -> Result<(), anyhow::Error> {
+    if displayed_suggestion.take().is_some() {
+        print_and_flush(CLEAR_TO_END_OF_LINE_SEQUENCE)?;
+    }
     Ok(())
 }
 
-fn handle_tab_completion(input: &mut String, autocomplete: &AutoCompletion, last_tab_input: &mut Option<String>) -> Result<(), anyhow::Error> {
-    let words: Vec<&str> = input.split_whitespace().collect();
-    if let Some(last_word) = words.last() {
-        let last_word = last_word.to_string();
-        let matches = autocomplete.complete(&last_word);
-        process_completion_matches(input, &last_word, matches, autocomplete, last_tab_input)?;
+/// Accepts the whole autosuggestion with Right/End/Ctrl-F, or its next word with Alt-F.
+fn accept_suggestion(input: &mut LineBuffer, action: EditorAction, suggestion: Option<&str>) -> Result<bool, anyhow::Error> {
+    let suggestion = match suggestion {
+        Some(suggestion) if input.is_cursor_at_end() => suggestion,
+        _ => return Ok(false),
+    };
+    let accepted_length = match action {
+        EditorAction::ForwardChar | EditorAction::EndOfLine => suggestion.len(),
+        EditorAction::ForwardWord => {
+            let mut suggestion_buffer = LineBuffer::new();
+            suggestion_buffer.replace(suggestion);
+            suggestion_buffer.move_home();
+            suggestion_buffer.move_word_right();
+            suggestion_buffer.text_before_cursor().len()
+        }
+        _ => return Ok(false),
+    };
+    insert_and_echo(input, &suggestion[..accepted_length])?;
+    Ok(true)
+}
+
+fn handle_backspace(input: &mut LineBuffer) -> Result<(), anyhow::Error> {
+    if input.delete_before_cursor() {
+        refresh_line(input)?;
     }
     Ok(())
 }
 
+fn handle_regular_char(input: &mut LineBuffer, ch: char) -> Result<(), anyhow::Error> {
+    input.insert_char(ch);
+    refresh_line(input)
+}
+
+/// Up/Down walk through entries starting with the text before the cursor when `history-prefix-search` is on.
+fn handle_history_action(input: &mut LineBuffer, action: EditorAction, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
+    // Up and Down move between the lines of a multi-line buffer before walking through the history
+    let moved_between_lines = match action {
+        EditorAction::PreviousHistory => input.move_line_up(),
+        EditorAction::NextHistory => input.move_line_down(),
+        _ => false,
+    };
+    if moved_between_lines {
+        return refresh_line(input);
+    }
+    let history_prefix_search = settings::current().history_prefix_search;
+    match action {
+        EditorAction::PreviousHistory if !history_prefix_search => handle_history_up(input, history_index, history),
+        EditorAction::NextHistory if !history_prefix_search => handle_history_down(input, history_index, history),
+        EditorAction::PreviousHistory | EditorAction::HistorySearchBackward => handle_history_search_backward(input, history_index, history),
+        EditorAction::NextHistory | EditorAction::HistorySearchForward => handle_history_search_forward(input, history_index, history),
+        _ => Ok(()),
+    }
+}
+
+fn handle_cursor_movement(input: &mut LineBuffer, action: EditorAction) -> Result<(), anyhow::Error> {
+    match action {
+        EditorAction::BackwardChar => { input.move_left(); }
+        EditorAction::ForwardChar => { input.move_right(); }
+        EditorAction::BeginningOfLine => input.move_home(),
+        EditorAction::EndOfLine => input.move_end(),
+        EditorAction::BackwardWord => input.move_word_left(),
+        EditorAction::ForwardWord => input.move_word_right(),
+        _ => return Ok(()),
+    }
+    refresh_line(input)
+}
+
+fn handle_kill(input: &mut LineBuffer, kill_ring: &mut KillRing, action: EditorAction) -> Result<(), anyhow::Error> {
+    let killed = match action {
+        EditorAction::KillLine => input.kill_to_end(),
+        EditorAction::UnixLineDiscard => input.kill_to_start(),
+        EditorAction::KillWord => input.kill_word_after_cursor(),
+        EditorAction::BackwardKillWord => input.kill_alphanumeric_word_before_cursor(),
+        _ => input.kill_word_before_cursor(),
+    };
+    if !killed.is_empty() {
+        kill_ring.push(killed);
+        refresh_line(input)?;
+    }
+    Ok(())
+}
+
+fn handle_yank(input: &mut LineBuffer, kill_ring: &KillRing) -> Result<(), anyhow::Error> {
+    if let Some(yanked) = kill_ring.yank() {
+        input.insert_str(yanked);
+        refresh_line(input)?;
+    }
+    Ok(())
+}
+
+fn handle_tab_completion(input: &mut LineBuffer, autocomplete: &AutoCompletion, last_tab_input: &mut Option<String>) -> Result<Option<CompletionMenu>, anyhow::Error> {
+    let word = autocompletion::word_before_cursor(input.text_before_cursor());
+    let completions = autocomplete.complete_word(&word, input.as_str(), input.cursor());
+    process_completion_matches(input, &word, completions, autocomplete, last_tab_input)
+}
+
+/// Completes the word and returns the menu of its matches when they are listed as one.
 fn process_completion_matches(
-    input: &mut String,
-    last_word: &str,
-    matches: Vec<String>,
+    input: &mut LineBuffer,
+    word: &CompletionWord,
+    completions: Completions,
     autocomplete: &AutoCompletion,
     last_tab_input: &mut Option<String>,
-) -> Result<(), anyhow::Error> {
-    match matches.len() {
+) -> Result<Option<CompletionMenu>, anyhow::Error> {
+    match completions.matches.len() {
         0 => {
             print_and_flush(format!("{}", BEEP).as_str())?;
         },
-        1 => handle_single_completion(input, last_word, &matches[0])?,
-        _ => handle_multiple_completions(input, last_word, matches, autocomplete, last_tab_input)?,
+        1 => handle_single_completion(input, word, &completions.matches[0], completions.style)?,
+        _ => return handle_multiple_completions(input, word, completions, autocomplete, last_tab_input),
     }
-    Ok(())
+    Ok(None)
 }
 
-fn handle_single_completion(input: &mut String, last_word: &str, completion: &str) -> Result<(), anyhow::Error> {
-    if completion.len() > last_word.len() {
-        let to_add = &completion[last_word.len()..];
-        let to_add_padded = format!("{} ", to_add);
-        print_and_flush(&to_add_padded)?;
-        input.push_str(&to_add_padded);
+/// Replaces the word with its only completion, followed by a space unless it is a directory.
+fn handle_single_completion(input: &mut LineBuffer, word: &CompletionWord, completion: &str, style: CompletionStyle) -> Result<(), anyhow::Error> {
+    // Also when only the case differs
+    if completion != word.value {
+        replace_word(input, word, &autocompletion::replacement_text(completion, word, style, true))?;
     }
     Ok(())
 }
 
 fn handle_multiple_completions(
-    input: &mut String,
-    last_word: &str,
-    matches: Vec<String>,
+    input: &mut LineBuffer,
+    word: &CompletionWord,
+    completions: Completions,
     autocomplete: &AutoCompletion,
     last_tab_input: &mut Option<String>,
-) -> Result<(), anyhow::Error> {
-    let is_consecutive_tab = last_tab_input.as_ref() == Some(input);
+) -> Result<Option<CompletionMenu>, anyhow::Error> {
+    let is_consecutive_tab = last_tab_input.as_deref() == Some(input.as_str());
     
-    if let Some(common_prefix) = autocomplete.find_common_prefix(last_word) {
-        if common_prefix.len() > last_word.len() {
-            let to_add = &common_prefix[last_word.len()..];
-            print_and_flush(to_add)?;
-            input.push_str(to_add);
-            *last_tab_input = Some(input.clone());
-        } else if is_consecutive_tab {
-            display_matches_and_reprompt(input, &matches)?;
-            *last_tab_input = None;
-        } else {
-            print_and_flush(format!("{}", BEEP).as_str())?;
-            *last_tab_input = Some(input.clone());
-        }
+    let common_prefix = autocomplete.common_prefix(&completions.matches, &word.value)
+        .filter(|prefix| *prefix != word.value && prefix.len() >= word.value.len() && Matcher::current().matches(&word.value, prefix));
+    if let Some(common_prefix) = common_prefix {
+        replace_word(input, word, &autocompletion::replacement_text(&common_prefix, word, completions.style, false))?;
+        *last_tab_input = Some(input.as_str().to_string());
     } else if is_consecutive_tab {
-        display_matches_and_reprompt(input, &matches)?;
         *last_tab_input = None;
+        return display_matches(input, word, completions);
     } else {
         print_and_flush(format!("{}", BEEP).as_str())?;
-        *last_tab_input = Some(input.clone());
+        *last_tab_input = Some(input.as_str().to_string());
     }
-    Ok(())
+    Ok(None)
 }
 
-fn display_matches_and_reprompt(input: &str, matches: &[String]) -> Result<(), anyhow::Error> {
+/// `menu-complete`: inserts the first (or last) match right away, the next keys cycling through the others.
+fn start_menu_completion(input: &mut LineBuffer, autocomplete: &AutoCompletion, direction: MenuMove) -> Result<Option<CompletionMenu>, anyhow::Error> {
+    let word = autocompletion::word_before_cursor(input.text_before_cursor());
+    let completions = autocomplete.complete_word(&word, input.as_str(), input.cursor());
+    match completions.matches.len() {
+        0 => print_and_flush(&BEEP.to_string()).map(|_| None),
+        1 => handle_single_completion(input, &word, &completions.matches[0], completions.style).map(|_| None),
+        _ => {
+            let mut menu = CompletionMenu::new(input, word, completions);
+            menu.select(direction, menu_grid(&menu).rows, input);
+            draw_menu(input, &menu)?;
+            Ok(Some(menu))
+        }
+    }
+}
+
+/// Lists the matches below the line. A list that fits on the screen becomes a menu to cycle through with Tab;
+/// a longer one is printed after asking when there are many, a screenful at a time.
+fn display_matches(input: &LineBuffer, word: &CompletionWord, completions: Completions) -> Result<Option<CompletionMenu>, anyhow::Error> {
+    let settings = settings::current();
+    let menu = CompletionMenu::new(input, word.clone(), completions);
+    let grid = menu_grid(&menu);
+    let count = menu.names().len();
+    if count < settings.completion_query_items && menu_fits(input, &grid) {
+        draw_menu(input, &menu)?;
+        return Ok(Some(menu));
+    }
+
+    move_to_last_row(input);
     println!();
-    for match_str in matches {
-        print!("{}  ", match_str);
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+    if count >= settings.completion_query_items && !completion_menu::confirm_display(&mut stdin, &mut stdout, count)? {
+        refresh_line(input)?;
+        return Ok(None);
+    }
+    let page_height = if settings.page_completions { terminal_rows().saturating_sub(1) } else { usize::MAX };
+    completion_menu::print_paged(&mut stdin, &mut stdout, &grid.lines(menu.names(), None), page_height)?;
+    refresh_line(input)?;
+    Ok(None)
+}
+
+fn menu_grid(menu: &CompletionMenu) -> Grid {
+    Grid::new(menu.names(), terminal_columns())
+}
+
+/// Whether the grid fits below the line without scrolling the line off the screen.
+fn menu_fits(input: &LineBuffer, grid: &Grid) -> bool {
+    let line_rows = screen_position(input.as_str()).row + 1;
+    grid.fits_width(terminal_columns()) && line_rows + grid.rows < terminal_rows()
+}
+
+/// Redraws the line with the grid of the menu below it and moves back to the cursor. Matches are only cycled
+/// through in the line when the grid does not fit.
+fn draw_menu(input: &LineBuffer, menu: &CompletionMenu) -> Result<(), anyhow::Error> {
+    refresh_line(input)?;
+    let grid = menu_grid(menu);
+    if !menu_fits(input, &grid) {
+        return Ok(());
+    }
+    let cursor = cursor_screen_position(input);
+    let last_row = screen_position(input.as_str()).row;
+    if last_row > cursor.row {
+        print!("\x1b[{}B", last_row - cursor.row);
+    }
+    for line in grid.lines(menu.names(), menu.selected()) {
+        print!("\r\n{}", line);
+    }
+    print!("\x1b[{}A\r", grid.rows + last_row - cursor.row);
+    if cursor.column > 0 {
+        print!("\x1b[{}C", cursor.column);
     }
-    print!("\n\r{}{}", PROMPT, input);
     io::stdout().flush()?;
     Ok(())
 }
 
+fn replace_word(input: &mut LineBuffer, word: &CompletionWord, replacement: &str) -> Result<(), anyhow::Error> {
+    input.replace_range(word.start, input.cursor(), replacement);
+    refresh_line(input)
+}
+
+fn insert_and_echo(input: &mut LineBuffer, text: &str) -> Result<(), anyhow::Error> {
+    input.insert_str(text);
+    refresh_line(input)
+}
+
 fn print_and_flush(text: &str) -> Result<(), anyhow::Error> {
     print!("{}", text);
     io::stdout().flush()?;
     Ok(())
 }
 
-enum ArrowKey {
-    Up,
-    Down,
+enum SearchOutcome {
+    Execute,
+    /// The search ended with a key that is handled by the editor afterwards
+    Accept(Option<(String, Option<KeyBinding>)>),
+    Abort,
+    Interrupt,
+}
+
+/// Runs the incremental history search prompt until the match is executed, accepted into the buffer, or aborted.
+fn handle_incremental_search<R: Read>(
+    reader: &mut R,
+    input: &mut LineBuffer,
+    history: &History,
+    direction: SearchDirection,
+    keymap: &Keymap,
+) -> Result<SearchOutcome, anyhow::Error> {
+    let original = input.as_str().to_string();
+    let mut search = IncrementalSearch::new(direction);
+    loop {
+        refresh_search_line(&search, history, &original)?;
+        let ch = match read_char(reader)? {
+            Some(ch) => ch,
+            None => continue,
+        };
+        if ch == CTRL_C {
+            return Ok(SearchOutcome::Interrupt);
+        }
+        if ch == ESC && !is_input_pending(ESCAPE_SEQUENCE_TIMEOUT_MS) {
+            accept_search_match(input, &search, history);
+            return Ok(SearchOutcome::Accept(None));
+        }
+        let (sequence, binding) = complete_key_sequence(reader, keymap, ch)?;
+        match binding {
+            Some(KeyBinding::Action(EditorAction::ReverseSearchHistory)) => search.search_again(SearchDirection::Backward, history),
+            Some(KeyBinding::Action(EditorAction::ForwardSearchHistory)) => search.search_again(SearchDirection::Forward, history),
+            Some(KeyBinding::Action(EditorAction::BackwardDeleteChar)) => search.pop_char(history),
+            Some(KeyBinding::Action(EditorAction::Abort)) => {
+                input.replace(&original);
+                return Ok(SearchOutcome::Abort);
+            }
+            Some(KeyBinding::Action(EditorAction::AcceptLine)) => {
+                accept_search_match(input, &search, history);
+                return Ok(SearchOutcome::Execute);
+            }
+            None if is_self_inserting(&sequence) => search.push_char(ch, history),
+            binding => {
+                accept_search_match(input, &search, history);
+                return Ok(SearchOutcome::Accept(Some((sequence, binding))));
+            }
+        }
+    }
+}
+
+fn accept_search_match(input: &mut LineBuffer, search: &IncrementalSearch, history: &History) {
+    if let Some(matched) = search.current_match(history) {
+        input.replace(matched);
+        if let Some(position) = search.match_position(history) {
+            input.set_cursor(position);
+        }
+    }
+}
+
+fn refresh_search_line(search: &IncrementalSearch, history: &History, original: &str) -> Result<(), anyhow::Error> {
+    let line = search.current_match(history).unwrap_or(original);
+    draw(&search.prompt(), line, line, search.match_position(history).unwrap_or(line.len()))
 }
 
-fn read_arrow_sequence(stdin: &mut io::Stdin) -> Result<Option<ArrowKey>, anyhow::Error> {
-    let mut buffer = [0; 1];
-    stdin.read_exact(&mut buffer)?;
-    if buffer[0] as char != '[' {
+/// Waits briefly for more input so that a lone Esc can be told apart from the start of an escape sequence.
+fn is_input_pending(timeout_ms: i32) -> bool {
+    let mut poll_fd = libc::pollfd { fd: STDIN_FILE_DESCRIPTOR, events: libc::POLLIN, revents: 0 };
+    unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) > 0 }
+}
+
+/// Reads one UTF-8 encoded character, returning `None` for an invalid byte sequence.
+fn read_char<R: Read>(reader: &mut R) -> Result<Option<char>, anyhow::Error> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes[..1])?;
+    let length = utf8_sequence_length(bytes[0]);
+    if length == 0 {
         return Ok(None);
     }
-    stdin.read_exact(&mut buffer)?;
-    match buffer[0] as char {
-        'A' => Ok(Some(ArrowKey::Up)),
-        'B' => Ok(Some(ArrowKey::Down)),
-        _ => Ok(None),
+    reader.read_exact(&mut bytes[1..length])?;
+    Ok(std::str::from_utf8(&bytes[..length]).ok().and_then(|s| s.chars().next()))
+}
+
+fn utf8_sequence_length(first_byte: u8) -> usize {
+    match first_byte {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 0,
+    }
+}
+
+/// Blocks until a key is typed, returning false instead when the terminal is resized in the meantime.
+fn wait_for_key() -> bool {
+    loop {
+        let mut poll_fd = libc::pollfd { fd: STDIN_FILE_DESCRIPTOR, events: libc::POLLIN, revents: 0 };
+        if unsafe { libc::poll(&mut poll_fd, 1, -1) } >= 0 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return true;
+        }
+        if signals::take_window_resize() {
+            return false;
+        }
     }
 }
 
-fn clear_line(input: &str) -> Result<(), anyhow::Error> {
-    // Move cursor to beginning of input (after prompt)
-    for _ in 0..input.len() {
-        print!("\x08");
+/// Redraws the prompt and the whole highlighted buffer, then puts the terminal cursor back to the buffer cursor.
+fn refresh_line(input: &LineBuffer) -> Result<(), anyhow::Error> {
+    draw(&current_prompt(), input.as_str(), &highlight(input.as_str()), input.cursor())
+}
+
+/// Draws a prompt and text, wrapped at the terminal width, over the previous drawing and moves to the cursor position.
+/// The text is shown as `styled_text`, which only adds escape sequences for colors.
+fn draw(prompt: &str, text: &str, styled_text: &str, cursor: usize) -> Result<(), anyhow::Error> {
+    let continuation_prompt = continuation_prompt();
+    let layout = Layout::new(prompt, &continuation_prompt, terminal_columns());
+    move_to_first_row();
+    print!("{}{}", prompt, styled_text.replace(NEWLINE, &format!("{}{}", NEWLINE, continuation_prompt)));
+    let end = layout.position_after(text);
+    if end.column == 0 && end.row > 0 && !text.ends_with(NEWLINE) {
+        // The terminal cursor stays on the last column of a full row until something follows
+        print!("\r\n");
     }
-    // Clear from cursor to end of line
-    print!("\x1b[K");
+    let cursor_position = layout.position_after(&text[..cursor]);
+    if cursor_position != end {
+        if end.row > cursor_position.row {
+            print!("\x1b[{}A", end.row - cursor_position.row);
+        }
+        print!("\r");
+        if cursor_position.column > 0 {
+            print!("\x1b[{}C", cursor_position.column);
+        }
+    }
+    set_cursor_row(cursor_position.row);
     io::stdout().flush()?;
     Ok(())
 }
 
-fn handle_history_up(input: &mut String, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
+/// Where the end of `text` is on the screen when it is drawn after the current prompt.
+fn screen_position(text: &str) -> ScreenPosition {
+    Layout::new(&current_prompt(), &continuation_prompt(), terminal_columns()).position_after(text)
+}
+
+fn cursor_screen_position(input: &LineBuffer) -> ScreenPosition {
+    screen_position(input.text_before_cursor())
+}
+
+/// Moves to the start of the prompt row, e.g. to draw a search prompt in place of a multi-line buffer.
+fn move_to_first_row() {
+    if cursor_row() > 0 {
+        print!("\x1b[{}A", cursor_row());
+    }
+    print!("\r{}", CLEAR_TO_END_OF_SCREEN_SEQUENCE);
+    set_cursor_row(0);
+}
+
+/// Moves to the last row of the buffer, so that output below it does not overwrite any of its rows.
+fn move_to_last_row(input: &LineBuffer) {
+    let last_row = screen_position(input.as_str()).row;
+    if last_row > cursor_row() {
+        print!("\x1b[{}B", last_row - cursor_row());
+    }
+    set_cursor_row(0);
+}
+
+fn handle_history_up(input: &mut LineBuffer, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
     if history.len() == 0 {
         return Ok(());
     }
@@ -220,10 +1082,8 @@ fn handle_history_up(input: &mut String, history_index: &mut Option<usize>, hist
         None => (),
         Some(new_index) => {
             if let Some(cmd) = history.get_last_command_by_idx(new_index) {
-                clear_line(input)?;
-                input.clear();
-                input.push_str(cmd);
-                print_and_flush(cmd)?;
+                input.replace(cmd);
+                refresh_line(input)?;
                 *history_index = Some(new_index);
             }
         }
@@ -232,7 +1092,7 @@ fn handle_history_up(input: &mut String, history_index: &mut Option<usize>, hist
     Ok(())
 }
 
-fn handle_history_down(input: &mut String, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
+fn handle_history_down(input: &mut LineBuffer, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
     let new_index = match history_index {
         None => return Ok(()), // Not navigating history, do nothing
         Some(idx) => {
@@ -247,16 +1107,14 @@ fn handle_history_down(input: &mut String, history_index: &mut Option<usize>, hi
     match new_index {
         None => {
             // Back to empty line
-            clear_line(input)?;
             input.clear();
+            refresh_line(input)?;
             *history_index = None;
         }
         Some(idx) => {
             if let Some(cmd) = history.get_last_command_by_idx(idx) {
-                clear_line(input)?;
-                input.clear();
-                input.push_str(cmd);
-                print_and_flush(cmd)?;
+                input.replace(cmd);
+                refresh_line(input)?;
                 *history_index = Some(idx);
             }
         }
@@ -265,28 +1123,92 @@ fn handle_history_down(input: &mut String, history_index: &mut Option<usize>, hi
     Ok(())
 }
 
+/// Up with `history-prefix-search`: recalls the previous entry starting with the text before the cursor,
+/// skipping entries identical to the line currently shown. Without a prefix it is plain previous-history.
+fn handle_history_search_backward(input: &mut LineBuffer, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
+    let prefix = search_prefix(input, *history_index);
+    let start = history_index.map(|idx| idx + 1).unwrap_or(0);
+    let found = (start..history.len()).find(|&idx| is_prefix_match(history, idx, &prefix, input.as_str()));
+    if let Some(idx) = found {
+        recall_history_entry(input, history, idx, &prefix)?;
+        *history_index = Some(idx);
+    }
+    Ok(())
+}
+
+/// Down with `history-prefix-search`: recalls the next newer matching entry, or goes back to the typed prefix.
+fn handle_history_search_forward(input: &mut LineBuffer, history_index: &mut Option<usize>, history: &History) -> Result<(), anyhow::Error> {
+    let current_index = match history_index {
+        None => return Ok(()),
+        Some(idx) => *idx,
+    };
+    let prefix = search_prefix(input, *history_index);
+    let found = (0..current_index).rev().find(|&idx| is_prefix_match(history, idx, &prefix, input.as_str()));
+    match found {
+        Some(idx) => {
+            recall_history_entry(input, history, idx, &prefix)?;
+            *history_index = Some(idx);
+        }
+        None => {
+            input.replace(&prefix);
+            refresh_line(input)?;
+            *history_index = None;
+        }
+    }
+    Ok(())
+}
+
+fn is_prefix_match(history: &History, idx: usize, prefix: &str, current_line: &str) -> bool {
+    history.get_last_command_by_idx(idx)
+        .map(|cmd| cmd.starts_with(prefix) && cmd != current_line)
+        .unwrap_or(false)
+}
+
+/// The text the search matches entries against. An entry recalled without a prefix has the cursor at its end,
+/// and the search goes on without a prefix.
+fn search_prefix(input: &LineBuffer, history_index: Option<usize>) -> String {
+    if history_index.is_some() && input.cursor() == input.as_str().len() {
+        return String::new();
+    }
+    input.text_before_cursor().to_string()
+}
+
+/// Shows the entry with the cursor after the prefix, or at the end of the line when there is no prefix.
+fn recall_history_entry(input: &mut LineBuffer, history: &History, idx: usize, prefix: &str) -> Result<(), anyhow::Error> {
+    if let Some(cmd) = history.get_last_command_by_idx(idx) {
+        input.replace(cmd);
+        input.set_cursor(if prefix.is_empty() { cmd.len() } else { prefix.len() });
+        refresh_line(input)?;
+    }
+    Ok(())
+}
+
 struct RawMode {
     original: Termios,
 }
 
 impl RawMode {
     fn enable() -> Result<Self, anyhow::Error> {
-        let stdin_file_descriptor = 0;
+        let stdin_file_descriptor = STDIN_FILE_DESCRIPTOR;
         let original = Termios::from_fd(stdin_file_descriptor)?;
         let mut raw = original.clone();
         // Disable canonical mode, echo, signals, and special chars
         raw.c_lflag &= !(ICANON | ECHO | IEXTEN | ISIG);
+        // Disable flow control so that Ctrl-S reaches the forward history search
+        raw.c_iflag &= !IXON;
         // Ensure reads return as soon as 1 byte is available
         raw.c_cc[VMIN] = 1;
         raw.c_cc[VTIME] = 0;
         tcsetattr(stdin_file_descriptor, TCSANOW, &raw)?;
+        print_and_flush(ENABLE_BRACKETED_PASTE_SEQUENCE)?;
         Ok(Self { original })
     }
 }
 
 impl Drop for RawMode {
     fn drop(&mut self) {
-        let stdin_file_descriptor = 0;
+        let stdin_file_descriptor = STDIN_FILE_DESCRIPTOR;
+        let _ = print_and_flush(DISABLE_BRACKETED_PASTE_SEQUENCE);
         let _ = tcsetattr(stdin_file_descriptor, TCSANOW, &self.original);
     }
 }
@@ -295,169 +1217,178 @@ impl Drop for RawMode {
 mod tests {
     use super::*;
 
+    fn line_buffer(text: &str) -> LineBuffer {
+        let mut buffer = LineBuffer::new();
+        buffer.replace(text);
+        buffer
+    }
+
+    fn completion_word(text: &str) -> CompletionWord {
+        autocompletion::word_before_cursor(text)
+    }
+
     fn create_test_autocomplete() -> AutoCompletion {
         AutoCompletion::new(vec!["echo", "exit", "export", "cd", "cat", "cargo"])
     }
 
     #[test]
     fn test_handle_single_completion() {
-        let mut input = String::from("ec");
+        let mut input = line_buffer("ec");
         let completion = "echo";
 
-        let result = handle_single_completion(&mut input, "ec", completion);
+        let result = handle_single_completion(&mut input, &completion_word("ec"), completion, CompletionStyle::default());
         assert!(result.is_ok());
-        assert_eq!(input, "echo ");
+        assert_eq!(input.as_str(), "echo ");
     }
 
     #[test]
     fn test_handle_single_completion_no_extension() {
-        let mut input = String::from("echo");
+        let mut input = line_buffer("echo");
         let completion = "echo";
 
-        let result = handle_single_completion(&mut input, "echo", completion);
+        let result = handle_single_completion(&mut input, &completion_word("echo"), completion, CompletionStyle::default());
         assert!(result.is_ok());
-        assert_eq!(input, "echo"); // Should remain unchanged
+        assert_eq!(input.as_str(), "echo"); // Should remain unchanged
     }
 
     #[test]
     fn test_process_completion_matches_no_matches() {
-        let mut input = String::from("xyz");
+        let mut input = line_buffer("xyz");
         let autocomplete = create_test_autocomplete();
         let matches = Vec::new();
         let mut last_tab_input = None;
 
-        let result = process_completion_matches(&mut input, "xyz", matches, &autocomplete, &mut last_tab_input);
+        let result = process_completion_matches(&mut input, &completion_word("xyz"), matches.into(), &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "xyz");
+        assert_eq!(input.as_str(), "xyz");
     }
 
     #[test]
     fn test_process_completion_matches_single_match() {
-        let mut input = String::from("ec");
+        let mut input = line_buffer("ec");
         let autocomplete = create_test_autocomplete();
         let matches = vec!["echo".to_string()];
         let mut last_tab_input = None;
 
-        let result = process_completion_matches(&mut input, "ec", matches, &autocomplete, &mut last_tab_input);
+        let result = process_completion_matches(&mut input, &completion_word("ec"), matches.into(), &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "echo ");
+        assert_eq!(input.as_str(), "echo ");
     }
 
     #[test]
     fn test_handle_backspace_with_content() {
-        let mut input = String::from("hello");
+        let mut input = line_buffer("hello");
 
         // We can't easily test the terminal output, but we can test the string manipulation
         let result = handle_backspace(&mut input);
         assert!(result.is_ok());
-        assert_eq!(input, "hell");
+        assert_eq!(input.as_str(), "hell");
     }
 
     #[test]
     fn test_handle_backspace_empty_input() {
-        let mut input = String::new();
+        let mut input = LineBuffer::new();
 
         let result = handle_backspace(&mut input);
         assert!(result.is_ok());
-        assert_eq!(input, ""); // Should remain empty
+        assert_eq!(input.as_str(), ""); // Should remain empty
     }
 
     #[test]
     fn test_handle_regular_char() {
-        let mut input = String::from("hell");
+        let mut input = line_buffer("hell");
 
         let result = handle_regular_char(&mut input, 'o');
         assert!(result.is_ok());
-        assert_eq!(input, "hello");
+        assert_eq!(input.as_str(), "hello");
     }
 
     #[test]
     fn test_handle_tab_completion_no_words() {
-        let mut input = String::new();
+        let mut input = LineBuffer::new();
         let autocomplete = create_test_autocomplete();
         let mut last_tab_input = None;
 
         let result = handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, ""); // Should remain empty when no words to complete
+        assert_eq!(input.as_str(), ""); // Should remain empty when no words to complete
     }
 
     #[test]
     fn test_handle_tab_completion_with_partial_word() {
-        let mut input = String::from("ec");
+        let mut input = line_buffer("ec");
         let autocomplete = create_test_autocomplete();
         let mut last_tab_input = None;
 
         let result = handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "echo "); // Should complete to "echo"
+        assert_eq!(input.as_str(), "echo "); // Should complete to "echo"
     }
 
     #[test]
     fn test_handle_tab_completion_with_multiple_words() {
-        let mut input = String::from("echo hello ec");
+        let mut input = line_buffer("echo hello | ec");
         let autocomplete = create_test_autocomplete();
         let mut last_tab_input = None;
 
         let result = handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "echo hello echo "); // Should complete the last word
+        assert_eq!(input.as_str(), "echo hello | echo "); // Should complete the command after the pipe
     }
 
     #[test]
     fn test_constants() {
-        assert_eq!(BACKSPACE_ERASE_SEQUENCE, "\x08 \x08");
         assert_eq!(PROMPT, "$ ");
         assert_eq!(NEWLINE, '\n');
         assert_eq!(CARRIAGE_RETURN, '\r');
-        assert_eq!(TAB, '\t');
         assert_eq!(BACKSPACE, '\u{7f}');
         assert_eq!(DELETE, '\u{0008}');
         assert_eq!(CTRL_C, '\u{0003}');
+        assert_eq!(CLEAR_TO_END_OF_LINE_SEQUENCE, "\x1b[K");
     }
 
     #[test]
     fn test_multiple_completions_with_common_prefix() {
-        let mut input = String::from("e");
+        let mut input = line_buffer("e");
         let autocomplete = create_test_autocomplete();
         let matches = vec!["echo".to_string(), "exit".to_string(), "export".to_string()];
         let mut last_tab_input = None;
 
-        let result = handle_multiple_completions(&mut input, "e", matches, &autocomplete, &mut last_tab_input);
+        let result = handle_multiple_completions(&mut input, &completion_word("e"), matches.into(), &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "e"); // Should remain "e" since that's the only common prefix
+        assert_eq!(input.as_str(), "e"); // Should remain "e" since that's the only common prefix
     }
 
     #[test]
     fn test_multiple_completions_extending_prefix() {
-        let mut input = String::from("ex");
+        let mut input = line_buffer("ex");
         let autocomplete = create_test_autocomplete();
         let matches = vec!["exit".to_string(), "export".to_string()];
         let mut last_tab_input = None;
 
-        let result = handle_multiple_completions(&mut input, "ex", matches, &autocomplete, &mut last_tab_input);
+        let result = handle_multiple_completions(&mut input, &completion_word("ex"), matches.into(), &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
         // Should extend to common prefix "ex" (no further extension possible)
-        assert_eq!(input, "ex");
+        assert_eq!(input.as_str(), "ex");
     }
 
     #[test]
     fn test_pressing_tab_twice_with_multiple_completions() {
-        let mut input = String::from("e");
+        let mut input = line_buffer("e");
         let autocomplete = create_test_autocomplete();
         let matches = vec!["echo".to_string(), "exit".to_string(), "export".to_string()];
         let mut last_tab_input = None;
 
         // First tab press - should set last_tab_input since no common prefix extension
-        let result = handle_multiple_completions(&mut input, "e", matches.clone(), &autocomplete, &mut last_tab_input);
+        let result = handle_multiple_completions(&mut input, &completion_word("e"), matches.clone().into(), &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "e");
+        assert_eq!(input.as_str(), "e");
         assert_eq!(last_tab_input, Some(String::from("e")));
 
         // Second tab press (consecutive) - should trigger display of matches and clear last_tab_input
-        let result = handle_multiple_completions(&mut input, "e", matches, &autocomplete, &mut last_tab_input);
+        let result = handle_multiple_completions(&mut input, &completion_word("e"), matches.into(), &autocomplete, &mut last_tab_input);
         assert!(result.is_ok());
-        assert_eq!(input, "e");
+        assert_eq!(input.as_str(), "e");
         assert_eq!(last_tab_input, None);
     }
 
@@ -465,12 +1396,12 @@ mod tests {
     fn test_handle_history_up_empty_history() {
         use crate::history::History;
         let history = History::new();
-        let mut input = String::new();
+        let mut input = LineBuffer::new();
         let mut history_index = None;
 
         let result = handle_history_up(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "");
+        assert_eq!(input.as_str(), "");
         assert_eq!(history_index, None);
     }
 
@@ -481,12 +1412,12 @@ mod tests {
         history.append("echo hello");
         history.append("echo world");
 
-        let mut input = String::new();
+        let mut input = LineBuffer::new();
         let mut history_index = None;
 
         let result = handle_history_up(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "echo world");
+        assert_eq!(input.as_str(), "echo world");
         assert_eq!(history_index, Some(0));
     }
 
@@ -497,14 +1428,14 @@ mod tests {
         history.append("echo hello");
         history.append("echo world");
 
-        let mut input = String::new();
+        let mut input = LineBuffer::new();
         let mut history_index = None;
 
         let mut result = handle_history_up(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
         result = handle_history_up(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "echo hello");
+        assert_eq!(input.as_str(), "echo hello");
         assert_eq!(history_index, Some(1));
     }
 
@@ -515,13 +1446,13 @@ mod tests {
         history.append("echo world");
         history.append("echo hello");
 
-        let mut input = String::from("echo world");
+        let mut input = line_buffer("echo world");
         let mut history_index = Some(1);
 
         // Should not change when already at oldest
         let result = handle_history_up(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "echo world");
+        assert_eq!(input.as_str(), "echo world");
         assert_eq!(history_index, Some(1));
     }
 
@@ -532,12 +1463,12 @@ mod tests {
         history.append("echo world");
         history.append("echo hello");
 
-        let mut input = String::from("test");
+        let mut input = line_buffer("test");
         let mut history_index = None;
 
         let result = handle_history_down(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "test");
+        assert_eq!(input.as_str(), "test");
         assert_eq!(history_index, None);
     }
 
@@ -547,12 +1478,12 @@ mod tests {
         let mut history = History::new();
         history.append("echo hello");
 
-        let mut input = String::from("echo hello");
+        let mut input = line_buffer("echo hello");
         let mut history_index = Some(0);
 
         let result = handle_history_down(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "");
+        assert_eq!(input.as_str(), "");
         assert_eq!(history_index, None);
     }
 
@@ -563,26 +1494,518 @@ mod tests {
         history.append("echo hello");
         history.append("echo world");
 
-        let mut input = String::from("echo hello");
+        let mut input = line_buffer("echo hello");
         let mut history_index = Some(1);
 
         let result = handle_history_down(&mut input, &mut history_index, &history);
         assert!(result.is_ok());
-        assert_eq!(input, "echo world");
+        assert_eq!(input.as_str(), "echo world");
         assert_eq!(history_index, Some(0));
     }
 
+    fn create_prefix_search_history() -> History {
+        let mut history = History::new();
+        history.append("git status");
+        history.append("cargo build");
+        history.append("git commit");
+        history.append("git commit");
+        history.append("cargo test");
+        history
+    }
+
+    #[test]
+    fn test_history_search_backward_filters_by_prefix() {
+        let history = create_prefix_search_history();
+        let mut input = line_buffer("git");
+        let mut history_index = None;
+
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git commit");
+        assert_eq!(input.cursor(), 3);
+        assert_eq!(history_index, Some(1));
+
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git status");
+        assert_eq!(input.cursor(), 3);
+        assert_eq!(history_index, Some(4));
+
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git status");
+        assert_eq!(history_index, Some(4));
+    }
+
+    #[test]
+    fn test_history_search_forward_returns_to_prefix() {
+        let history = create_prefix_search_history();
+        let mut input = line_buffer("git");
+        let mut history_index = None;
+
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        handle_history_search_forward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git commit");
+        assert_eq!(history_index, Some(2));
+
+        handle_history_search_forward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git");
+        assert_eq!(input.cursor(), 3);
+        assert_eq!(history_index, None);
+    }
+
+    #[test]
+    fn test_history_search_with_empty_prefix_skips_duplicates() {
+        let history = create_prefix_search_history();
+        let mut input = LineBuffer::new();
+        let mut history_index = None;
+
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "cargo test");
+        assert_eq!(input.cursor(), "cargo test".len());
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git commit");
+        assert_eq!(input.cursor(), "git commit".len());
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "cargo build");
+        assert_eq!(input.cursor(), "cargo build".len());
+        assert_eq!(history_index, Some(3));
+
+        handle_history_search_forward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "git commit");
+        assert_eq!(input.cursor(), "git commit".len());
+    }
+
+    #[test]
+    fn test_history_search_backward_no_match() {
+        let history = create_prefix_search_history();
+        let mut input = line_buffer("ls");
+        let mut history_index = None;
+
+        handle_history_search_backward(&mut input, &mut history_index, &history).unwrap();
+        assert_eq!(input.as_str(), "ls");
+        assert_eq!(history_index, None);
+    }
+
+    #[test]
+    fn test_find_suggestion() {
+        let mut history = History::new();
+        history.append("cargo build");
+        history.append("cargo test --release");
+
+        assert_eq!(find_suggestion(&line_buffer("cargo t"), &history), Some("est --release".to_string()));
+        assert_eq!(find_suggestion(&line_buffer("ls"), &history), None);
+        assert_eq!(find_suggestion(&LineBuffer::from_str_with_cursor("cargo t", 2), &history), None);
+    }
+
+    #[test]
+    fn test_no_suggestion_of_multi_line_command() {
+        let mut history = History::new();
+        history.append("echo 'a\nb'");
+        assert_eq!(find_suggestion(&line_buffer("echo"), &history), None);
+    }
+
+    #[test]
+    fn test_needs_continuation() {
+        assert!(needs_continuation(&line_buffer("echo 'a")));
+        assert!(needs_continuation(&line_buffer("ls |")));
+        assert!(!needs_continuation(&line_buffer("ls | wc")));
+        assert!(!needs_continuation(&line_buffer("")));
+    }
+
+    #[test]
+    fn test_accept_whole_suggestion() -> Result<(), anyhow::Error> {
+        let mut input = line_buffer("cargo t");
+
+        assert!(accept_suggestion(&mut input, EditorAction::ForwardChar, Some("est --release"))?);
+        assert_eq!(input.as_str(), "cargo test --release");
+        assert!(input.is_cursor_at_end());
+        Ok(())
+    }
+
     #[test]
-    fn test_clear_line_empty_input() {
-        let input = "";
-        let result = clear_line(input);
+    fn test_accept_suggestion_word_by_word() -> Result<(), anyhow::Error> {
+        let mut input = line_buffer("cargo");
+
+        assert!(accept_suggestion(&mut input, EditorAction::ForwardWord, Some(" test --release"))?);
+        assert_eq!(input.as_str(), "cargo test");
+        Ok(())
+    }
+
+    #[test]
+    fn test_accept_suggestion_only_at_end_of_line() -> Result<(), anyhow::Error> {
+        let mut input = LineBuffer::from_str_with_cursor("cargo t", 2);
+
+        assert!(!accept_suggestion(&mut input, EditorAction::ForwardChar, Some("est"))?);
+        assert!(!accept_suggestion(&mut line_buffer("cargo t"), EditorAction::BackwardChar, Some("est"))?);
+        assert!(!accept_suggestion(&mut line_buffer("cargo t"), EditorAction::ForwardChar, None)?);
+        assert_eq!(input.as_str(), "cargo t");
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_line_empty_input() {
+        let input = LineBuffer::new();
+        let result = refresh_line(&input);
         assert!(result.is_ok());
     }
 
     #[test]
-    fn test_clear_line_with_content() {
-        let input = "echo hello";
-        let result = clear_line(input);
+    fn test_refresh_line_with_content() {
+        let input = LineBuffer::from_str_with_cursor("echo hello", 4);
+        let result = refresh_line(&input);
         assert!(result.is_ok());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_handle_regular_char_in_the_middle() {
+        let mut input = LineBuffer::from_str_with_cursor("eho", 1);
+
+        let result = handle_regular_char(&mut input, 'c');
+        assert!(result.is_ok());
+        assert_eq!(input.as_str(), "echo");
+        assert_eq!(input.cursor(), 2);
+    }
+
+    #[test]
+    fn test_handle_backspace_in_the_middle() {
+        let mut input = LineBuffer::from_str_with_cursor("ecxho", 3);
+
+        let result = handle_backspace(&mut input);
+        assert!(result.is_ok());
+        assert_eq!(input.as_str(), "echo");
+        assert_eq!(input.cursor(), 2);
+    }
+
+    #[test]
+    fn test_handle_kill_and_yank() {
+        let mut input = line_buffer("echo hello");
+        let mut kill_ring = KillRing::new();
+
+        handle_kill(&mut input, &mut kill_ring, EditorAction::UnixWordRubout).unwrap();
+        assert_eq!(input.as_str(), "echo ");
+        handle_kill(&mut input, &mut kill_ring, EditorAction::UnixLineDiscard).unwrap();
+        assert_eq!(input.as_str(), "");
+
+        handle_yank(&mut input, &kill_ring).unwrap();
+        assert_eq!(input.as_str(), "echo ");
+    }
+
+    #[test]
+    fn test_handle_kill_to_end() {
+        let mut input = LineBuffer::from_str_with_cursor("echo hello", 4);
+        let mut kill_ring = KillRing::new();
+
+        handle_kill(&mut input, &mut kill_ring, EditorAction::KillLine).unwrap();
+        assert_eq!(input.as_str(), "echo");
+        assert_eq!(kill_ring.yank(), Some(" hello"));
+    }
+
+    #[test]
+    fn test_handle_cursor_movement() {
+        let mut input = line_buffer("echo hello");
+
+        handle_cursor_movement(&mut input, EditorAction::BackwardWord).unwrap();
+        assert_eq!(input.cursor(), 5);
+        handle_cursor_movement(&mut input, EditorAction::BackwardChar).unwrap();
+        assert_eq!(input.cursor(), 4);
+        handle_cursor_movement(&mut input, EditorAction::BeginningOfLine).unwrap();
+        assert_eq!(input.cursor(), 0);
+        handle_cursor_movement(&mut input, EditorAction::ForwardChar).unwrap();
+        assert_eq!(input.cursor(), 1);
+        handle_cursor_movement(&mut input, EditorAction::EndOfLine).unwrap();
+        assert_eq!(input.cursor(), 10);
+    }
+
+    #[test]
+    fn test_tab_completion_in_the_middle_of_line() {
+        let mut input = LineBuffer::from_str_with_cursor("ec hello", 2);
+        let autocomplete = create_test_autocomplete();
+        let mut last_tab_input = None;
+
+        let result = handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input);
+        assert!(result.is_ok());
+        assert_eq!(input.as_str(), "echo  hello");
+        assert_eq!(input.cursor(), 5);
+    }
+
+    #[test]
+    fn test_tab_completion_uses_the_word_context() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("echo.txt"), "")?;
+        let autocomplete = create_test_autocomplete();
+        let mut last_tab_input = None;
+
+        let mut input = line_buffer(&format!("cat {}/ec", directory.path().display()));
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), format!("cat {}/echo.txt ", directory.path().display()));
+
+        let mut input = LineBuffer::from_str_with_cursor("cd /; ec hello", 8);
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), "cd /; echo  hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tab_completion_from_completion_spec() -> Result<(), anyhow::Error> {
+        use crate::input::completion_spec::{self, CompletionSpec};
+        completion_spec::set("spec-test-tool", CompletionSpec { words: Some("deploy destroy --env=".to_string()), ..CompletionSpec::default() })?;
+        let autocomplete = create_test_autocomplete();
+        let mut last_tab_input = None;
+
+        let mut input = line_buffer("spec-test-tool dep");
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), "spec-test-tool deploy ");
+
+        completion_spec::set("spec-test-tool", CompletionSpec { words: Some("--env=".to_string()), no_space: true, ..CompletionSpec::default() })?;
+        let mut input = line_buffer("spec-test-tool --e");
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), "spec-test-tool --env=");
+        completion_spec::remove("spec-test-tool")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tab_completes_file_names() -> Result<(), anyhow::Error> {
+        let directory = tempfile::tempdir()?;
+        std::fs::write(directory.path().join("my file.txt"), "")?;
+        std::fs::create_dir(directory.path().join("src"))?;
+        let autocomplete = create_test_autocomplete();
+        let mut last_tab_input = None;
+
+        let mut input = line_buffer(&format!("cat {}/my", directory.path().display()));
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), format!("cat {}/my\\ file.txt ", directory.path().display()));
+
+        let mut input = line_buffer(&format!("cat \"{}/my", directory.path().display()));
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), format!("cat \"{}/my file.txt\" ", directory.path().display()));
+
+        let mut input = line_buffer(&format!("cd {}/s", directory.path().display()));
+        handle_tab_completion(&mut input, &autocomplete, &mut last_tab_input)?;
+        assert_eq!(input.as_str(), format!("cd {}/src/", directory.path().display()));
+        Ok(())
+    }
+
+    fn create_search_history() -> History {
+        let mut history = History::new();
+        history.append("echo hello");
+        history.append("cargo build");
+        history.append("cargo test");
+        history
+    }
+
+    #[test]
+    fn test_incremental_search_execute() -> Result<(), anyhow::Error> {
+        let history = create_search_history();
+        let mut input = LineBuffer::new();
+
+        let outcome = handle_incremental_search(&mut "car\r".as_bytes(), &mut input, &history, SearchDirection::Backward, &Keymap::emacs())?;
+        assert!(matches!(outcome, SearchOutcome::Execute));
+        assert_eq!(input.as_str(), "cargo test");
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_search_repeated_ctrl_r() -> Result<(), anyhow::Error> {
+        let history = create_search_history();
+        let mut input = LineBuffer::new();
+
+        let outcome = handle_incremental_search(&mut "car\x12\x01".as_bytes(), &mut input, &history, SearchDirection::Backward, &Keymap::emacs())?;
+        assert!(matches!(outcome, SearchOutcome::Accept(Some((_, Some(KeyBinding::Action(EditorAction::BeginningOfLine)))))));
+        assert_eq!(input.as_str(), "cargo build");
+        assert_eq!(input.cursor(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_search_abort_restores_line() -> Result<(), anyhow::Error> {
+        let history = create_search_history();
+        let mut input = line_buffer("ec");
+
+        let outcome = handle_incremental_search(&mut "test\x07".as_bytes(), &mut input, &history, SearchDirection::Backward, &Keymap::emacs())?;
+        assert!(matches!(outcome, SearchOutcome::Abort));
+        assert_eq!(input.as_str(), "ec");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_char_multi_byte() -> Result<(), anyhow::Error> {
+        let mut reader = "aä漢👍".as_bytes();
+        assert_eq!(read_char(&mut reader)?, Some('a'));
+        assert_eq!(read_char(&mut reader)?, Some('ä'));
+        assert_eq!(read_char(&mut reader)?, Some('漢'));
+        assert_eq!(read_char(&mut reader)?, Some('👍'));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_char_invalid_sequence() -> Result<(), anyhow::Error> {
+        let mut reader: &[u8] = &[0xff, b'a', 0xc3, 0x28];
+        assert_eq!(read_char(&mut reader)?, None);
+        assert_eq!(read_char(&mut reader)?, Some('a'));
+        assert_eq!(read_char(&mut reader)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("echo"), 4);
+        assert_eq!(display_width("päivä"), 5);
+        assert_eq!(display_width("漢字"), 4);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+    }
+
+    #[test]
+    fn test_handle_backspace_wide_character() {
+        let mut input = line_buffer("ls 漢字");
+
+        let result = handle_backspace(&mut input);
+        assert!(result.is_ok());
+        assert_eq!(input.as_str(), "ls 漢");
+    }
+
+    #[test]
+    fn test_handle_regular_char_non_ascii() {
+        let mut input = line_buffer("echo p");
+
+        handle_regular_char(&mut input, 'ä').unwrap();
+        assert_eq!(input.as_str(), "echo pä");
+        assert_eq!(input.cursor(), 8);
+    }
+
+    fn read_keys(keys: &str) -> Result<Vec<(String, Option<KeyBinding>)>, anyhow::Error> {
+        let keymap = Keymap::emacs();
+        let mut reader = keys.as_bytes();
+        let mut sequences = Vec::new();
+        while !reader.is_empty() {
+            sequences.push(read_key_sequence(&mut reader, &keymap)?);
+        }
+        Ok(sequences)
+    }
+
+    #[test]
+    fn test_read_key_sequence() -> Result<(), anyhow::Error> {
+        let action = |action| Some(KeyBinding::Action(action));
+        assert_eq!(read_keys("\x1b[A")?, vec![("\x1b[A".to_string(), action(EditorAction::PreviousHistory))]);
+        assert_eq!(read_keys("\x1bOF")?, vec![("\x1bOF".to_string(), action(EditorAction::EndOfLine))]);
+        assert_eq!(read_keys("\x1b[3~")?, vec![("\x1b[3~".to_string(), action(EditorAction::DeleteChar))]);
+        assert_eq!(read_keys("\x1b[1;5C")?, vec![("\x1b[1;5C".to_string(), action(EditorAction::ForwardWord))]);
+        assert_eq!(read_keys("\x1bb")?, vec![("\x1bb".to_string(), action(EditorAction::BackwardWord))]);
+        assert_eq!(read_keys("\x01a")?, vec![
+            ("\x01".to_string(), action(EditorAction::BeginningOfLine)),
+            ("a".to_string(), None),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_unbound_terminal_sequence() -> Result<(), anyhow::Error> {
+        assert_eq!(read_keys("\x1b[2~x")?, vec![("\x1b[2~".to_string(), None), ("x".to_string(), None)]);
+        assert_eq!(read_keys("\x1b[15;2~")?, vec![("\x1b[15;2~".to_string(), None)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_bracketed_paste() -> Result<(), anyhow::Error> {
+        let mut reader = "\x1b[200~echo a\tb\recho c\r\n\x1b[201~x".as_bytes();
+        assert_eq!(read_key_sequence(&mut reader, &Keymap::emacs())?.1, Some(KeyBinding::Action(EditorAction::BracketedPasteBegin)));
+        assert_eq!(read_bracketed_paste(&mut reader)?, "echo a\tb\necho c\n");
+        assert_eq!(reader, b"x");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bracketed_paste_is_inserted_literally() -> Result<(), anyhow::Error> {
+        let mut input = line_buffer("echo ");
+        let mut reader = "a\tb\nc\x1b[201~".as_bytes();
+        handle_bracketed_paste(&mut reader, &mut input)?;
+        assert_eq!(input.as_str(), "echo a\tb\nc");
+        assert!(input.undo());
+        assert_eq!(input.as_str(), "echo ");
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_self_inserting() {
+        assert!(is_self_inserting("a"));
+        assert!(is_self_inserting("漢"));
+        assert!(!is_self_inserting("\x02"));
+        assert!(!is_self_inserting("\x1b[2~"));
+    }
+
+    #[test]
+    fn test_handle_kill_word_forward_and_backward() {
+        let mut input = LineBuffer::from_str_with_cursor("git commit --amend", 4);
+        let mut kill_ring = KillRing::new();
+        handle_kill(&mut input, &mut kill_ring, EditorAction::KillWord).unwrap();
+        assert_eq!(input.as_str(), "git  --amend");
+        input.move_end();
+        handle_kill(&mut input, &mut kill_ring, EditorAction::BackwardKillWord).unwrap();
+        assert_eq!(input.as_str(), "git  --");
+        assert_eq!(kill_ring.yank(), Some("amend"));
+    }
+
+    #[test]
+    fn test_incremental_search_hands_over_ending_key() -> Result<(), anyhow::Error> {
+        let history = create_search_history();
+        let mut input = LineBuffer::new();
+
+        let outcome = handle_incremental_search(&mut "echo\x02".as_bytes(), &mut input, &history, SearchDirection::Backward, &Keymap::emacs())?;
+        match outcome {
+            SearchOutcome::Accept(Some((sequence, binding))) => {
+                assert_eq!(sequence, "\x02");
+                assert_eq!(binding, Some(KeyBinding::Action(EditorAction::BackwardChar)));
+            }
+            _ => panic!("the search should end with the pressed key"),
+        }
+        assert_eq!(input.as_str(), "echo hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_vi_history_search_walks_older_and_newer_matches() -> Result<(), anyhow::Error> {
+        let history = create_prefix_search_history();
+        let mut input = line_buffer("");
+        let mut history_index = None;
+
+        assert!(handle_vi_history_search(&mut input, &mut history_index, &history, "commit", SearchDirection::Backward)?);
+        assert_eq!(input.as_str(), "git commit");
+        assert_eq!(input.cursor(), 0);
+        assert_eq!(history_index, Some(1));
+
+        assert!(handle_vi_history_search(&mut input, &mut history_index, &history, "commit", SearchDirection::Backward)?);
+        assert_eq!(history_index, Some(2));
+        assert!(!handle_vi_history_search(&mut input, &mut history_index, &history, "commit", SearchDirection::Backward)?);
+
+        assert!(handle_vi_history_search(&mut input, &mut history_index, &history, "cargo", SearchDirection::Forward)?);
+        assert_eq!(input.as_str(), "cargo test");
+        assert_eq!(history_index, Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_vi_history_search_forward_needs_recalled_entry() -> Result<(), anyhow::Error> {
+        let history = create_prefix_search_history();
+        let mut input = line_buffer("");
+        let mut history_index = None;
+        assert!(!handle_vi_history_search(&mut input, &mut history_index, &history, "git", SearchDirection::Forward)?);
+        assert_eq!(input.as_str(), "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_vi_search_pattern() -> Result<(), anyhow::Error> {
+        assert_eq!(read_vi_search_pattern(&mut "git\r".as_bytes(), SearchDirection::Backward)?, Some("git".to_string()));
+        assert_eq!(read_vi_search_pattern(&mut "gix\x7ft\r".as_bytes(), SearchDirection::Backward)?, Some("git".to_string()));
+        assert_eq!(read_vi_search_pattern(&mut "git\x1b".as_bytes(), SearchDirection::Forward)?, None);
+        assert_eq!(read_vi_search_pattern(&mut "\x7f".as_bytes(), SearchDirection::Forward)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vi_key_for_action() {
+        assert_eq!(vi_key_for_action(EditorAction::PreviousHistory), Some('k'));
+        assert_eq!(vi_key_for_action(EditorAction::BackwardChar), Some('h'));
+        assert_eq!(vi_key_for_action(EditorAction::EndOfLine), Some('$'));
+        assert_eq!(vi_key_for_action(EditorAction::Complete), None);
+    }
+}
@@ -0,0 +1,131 @@
+use unicode_width::UnicodeWidthStr;
+
+const DEFAULT_COLUMNS: usize = 80;
+const DEFAULT_ROWS: usize = 24;
+const ESC: char = '\u{001b}';
+// Readline's markers around the invisible parts of a prompt
+const START_IGNORE: char = '\u{0001}';
+const END_IGNORE: char = '\u{0002}';
+
+/// A terminal row and column counted from the start of the prompt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ScreenPosition {
+    pub(crate) row: usize,
+    pub(crate) column: usize,
+}
+
+/// Lays out a prompt followed by a possibly multi-line buffer whose continuation lines start with the `PS2` prompt.
+pub(crate) struct Layout {
+    prompt_width: usize,
+    continuation_prompt_width: usize,
+    columns: usize,
+}
+
+impl Layout {
+    pub(crate) fn new(prompt: &str, continuation_prompt: &str, columns: usize) -> Self {
+        Layout {
+            prompt_width: display_width(prompt),
+            continuation_prompt_width: display_width(continuation_prompt),
+            columns: columns.max(1),
+        }
+    }
+
+    /// Where drawing `text` after the prompt ends. A row filled up to its last column counts as wrapped,
+    /// although terminals keep the cursor on that row until the next character is printed.
+    pub(crate) fn position_after(&self, text: &str) -> ScreenPosition {
+        let mut row = 0;
+        let mut lines = text.split('\n').peekable();
+        let mut prompt_width = self.prompt_width;
+        while let Some(line) = lines.next() {
+            let width = prompt_width + display_width(line);
+            if lines.peek().is_none() {
+                return ScreenPosition { row: row + width / self.columns, column: width % self.columns };
+            }
+            row += width.div_ceil(self.columns).max(1);
+            prompt_width = self.continuation_prompt_width;
+        }
+        ScreenPosition { row, column: 0 }
+    }
+}
+
+/// Number of terminal columns the text occupies, East-Asian wide characters taking two and escape sequences,
+/// such as colors in a mode string, none.
+pub(crate) fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut visible = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            START_IGNORE => {
+                chars.by_ref().find(|ch| *ch == END_IGNORE);
+            }
+            ESC if chars.peek() == Some(&'[') => {
+                chars.next();
+                chars.by_ref().find(|ch| ('@'..='~').contains(ch));
+            }
+            ESC => {
+                chars.next();
+            }
+            _ => {
+                visible.push(ch);
+                continue;
+            }
+        }
+        width += visible.width();
+        visible.clear();
+    }
+    width + visible.width()
+}
+
+/// Width of the terminal on standard output, or 80 columns when it is not a terminal.
+pub(crate) fn terminal_columns() -> usize {
+    terminal_size().map(|size| size.ws_col as usize).filter(|columns| *columns > 0).unwrap_or(DEFAULT_COLUMNS)
+}
+
+/// Height of the terminal on standard output, or 24 rows when it is not a terminal.
+pub(crate) fn terminal_rows() -> usize {
+    terminal_size().map(|size| size.ws_row as usize).filter(|rows| *rows > 0).unwrap_or(DEFAULT_ROWS)
+}
+
+fn terminal_size() -> Option<libc::winsize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0).then_some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("echo"), 4);
+        assert_eq!(display_width("漢字"), 4);
+        assert_eq!(display_width("\x1b[1;32m(ins)\x1b[0m$ "), 7);
+        assert_eq!(display_width("\x01\x1b[2m\x02> "), 2);
+    }
+
+    #[test]
+    fn test_position_on_one_row() {
+        let layout = Layout::new("$ ", "> ", 20);
+        assert_eq!(layout.position_after(""), ScreenPosition { row: 0, column: 2 });
+        assert_eq!(layout.position_after("echo"), ScreenPosition { row: 0, column: 6 });
+    }
+
+    #[test]
+    fn test_position_in_wrapped_rows() {
+        let layout = Layout::new("$ ", "> ", 10);
+        assert_eq!(layout.position_after("echo hello"), ScreenPosition { row: 1, column: 2 });
+        assert_eq!(layout.position_after("echo he"), ScreenPosition { row: 0, column: 9 });
+        assert_eq!(layout.position_after("echo hel"), ScreenPosition { row: 1, column: 0 });
+    }
+
+    #[test]
+    fn test_position_after_continuation_lines() {
+        let layout = Layout::new("$ ", "> ", 10);
+        assert_eq!(layout.position_after("echo 'a\nb"), ScreenPosition { row: 1, column: 3 });
+        // A first line filling exactly one row does not take an extra row
+        assert_eq!(layout.position_after("echo 'ab\n"), ScreenPosition { row: 1, column: 2 });
+        assert_eq!(layout.position_after("echo 'abcdef\n\nx"), ScreenPosition { row: 3, column: 3 });
+    }
+}
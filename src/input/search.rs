@@ -0,0 +1,193 @@
+use crate::history::History;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SearchDirection {
+    Backward,
+    Forward,
+}
+
+/// State of an incremental history search started with Ctrl-R or Ctrl-S.
+pub(crate) struct IncrementalSearch {
+    query: String,
+    direction: SearchDirection,
+    match_index: Option<usize>,
+    failed: bool,
+}
+
+impl IncrementalSearch {
+    pub(crate) fn new(direction: SearchDirection) -> Self {
+        IncrementalSearch { query: String::new(), direction, match_index: None, failed: false }
+    }
+
+    pub(crate) fn push_char(&mut self, ch: char, history: &History) {
+        self.query.push(ch);
+        self.search(history, true);
+    }
+
+    /// Removes the last query character and restarts the search from the newest entry.
+    pub(crate) fn pop_char(&mut self, history: &History) {
+        self.query.pop();
+        self.match_index = None;
+        self.failed = false;
+        if !self.query.is_empty() {
+            self.search(history, true);
+        }
+    }
+
+    /// Jumps to the next match in the given direction (repeated Ctrl-R / Ctrl-S).
+    pub(crate) fn search_again(&mut self, direction: SearchDirection, history: &History) {
+        self.direction = direction;
+        if !self.query.is_empty() {
+            self.search(history, false);
+        }
+    }
+
+    pub(crate) fn current_match<'a>(&self, history: &'a History) -> Option<&'a str> {
+        self.match_index.and_then(|index| history.get(index))
+    }
+
+    /// Byte position of the query inside the current match, used to place the cursor.
+    pub(crate) fn match_position(&self, history: &History) -> Option<usize> {
+        self.current_match(history).and_then(|matched| matched.find(&self.query))
+    }
+
+    pub(crate) fn prompt(&self) -> String {
+        let failed = if self.failed { "failed " } else { "" };
+        let direction = match self.direction {
+            SearchDirection::Backward => "reverse-",
+            SearchDirection::Forward => "",
+        };
+        format!("({}{}i-search)`{}': ", failed, direction, self.query)
+    }
+
+    fn search(&mut self, history: &History, include_current: bool) {
+        let found = match (self.direction, self.match_index) {
+            (SearchDirection::Backward, None) => history.len().checked_sub(1)
+                .and_then(|from| history.search_backward(&self.query, from)),
+            (SearchDirection::Backward, Some(index)) => {
+                let from = if include_current { Some(index) } else { index.checked_sub(1) };
+                from.and_then(|from| history.search_backward(&self.query, from))
+            }
+            // Searching forward from the line being edited, there is nothing newer to find
+            (SearchDirection::Forward, None) => None,
+            (SearchDirection::Forward, Some(index)) => {
+                let from = if include_current { index } else { index + 1 };
+                history.search_forward(&self.query, from)
+            }
+        };
+        self.failed = found.is_none();
+        if found.is_some() {
+            self.match_index = found;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_history() -> History {
+        let mut history = History::new();
+        history.append("echo hello");
+        history.append("cargo build");
+        history.append("echo world");
+        history.append("cargo test");
+        history
+    }
+
+    #[test]
+    fn test_typing_narrows_to_newest_match() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('e', &history);
+        assert_eq!(search.current_match(&history), Some("cargo test"));
+        search.push_char('c', &history);
+        assert_eq!(search.current_match(&history), Some("echo world"));
+        assert_eq!(search.prompt(), "(reverse-i-search)`ec': ");
+    }
+
+    #[test]
+    fn test_repeated_search_goes_to_older_matches() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('c', &history);
+        search.push_char('a', &history);
+        assert_eq!(search.current_match(&history), Some("cargo test"));
+        search.search_again(SearchDirection::Backward, &history);
+        assert_eq!(search.current_match(&history), Some("cargo build"));
+        search.search_again(SearchDirection::Backward, &history);
+        assert_eq!(search.current_match(&history), Some("cargo build"));
+        assert_eq!(search.prompt(), "(failed reverse-i-search)`ca': ");
+    }
+
+    #[test]
+    fn test_forward_search_goes_back_to_newer_matches() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('e', &history);
+        search.push_char('c', &history);
+        search.search_again(SearchDirection::Backward, &history);
+        assert_eq!(search.current_match(&history), Some("echo hello"));
+        search.search_again(SearchDirection::Forward, &history);
+        assert_eq!(search.current_match(&history), Some("echo world"));
+        assert_eq!(search.prompt(), "(i-search)`ec': ");
+    }
+
+    #[test]
+    fn test_forward_search_without_match_fails() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Forward);
+
+        search.push_char('e', &history);
+        assert_eq!(search.current_match(&history), None);
+        assert_eq!(search.prompt(), "(failed i-search)`e': ");
+    }
+
+    #[test]
+    fn test_no_match_keeps_previous_match() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('w', &history);
+        search.push_char('x', &history);
+        assert_eq!(search.current_match(&history), Some("echo world"));
+        assert_eq!(search.prompt(), "(failed reverse-i-search)`wx': ");
+    }
+
+    #[test]
+    fn test_pop_char_restarts_search() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('b', &history);
+        search.push_char('x', &history);
+        search.pop_char(&history);
+        assert_eq!(search.current_match(&history), Some("cargo build"));
+        assert_eq!(search.prompt(), "(reverse-i-search)`b': ");
+        search.pop_char(&history);
+        assert_eq!(search.current_match(&history), None);
+    }
+
+    #[test]
+    fn test_match_position() {
+        let history = create_test_history();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('t', &history);
+        search.push_char('e', &history);
+        assert_eq!(search.match_position(&history), Some(6));
+    }
+
+    #[test]
+    fn test_search_in_empty_history() {
+        let history = History::new();
+        let mut search = IncrementalSearch::new(SearchDirection::Backward);
+
+        search.push_char('e', &history);
+        search.search_again(SearchDirection::Backward, &history);
+        assert_eq!(search.current_match(&history), None);
+    }
+}
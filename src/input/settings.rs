@@ -0,0 +1,204 @@
+use std::sync::RwLock;
+use lazy_static::lazy_static;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum EditingMode {
+    Emacs,
+    Vi,
+}
+
+/// Readline-style variables of the line editor, changed with `bind 'set name value'`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EditorSettings {
+    pub(crate) editing_mode: EditingMode,
+    /// Completion matches names regardless of case
+    pub(crate) completion_ignore_case: bool,
+    /// Completion also matches names containing the typed text, or its characters in order
+    pub(crate) completion_fuzzy: bool,
+    /// Listing at least this many completions first asks for confirmation
+    pub(crate) completion_query_items: usize,
+    /// Completion lists longer than the screen are shown a screenful at a time
+    pub(crate) page_completions: bool,
+    /// Up/Down only walk through history entries starting with the text before the cursor
+    pub(crate) history_prefix_search: bool,
+    /// Prefix the prompt with one of the mode strings below
+    pub(crate) show_mode_in_prompt: bool,
+    pub(crate) emacs_mode_string: String,
+    pub(crate) vi_ins_mode_string: String,
+    pub(crate) vi_cmd_mode_string: String,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        EditorSettings {
+            editing_mode: EditingMode::Emacs,
+            completion_ignore_case: false,
+            completion_fuzzy: false,
+            completion_query_items: 100,
+            page_completions: true,
+            history_prefix_search: true,
+            show_mode_in_prompt: false,
+            emacs_mode_string: "@".to_string(),
+            vi_ins_mode_string: "(ins)".to_string(),
+            vi_cmd_mode_string: "(cmd)".to_string(),
+        }
+    }
+}
+
+impl EditorSettings {
+    pub(crate) fn set_variable(&mut self, name: &str, value: &str) -> Result<(), anyhow::Error> {
+        match name {
+            "completion-fuzzy" => self.completion_fuzzy = parse_boolean(name, value)?,
+            "completion-ignore-case" => self.completion_ignore_case = parse_boolean(name, value)?,
+            "completion-query-items" => self.completion_query_items = parse_number(name, value)?,
+            "editing-mode" => self.editing_mode = parse_editing_mode(value)?,
+            "history-prefix-search" => self.history_prefix_search = parse_boolean(name, value)?,
+            "page-completions" => self.page_completions = parse_boolean(name, value)?,
+            "show-mode-in-prompt" => self.show_mode_in_prompt = parse_boolean(name, value)?,
+            "emacs-mode-string" => self.emacs_mode_string = value.to_string(),
+            "vi-ins-mode-string" => self.vi_ins_mode_string = value.to_string(),
+            "vi-cmd-mode-string" => self.vi_cmd_mode_string = value.to_string(),
+            _ => return Err(anyhow::anyhow!("{}: unknown variable name", name)),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn variables(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("completion-fuzzy", format_boolean(self.completion_fuzzy)),
+            ("completion-ignore-case", format_boolean(self.completion_ignore_case)),
+            ("completion-query-items", self.completion_query_items.to_string()),
+            ("editing-mode", format_editing_mode(self.editing_mode)),
+            ("emacs-mode-string", self.emacs_mode_string.clone()),
+            ("history-prefix-search", format_boolean(self.history_prefix_search)),
+            ("page-completions", format_boolean(self.page_completions)),
+            ("show-mode-in-prompt", format_boolean(self.show_mode_in_prompt)),
+            ("vi-cmd-mode-string", self.vi_cmd_mode_string.clone()),
+            ("vi-ins-mode-string", self.vi_ins_mode_string.clone()),
+        ]
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: RwLock<EditorSettings> = RwLock::new(EditorSettings::default());
+}
+
+pub(crate) fn current() -> EditorSettings {
+    SETTINGS.read().map(|settings| settings.clone()).unwrap_or_default()
+}
+
+pub(crate) fn set_variable(name: &str, value: &str) -> Result<(), anyhow::Error> {
+    let mut settings = SETTINGS.write()
+        .map_err(|_| anyhow::anyhow!("Editor settings are unavailable"))?;
+    settings.set_variable(name, value)
+}
+
+pub(crate) fn set_editing_mode(editing_mode: EditingMode) -> Result<(), anyhow::Error> {
+    let mut settings = SETTINGS.write()
+        .map_err(|_| anyhow::anyhow!("Editor settings are unavailable"))?;
+    settings.editing_mode = editing_mode;
+    Ok(())
+}
+
+fn parse_editing_mode(value: &str) -> Result<EditingMode, anyhow::Error> {
+    match value {
+        "emacs" => Ok(EditingMode::Emacs),
+        "vi" => Ok(EditingMode::Vi),
+        _ => Err(anyhow::anyhow!("editing-mode: invalid value '{}', expected emacs or vi", value)),
+    }
+}
+
+fn format_editing_mode(editing_mode: EditingMode) -> String {
+    match editing_mode {
+        EditingMode::Emacs => "emacs".to_string(),
+        EditingMode::Vi => "vi".to_string(),
+    }
+}
+
+/// Accepts readline's boolean values "on"/"off" in any case, as well as "1"/"0".
+fn parse_boolean(name: &str, value: &str) -> Result<bool, anyhow::Error> {
+    match value.to_lowercase().as_str() {
+        "on" | "1" => Ok(true),
+        "off" | "0" => Ok(false),
+        _ => Err(anyhow::anyhow!("{}: invalid value '{}', expected on or off", name, value)),
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<usize, anyhow::Error> {
+    value.parse().map_err(|_| anyhow::anyhow!("{}: invalid value '{}', expected a number", name, value))
+}
+
+fn format_boolean(value: bool) -> String {
+    if value { "on".to_string() } else { "off".to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_prefix_search_is_on_by_default() {
+        assert!(EditorSettings::default().history_prefix_search);
+    }
+
+    #[test]
+    fn test_set_boolean_variable() -> Result<(), anyhow::Error> {
+        let mut settings = EditorSettings::default();
+        settings.set_variable("history-prefix-search", "off")?;
+        assert!(!settings.history_prefix_search);
+        settings.set_variable("history-prefix-search", "On")?;
+        assert!(settings.history_prefix_search);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_invalid_value() {
+        let mut settings = EditorSettings::default();
+        assert!(settings.set_variable("history-prefix-search", "maybe").is_err());
+        assert!(settings.history_prefix_search);
+    }
+
+    #[test]
+    fn test_set_completion_query_items() -> Result<(), anyhow::Error> {
+        let mut settings = EditorSettings::default();
+        assert_eq!(settings.completion_query_items, 100);
+        settings.set_variable("completion-query-items", "250")?;
+        assert_eq!(settings.completion_query_items, 250);
+        assert!(settings.set_variable("completion-query-items", "many").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_unknown_variable() {
+        let mut settings = EditorSettings::default();
+        assert!(settings.set_variable("no-such-variable", "on").is_err());
+    }
+
+    #[test]
+    fn test_set_editing_mode() -> Result<(), anyhow::Error> {
+        let mut settings = EditorSettings::default();
+        assert_eq!(settings.editing_mode, EditingMode::Emacs);
+        settings.set_variable("editing-mode", "vi")?;
+        assert_eq!(settings.editing_mode, EditingMode::Vi);
+        assert!(settings.set_variable("editing-mode", "nano").is_err());
+        assert_eq!(settings.editing_mode, EditingMode::Vi);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_string_variable() -> Result<(), anyhow::Error> {
+        let mut settings = EditorSettings::default();
+        settings.set_variable("vi-cmd-mode-string", "[N]")?;
+        assert_eq!(settings.vi_cmd_mode_string, "[N]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() {
+        let settings = EditorSettings::default();
+        let variables = settings.variables();
+        assert!(variables.contains(&("editing-mode", "emacs".to_string())));
+        assert!(variables.contains(&("history-prefix-search", "on".to_string())));
+        assert!(variables.contains(&("show-mode-in-prompt", "off".to_string())));
+    }
+}
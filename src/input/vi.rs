@@ -0,0 +1,863 @@
+use crate::input::line_buffer::LineBuffer;
+use crate::input::search::SearchDirection;
+
+const ESC: char = '\u{001b}';
+const BACKSPACE: char = '\u{7f}';
+// Larger counts are cut down to this, like readline does with numeric arguments
+pub(crate) const MAX_COUNT: usize = 1_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ViMode {
+    Insert,
+    Normal,
+}
+
+/// What the line editor has to do after a key was handled in vi normal mode.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ViOutcome {
+    /// The command is not complete yet, e.g. after `d` or `2`
+    Pending,
+    /// The buffer, the cursor or the mode changed and the line has to be redrawn
+    Changed,
+    Beep,
+    HistoryPrevious(usize),
+    HistoryNext(usize),
+    /// `/` or `?` asks for a history search pattern
+    SearchHistory(SearchDirection),
+    /// `n` repeats the last search, `N` repeats it in the opposite direction
+    RepeatSearch { reverse: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Motion {
+    Left,
+    Right,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    Find(FindChar),
+    RepeatFind { reverse: bool },
+    /// `dd`, `cc` and `yy` act on the whole line
+    WholeLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FindChar {
+    target: char,
+    forward: bool,
+    /// `t`/`T` stop one character before the target
+    till: bool,
+}
+
+impl FindChar {
+    fn reversed(self) -> Self {
+        FindChar { forward: !self.forward, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InsertPosition {
+    BeforeCursor,
+    AfterCursor,
+    LineStart,
+    LineEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Move(Motion),
+    Operate(Operator, Motion),
+    Insert(InsertPosition),
+    Put { before_cursor: bool },
+    Undo,
+    RepeatChange,
+    HistoryPrevious,
+    HistoryNext,
+    Search(SearchDirection),
+    RepeatSearch { reverse: bool },
+}
+
+#[derive(Debug, PartialEq)]
+enum Parsed {
+    Incomplete,
+    Invalid,
+    Command(Option<usize>, Action),
+}
+
+/// Modal editing state of `set -o vi`, kept for the duration of one line.
+pub(crate) struct ViState {
+    mode: ViMode,
+    pending: Vec<char>,
+    register: String,
+    last_find: Option<FindChar>,
+    last_change: Vec<char>,
+    recording: Option<Vec<char>>,
+}
+
+impl ViState {
+    /// Editing a new line starts in insert mode, like in bash, so the typed text is one undo step.
+    pub(crate) fn new(buffer: &mut LineBuffer) -> Self {
+        buffer.begin_undo_group();
+        ViState {
+            mode: ViMode::Insert,
+            pending: Vec::new(),
+            register: String::new(),
+            last_find: None,
+            last_change: Vec::new(),
+            recording: None,
+        }
+    }
+
+    pub(crate) fn mode(&self) -> ViMode {
+        self.mode
+    }
+
+    /// Esc in insert mode: the cursor moves back onto the last inserted character.
+    pub(crate) fn enter_normal_mode(&mut self, buffer: &mut LineBuffer) {
+        self.mode = ViMode::Normal;
+        self.pending.clear();
+        buffer.end_undo_group();
+        buffer.move_left();
+        if let Some(mut keys) = self.recording.take() {
+            keys.push(ESC);
+            self.last_change = keys;
+        }
+    }
+
+    /// Keeps track of text typed in insert mode so that `.` can repeat it.
+    pub(crate) fn record_insert_key(&mut self, key: char) {
+        if let Some(keys) = self.recording.as_mut() {
+            keys.push(key);
+        }
+    }
+
+    /// Drops a half-typed command such as `d2` when Esc is pressed in normal mode.
+    pub(crate) fn cancel_pending(&mut self) -> bool {
+        let had_pending = !self.pending.is_empty();
+        self.pending.clear();
+        had_pending
+    }
+
+    pub(crate) fn handle_key(&mut self, key: char, buffer: &mut LineBuffer) -> ViOutcome {
+        self.pending.push(key);
+        match parse_command(&self.pending) {
+            Parsed::Incomplete => ViOutcome::Pending,
+            Parsed::Invalid => {
+                self.pending.clear();
+                ViOutcome::Beep
+            }
+            Parsed::Command(count, action) => {
+                let keys = std::mem::take(&mut self.pending);
+                let outcome = self.execute(count, action, &keys, buffer);
+                if self.mode == ViMode::Normal && buffer.is_cursor_at_end() {
+                    buffer.move_left();
+                }
+                outcome
+            }
+        }
+    }
+
+    fn execute(&mut self, count: Option<usize>, action: Action, keys: &[char], buffer: &mut LineBuffer) -> ViOutcome {
+        let repeat = count.unwrap_or(1);
+        match action {
+            Action::Move(motion) => match self.motion_target(buffer, motion, repeat, false) {
+                Some(target) => {
+                    buffer.set_cursor(target);
+                    ViOutcome::Changed
+                }
+                None => ViOutcome::Beep,
+            },
+            Action::Operate(operator, motion) => {
+                // A change stays one undo step together with the text typed until Esc
+                buffer.begin_undo_group();
+                let operated = self.operate(buffer, operator, motion, repeat);
+                if operated && operator == Operator::Change {
+                    self.mode = ViMode::Insert;
+                } else {
+                    buffer.end_undo_group();
+                }
+                if !operated {
+                    return ViOutcome::Beep;
+                }
+                self.record_change(keys);
+                ViOutcome::Changed
+            }
+            Action::Insert(position) => {
+                buffer.begin_undo_group();
+                match position {
+                    InsertPosition::BeforeCursor => (),
+                    InsertPosition::AfterCursor => { buffer.move_right(); }
+                    InsertPosition::LineStart => buffer.move_home(),
+                    InsertPosition::LineEnd => buffer.move_end(),
+                }
+                self.mode = ViMode::Insert;
+                self.record_change(keys);
+                ViOutcome::Changed
+            }
+            Action::Put { before_cursor } => {
+                if self.register.is_empty() {
+                    return ViOutcome::Beep;
+                }
+                if !before_cursor {
+                    buffer.move_right();
+                }
+                // The pasted text is kept within the count limit however long the register is
+                let repeat = repeat.min((MAX_COUNT / self.register.len()).max(1));
+                buffer.insert_str(&self.register.repeat(repeat));
+                buffer.move_left();
+                self.record_change(keys);
+                ViOutcome::Changed
+            }
+            Action::Undo if buffer.undo() => ViOutcome::Changed,
+            Action::Undo => ViOutcome::Beep,
+            Action::RepeatChange => self.repeat_last_change(count, buffer),
+            Action::HistoryPrevious => ViOutcome::HistoryPrevious(repeat),
+            Action::HistoryNext => ViOutcome::HistoryNext(repeat),
+            Action::Search(direction) => ViOutcome::SearchHistory(direction),
+            Action::RepeatSearch { reverse } => ViOutcome::RepeatSearch { reverse },
+        }
+    }
+
+    fn operate(&mut self, buffer: &mut LineBuffer, operator: Operator, motion: Motion, count: usize) -> bool {
+        let cursor = buffer.cursor();
+        let text = buffer.as_str().to_string();
+        // Like in vi, `cw` on a word changes only up to the end of the word
+        let on_word = text[cursor..].chars().next().map(|ch| !ch.is_whitespace()).unwrap_or(false);
+        let motion = if operator == Operator::Change && motion == Motion::WordForward && on_word {
+            Motion::WordEnd
+        } else {
+            motion
+        };
+        let (start, end) = if motion == Motion::WholeLine {
+            (0, text.len())
+        } else {
+            let target = match self.motion_target(buffer, motion, count, true) {
+                Some(target) => target,
+                None => return false,
+            };
+            if is_inclusive(motion) {
+                (cursor.min(target), buffer.next_boundary(cursor.max(target)))
+            } else {
+                (cursor.min(target), cursor.max(target))
+            }
+        };
+        if start == end && motion != Motion::WholeLine {
+            return false;
+        }
+        self.register = text[start..end].to_string();
+        match operator {
+            Operator::Yank => buffer.set_cursor(if motion == Motion::WholeLine { cursor } else { start }),
+            Operator::Delete | Operator::Change => { buffer.delete_range(start, end); }
+        }
+        true
+    }
+
+    /// Computes where a motion repeated `count` times ends, `None` when it cannot move at all.
+    fn motion_target(&mut self, buffer: &LineBuffer, motion: Motion, count: usize, for_operator: bool) -> Option<usize> {
+        let text = buffer.as_str();
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let position = chars.iter().position(|(idx, _)| *idx == buffer.cursor()).unwrap_or(chars.len());
+        let byte_index = |position: usize| chars.get(position).map(|(idx, _)| *idx).unwrap_or(text.len());
+        let mut moved = LineBuffer::from_str_with_cursor(text, buffer.cursor());
+        match motion {
+            Motion::Left => {
+                let mut any = false;
+                for _ in 0..count {
+                    if !moved.move_left() {
+                        break;
+                    }
+                    any = true;
+                }
+                any.then_some(moved.cursor())
+            }
+            Motion::Right => {
+                let mut any = false;
+                for _ in 0..count {
+                    // Outside of operators the cursor stays on the last character
+                    if !for_operator && moved.next_boundary(moved.cursor()) >= text.len() {
+                        break;
+                    }
+                    if !moved.move_right() {
+                        break;
+                    }
+                    any = true;
+                }
+                any.then_some(moved.cursor())
+            }
+            Motion::WordForward => {
+                let mut target = position;
+                // Stops early at the ends of the line, where the motion no longer moves
+                for _ in 0..count {
+                    let next = next_word_start(&chars, target);
+                    if next == target {
+                        break;
+                    }
+                    target = next;
+                }
+                Some(byte_index(target))
+            }
+            Motion::WordBackward => {
+                let mut target = position;
+                for _ in 0..count {
+                    let next = previous_word_start(&chars, target);
+                    if next == target {
+                        break;
+                    }
+                    target = next;
+                }
+                Some(byte_index(target))
+            }
+            Motion::WordEnd => {
+                let mut target = position;
+                for _ in 0..count {
+                    let next = word_end(&chars, target);
+                    if next == target {
+                        break;
+                    }
+                    target = next;
+                }
+                (target < chars.len()).then(|| byte_index(target))
+            }
+            Motion::LineStart => Some(0),
+            Motion::FirstNonBlank => Some(byte_index(chars.iter().position(|(_, ch)| !ch.is_whitespace()).unwrap_or(chars.len()))),
+            Motion::LineEnd => (!chars.is_empty()).then(|| byte_index(chars.len() - 1)),
+            Motion::Find(find) => {
+                self.last_find = Some(find);
+                find_char(&chars, position, find, count).map(byte_index)
+            }
+            Motion::RepeatFind { reverse } => {
+                let find = self.last_find?;
+                let find = if reverse { find.reversed() } else { find };
+                // Repeating `t` must not get stuck right before the character it stopped at
+                let start = match (find.till, find.forward) {
+                    (true, true) => position + 1,
+                    (true, false) => position.saturating_sub(1),
+                    _ => position,
+                };
+                find_char(&chars, start, find, count).map(byte_index)
+            }
+            Motion::WholeLine => Some(0),
+        }
+    }
+
+    fn record_change(&mut self, keys: &[char]) {
+        if self.mode == ViMode::Insert {
+            self.recording = Some(keys.to_vec());
+        } else {
+            self.last_change = keys.to_vec();
+        }
+    }
+
+    /// `.` replays the keys of the last change, a new count replacing the original one.
+    fn repeat_last_change(&mut self, count: Option<usize>, buffer: &mut LineBuffer) -> ViOutcome {
+        if self.last_change.is_empty() {
+            return ViOutcome::Beep;
+        }
+        let mut keys: Vec<char> = self.last_change.clone();
+        if let Some(count) = count {
+            let original_count_length = keys.iter().take_while(|ch| ch.is_ascii_digit()).count();
+            keys.splice(..original_count_length, count.to_string().chars());
+        }
+        for key in keys.iter().copied() {
+            match self.mode {
+                ViMode::Normal => { self.handle_key(key, buffer); }
+                ViMode::Insert if key == ESC => self.enter_normal_mode(buffer),
+                ViMode::Insert if key == BACKSPACE => { buffer.delete_before_cursor(); }
+                ViMode::Insert => buffer.insert_char(key),
+            }
+        }
+        self.last_change = keys;
+        ViOutcome::Changed
+    }
+}
+
+fn is_inclusive(motion: Motion) -> bool {
+    match motion {
+        Motion::WordEnd | Motion::LineEnd => true,
+        Motion::Find(find) => find.forward,
+        Motion::RepeatFind { .. } => true,
+        _ => false,
+    }
+}
+
+/// Splits off a leading count; a count cannot start with `0`, which is the line start motion.
+fn take_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    if keys.first().map(|ch| matches!(ch, '1'..='9')).unwrap_or(false) {
+        let length = keys.iter().take_while(|ch| ch.is_ascii_digit()).count();
+        let count = keys[..length].iter()
+            .filter_map(|ch| ch.to_digit(10))
+            .fold(0usize, |count, digit| count.saturating_mul(10).saturating_add(digit as usize))
+            .min(MAX_COUNT);
+        (Some(count), &keys[length..])
+    } else {
+        (None, keys)
+    }
+}
+
+fn multiply_counts(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (None, None) => None,
+        (first, second) => Some(first.unwrap_or(1).saturating_mul(second.unwrap_or(1)).min(MAX_COUNT)),
+    }
+}
+
+fn parse_command(keys: &[char]) -> Parsed {
+    let (count, rest) = take_count(keys);
+    let key = match rest.first() {
+        Some(key) => *key,
+        None => return Parsed::Incomplete,
+    };
+    let complete = |action: Action| {
+        if rest.len() == 1 { Parsed::Command(count, action) } else { Parsed::Invalid }
+    };
+    match key {
+        'd' | 'c' | 'y' => {
+            let operator = match key {
+                'd' => Operator::Delete,
+                'c' => Operator::Change,
+                _ => Operator::Yank,
+            };
+            let (motion_count, motion_keys) = take_count(&rest[1..]);
+            let count = multiply_counts(count, motion_count);
+            match motion_keys {
+                [] => Parsed::Incomplete,
+                [repeated] if *repeated == key => Parsed::Command(count, Action::Operate(operator, Motion::WholeLine)),
+                _ => match parse_motion(motion_keys) {
+                    Parsed::Command(_, Action::Move(motion)) => Parsed::Command(count, Action::Operate(operator, motion)),
+                    other => other,
+                },
+            }
+        }
+        'x' => complete(Action::Operate(Operator::Delete, Motion::Right)),
+        'X' => complete(Action::Operate(Operator::Delete, Motion::Left)),
+        's' => complete(Action::Operate(Operator::Change, Motion::Right)),
+        'D' => complete(Action::Operate(Operator::Delete, Motion::LineEnd)),
+        'C' => complete(Action::Operate(Operator::Change, Motion::LineEnd)),
+        'S' => complete(Action::Operate(Operator::Change, Motion::WholeLine)),
+        'i' => complete(Action::Insert(InsertPosition::BeforeCursor)),
+        'a' => complete(Action::Insert(InsertPosition::AfterCursor)),
+        'I' => complete(Action::Insert(InsertPosition::LineStart)),
+        'A' => complete(Action::Insert(InsertPosition::LineEnd)),
+        'p' => complete(Action::Put { before_cursor: false }),
+        'P' => complete(Action::Put { before_cursor: true }),
+        'u' => complete(Action::Undo),
+        '.' => complete(Action::RepeatChange),
+        'k' | '-' => complete(Action::HistoryPrevious),
+        'j' | '+' => complete(Action::HistoryNext),
+        '/' => complete(Action::Search(SearchDirection::Backward)),
+        '?' => complete(Action::Search(SearchDirection::Forward)),
+        'n' => complete(Action::RepeatSearch { reverse: false }),
+        'N' => complete(Action::RepeatSearch { reverse: true }),
+        _ => match parse_motion(rest) {
+            Parsed::Command(_, action) => Parsed::Command(count, action),
+            other => other,
+        },
+    }
+}
+
+fn parse_motion(keys: &[char]) -> Parsed {
+    let motion = match keys {
+        ['h'] => Motion::Left,
+        ['l'] | [' '] => Motion::Right,
+        ['w'] => Motion::WordForward,
+        ['b'] => Motion::WordBackward,
+        ['e'] => Motion::WordEnd,
+        ['0'] => Motion::LineStart,
+        ['^'] => Motion::FirstNonBlank,
+        ['$'] => Motion::LineEnd,
+        [';'] => Motion::RepeatFind { reverse: false },
+        [','] => Motion::RepeatFind { reverse: true },
+        ['f' | 't' | 'F' | 'T'] => return Parsed::Incomplete,
+        [kind @ ('f' | 't' | 'F' | 'T'), target] => Motion::Find(FindChar {
+            target: *target,
+            forward: matches!(kind, 'f' | 't'),
+            till: matches!(kind, 't' | 'T'),
+        }),
+        _ => return Parsed::Invalid,
+    };
+    Parsed::Command(None, Action::Move(motion))
+}
+
+/// Vi distinguishes words of letters, digits and underscores from runs of other non-blank characters.
+fn char_class(ch: char) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if ch.is_alphanumeric() || ch == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn next_word_start(chars: &[(usize, char)], mut position: usize) -> usize {
+    if position >= chars.len() {
+        return chars.len();
+    }
+    let class = char_class(chars[position].1);
+    if class != 0 {
+        while position < chars.len() && char_class(chars[position].1) == class {
+            position += 1;
+        }
+    }
+    while position < chars.len() && char_class(chars[position].1) == 0 {
+        position += 1;
+    }
+    position
+}
+
+fn previous_word_start(chars: &[(usize, char)], mut position: usize) -> usize {
+    if position == 0 {
+        return 0;
+    }
+    position -= 1;
+    while position > 0 && char_class(chars[position].1) == 0 {
+        position -= 1;
+    }
+    let class = char_class(chars[position].1);
+    while position > 0 && char_class(chars[position - 1].1) == class {
+        position -= 1;
+    }
+    position
+}
+
+fn word_end(chars: &[(usize, char)], mut position: usize) -> usize {
+    position += 1;
+    while position < chars.len() && char_class(chars[position].1) == 0 {
+        position += 1;
+    }
+    if position >= chars.len() {
+        return chars.len();
+    }
+    let class = char_class(chars[position].1);
+    while position + 1 < chars.len() && char_class(chars[position + 1].1) == class {
+        position += 1;
+    }
+    position
+}
+
+fn find_char(chars: &[(usize, char)], position: usize, find: FindChar, count: usize) -> Option<usize> {
+    let mut found = position;
+    for _ in 0..count {
+        found = if find.forward {
+            (found + 1..chars.len()).find(|&idx| chars[idx].1 == find.target)?
+        } else {
+            (0..found.min(chars.len())).rev().find(|&idx| chars[idx].1 == find.target)?
+        };
+    }
+    match (find.till, find.forward) {
+        (true, true) => Some(found - 1),
+        (true, false) => Some(found + 1),
+        _ => Some(found),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normal_mode(text: &str, cursor: usize) -> (ViState, LineBuffer) {
+        let mut buffer = LineBuffer::from_str_with_cursor(text, cursor);
+        let mut vi = ViState::new(&mut buffer);
+        vi.mode = ViMode::Normal;
+        buffer.end_undo_group();
+        (vi, buffer)
+    }
+
+    fn type_keys(vi: &mut ViState, buffer: &mut LineBuffer, keys: &str) -> ViOutcome {
+        let mut outcome = ViOutcome::Pending;
+        for key in keys.chars() {
+            outcome = match vi.mode() {
+                ViMode::Normal => vi.handle_key(key, buffer),
+                ViMode::Insert if key == ESC => {
+                    vi.enter_normal_mode(buffer);
+                    ViOutcome::Changed
+                }
+                ViMode::Insert => {
+                    vi.record_insert_key(key);
+                    buffer.insert_char(key);
+                    ViOutcome::Changed
+                }
+            };
+        }
+        outcome
+    }
+
+    #[test]
+    fn test_enter_normal_mode_moves_cursor_back() {
+        let mut buffer = LineBuffer::from_str_with_cursor("echo", 4);
+        let mut vi = ViState::new(&mut buffer);
+        vi.enter_normal_mode(&mut buffer);
+        assert_eq!(vi.mode(), ViMode::Normal);
+        assert_eq!(buffer.cursor(), 3);
+    }
+
+    #[test]
+    fn test_character_motions() {
+        let (mut vi, mut buffer) = normal_mode("echo", 3);
+        type_keys(&mut vi, &mut buffer, "h");
+        assert_eq!(buffer.cursor(), 2);
+        type_keys(&mut vi, &mut buffer, "0");
+        assert_eq!(buffer.cursor(), 0);
+        type_keys(&mut vi, &mut buffer, "$");
+        assert_eq!(buffer.cursor(), 3);
+        type_keys(&mut vi, &mut buffer, "l");
+        assert_eq!(buffer.cursor(), 3);
+        type_keys(&mut vi, &mut buffer, "2h");
+        assert_eq!(buffer.cursor(), 1);
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let (mut vi, mut buffer) = normal_mode("cat foo.txt | wc", 0);
+        type_keys(&mut vi, &mut buffer, "w");
+        assert_eq!(buffer.cursor(), 4);
+        type_keys(&mut vi, &mut buffer, "w");
+        assert_eq!(buffer.cursor(), 7);
+        type_keys(&mut vi, &mut buffer, "e");
+        assert_eq!(buffer.cursor(), 10);
+        type_keys(&mut vi, &mut buffer, "2w");
+        assert_eq!(buffer.cursor(), 14);
+        type_keys(&mut vi, &mut buffer, "b");
+        assert_eq!(buffer.cursor(), 12);
+        type_keys(&mut vi, &mut buffer, "3b");
+        assert_eq!(buffer.cursor(), 4);
+    }
+
+    #[test]
+    fn test_find_motions() {
+        let (mut vi, mut buffer) = normal_mode("a,b,c,d", 0);
+        type_keys(&mut vi, &mut buffer, "f,");
+        assert_eq!(buffer.cursor(), 1);
+        type_keys(&mut vi, &mut buffer, ";");
+        assert_eq!(buffer.cursor(), 3);
+        type_keys(&mut vi, &mut buffer, ",");
+        assert_eq!(buffer.cursor(), 1);
+        type_keys(&mut vi, &mut buffer, "tc");
+        assert_eq!(buffer.cursor(), 3);
+        type_keys(&mut vi, &mut buffer, "$Fb");
+        assert_eq!(buffer.cursor(), 2);
+        type_keys(&mut vi, &mut buffer, "Ta");
+        assert_eq!(buffer.cursor(), 1);
+        assert_eq!(type_keys(&mut vi, &mut buffer, "fz"), ViOutcome::Beep);
+    }
+
+    #[test]
+    fn test_delete_with_motions() {
+        let (mut vi, mut buffer) = normal_mode("echo hello world", 5);
+        type_keys(&mut vi, &mut buffer, "dw");
+        assert_eq!(buffer.as_str(), "echo world");
+        assert_eq!(buffer.cursor(), 5);
+        type_keys(&mut vi, &mut buffer, "d$");
+        assert_eq!(buffer.as_str(), "echo ");
+        assert_eq!(buffer.cursor(), 4);
+        type_keys(&mut vi, &mut buffer, "d0");
+        assert_eq!(buffer.as_str(), " ");
+    }
+
+    #[test]
+    fn test_delete_with_counts() {
+        let (mut vi, mut buffer) = normal_mode("a b c d e f", 0);
+        type_keys(&mut vi, &mut buffer, "2dw");
+        assert_eq!(buffer.as_str(), "c d e f");
+        type_keys(&mut vi, &mut buffer, "d2w");
+        assert_eq!(buffer.as_str(), "e f");
+        type_keys(&mut vi, &mut buffer, "3x");
+        assert_eq!(buffer.as_str(), "");
+    }
+
+    #[test]
+    fn test_huge_counts_are_limited() {
+        assert_eq!(take_count(&['9'; 30]).0, Some(MAX_COUNT));
+        assert_eq!(multiply_counts(Some(MAX_COUNT), Some(MAX_COUNT)), Some(MAX_COUNT));
+
+        let (mut vi, mut buffer) = normal_mode("echo hello world", 0);
+        type_keys(&mut vi, &mut buffer, "99999999999w");
+        assert_eq!(buffer.cursor(), "echo hello world".len() - 1);
+        type_keys(&mut vi, &mut buffer, "9999999999b");
+        assert_eq!(buffer.cursor(), 0);
+        type_keys(&mut vi, &mut buffer, "9999999999l");
+        assert_eq!(buffer.cursor(), "echo hello world".len() - 1);
+
+        let (mut vi, mut buffer) = normal_mode("ab", 0);
+        type_keys(&mut vi, &mut buffer, "yl9999999999p");
+        assert_eq!(buffer.as_str().len(), 2 + MAX_COUNT);
+    }
+
+    #[test]
+    fn test_delete_to_found_character_is_inclusive() {
+        let (mut vi, mut buffer) = normal_mode("git commit -m 'x'", 0);
+        type_keys(&mut vi, &mut buffer, "df ");
+        assert_eq!(buffer.as_str(), "commit -m 'x'");
+        type_keys(&mut vi, &mut buffer, "dt-");
+        assert_eq!(buffer.as_str(), "-m 'x'");
+    }
+
+    #[test]
+    fn test_dd_and_cc() {
+        let (mut vi, mut buffer) = normal_mode("echo hello", 3);
+        type_keys(&mut vi, &mut buffer, "dd");
+        assert_eq!(buffer.as_str(), "");
+
+        let (mut vi, mut buffer) = normal_mode("echo hello", 3);
+        type_keys(&mut vi, &mut buffer, "ccls");
+        assert_eq!(buffer.as_str(), "ls");
+        assert_eq!(vi.mode(), ViMode::Insert);
+    }
+
+    #[test]
+    fn test_change_word() {
+        let (mut vi, mut buffer) = normal_mode("echo hello world", 5);
+        type_keys(&mut vi, &mut buffer, "cwbye\u{1b}");
+        assert_eq!(buffer.as_str(), "echo bye world");
+        assert_eq!(vi.mode(), ViMode::Normal);
+        assert_eq!(buffer.cursor(), 7);
+    }
+
+    #[test]
+    fn test_x_and_capital_x() {
+        let (mut vi, mut buffer) = normal_mode("abcd", 1);
+        type_keys(&mut vi, &mut buffer, "x");
+        assert_eq!(buffer.as_str(), "acd");
+        type_keys(&mut vi, &mut buffer, "X");
+        assert_eq!(buffer.as_str(), "cd");
+        assert_eq!(buffer.cursor(), 0);
+        assert_eq!(type_keys(&mut vi, &mut buffer, "X"), ViOutcome::Beep);
+    }
+
+    #[test]
+    fn test_x_at_end_of_line() {
+        let (mut vi, mut buffer) = normal_mode("abc", 2);
+        type_keys(&mut vi, &mut buffer, "x");
+        assert_eq!(buffer.as_str(), "ab");
+        assert_eq!(buffer.cursor(), 1);
+    }
+
+    #[test]
+    fn test_yank_and_put() {
+        let (mut vi, mut buffer) = normal_mode("echo hello", 5);
+        type_keys(&mut vi, &mut buffer, "yw");
+        assert_eq!(buffer.as_str(), "echo hello");
+        type_keys(&mut vi, &mut buffer, "$p");
+        assert_eq!(buffer.as_str(), "echo hellohello");
+        assert_eq!(buffer.cursor(), 14);
+        type_keys(&mut vi, &mut buffer, "0P");
+        assert_eq!(buffer.as_str(), "helloecho hellohello");
+    }
+
+    #[test]
+    fn test_delete_and_put_swaps_characters() {
+        let (mut vi, mut buffer) = normal_mode("ecoh", 2);
+        type_keys(&mut vi, &mut buffer, "xp");
+        assert_eq!(buffer.as_str(), "echo");
+    }
+
+    #[test]
+    fn test_insert_commands() {
+        let (mut vi, mut buffer) = normal_mode("ell", 1);
+        type_keys(&mut vi, &mut buffer, "Ih\u{1b}");
+        assert_eq!(buffer.as_str(), "hell");
+        type_keys(&mut vi, &mut buffer, "Ao\u{1b}");
+        assert_eq!(buffer.as_str(), "hello");
+        type_keys(&mut vi, &mut buffer, "0a-\u{1b}");
+        assert_eq!(buffer.as_str(), "h-ello");
+        type_keys(&mut vi, &mut buffer, "i+\u{1b}");
+        assert_eq!(buffer.as_str(), "h+-ello");
+    }
+
+    #[test]
+    fn test_undo() {
+        let (mut vi, mut buffer) = normal_mode("echo hello world", 5);
+        type_keys(&mut vi, &mut buffer, "dw");
+        type_keys(&mut vi, &mut buffer, "x");
+        assert_eq!(buffer.as_str(), "echo orld");
+        type_keys(&mut vi, &mut buffer, "u");
+        assert_eq!(buffer.as_str(), "echo world");
+        type_keys(&mut vi, &mut buffer, "u");
+        assert_eq!(buffer.as_str(), "echo hello world");
+        assert_eq!(buffer.cursor(), 5);
+    }
+
+    #[test]
+    fn test_undo_reverts_typed_text_of_new_line() {
+        let mut buffer = LineBuffer::new();
+        let mut vi = ViState::new(&mut buffer);
+        type_keys(&mut vi, &mut buffer, "ls -la\u{1b}u");
+        assert_eq!(buffer.as_str(), "");
+    }
+
+    #[test]
+    fn test_repeat_last_change() {
+        let (mut vi, mut buffer) = normal_mode("a b c d e", 0);
+        type_keys(&mut vi, &mut buffer, "dw..");
+        assert_eq!(buffer.as_str(), "d e");
+        type_keys(&mut vi, &mut buffer, "x");
+        type_keys(&mut vi, &mut buffer, "2.");
+        assert_eq!(buffer.as_str(), "");
+    }
+
+    #[test]
+    fn test_repeat_change_with_inserted_text() {
+        let (mut vi, mut buffer) = normal_mode("foo foo", 0);
+        type_keys(&mut vi, &mut buffer, "cwbar\u{1b}w.");
+        assert_eq!(buffer.as_str(), "bar bar");
+    }
+
+    #[test]
+    fn test_repeat_without_change_beeps() {
+        let (mut vi, mut buffer) = normal_mode("echo", 0);
+        assert_eq!(type_keys(&mut vi, &mut buffer, "."), ViOutcome::Beep);
+    }
+
+    #[test]
+    fn test_pending_and_invalid_commands() {
+        let (mut vi, mut buffer) = normal_mode("echo", 0);
+        assert_eq!(vi.handle_key('2', &mut buffer), ViOutcome::Pending);
+        assert_eq!(vi.handle_key('d', &mut buffer), ViOutcome::Pending);
+        assert_eq!(vi.handle_key('f', &mut buffer), ViOutcome::Pending);
+        assert!(vi.cancel_pending());
+        assert_eq!(vi.handle_key('Z', &mut buffer), ViOutcome::Beep);
+        assert!(!vi.cancel_pending());
+        assert_eq!(buffer.as_str(), "echo");
+    }
+
+    #[test]
+    fn test_history_and_search_outcomes() {
+        let (mut vi, mut buffer) = normal_mode("", 0);
+        assert_eq!(type_keys(&mut vi, &mut buffer, "k"), ViOutcome::HistoryPrevious(1));
+        assert_eq!(type_keys(&mut vi, &mut buffer, "3j"), ViOutcome::HistoryNext(3));
+        assert_eq!(type_keys(&mut vi, &mut buffer, "/"), ViOutcome::SearchHistory(SearchDirection::Backward));
+        assert_eq!(type_keys(&mut vi, &mut buffer, "?"), ViOutcome::SearchHistory(SearchDirection::Forward));
+        assert_eq!(type_keys(&mut vi, &mut buffer, "N"), ViOutcome::RepeatSearch { reverse: true });
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let keys = |text: &str| text.chars().collect::<Vec<char>>();
+        assert_eq!(parse_command(&keys("2d3w")), Parsed::Command(Some(6), Action::Operate(Operator::Delete, Motion::WordForward)));
+        assert_eq!(parse_command(&keys("dd")), Parsed::Command(None, Action::Operate(Operator::Delete, Motion::WholeLine)));
+        assert_eq!(parse_command(&keys("12")), Parsed::Incomplete);
+        assert_eq!(parse_command(&keys("0")), Parsed::Command(None, Action::Move(Motion::LineStart)));
+        assert_eq!(parse_command(&keys("dy")), Parsed::Invalid);
+    }
+
+    #[test]
+    fn test_motions_over_non_ascii_text() {
+        let (mut vi, mut buffer) = normal_mode("echo päivää yö", 0);
+        type_keys(&mut vi, &mut buffer, "w");
+        assert_eq!(buffer.text_after_cursor(), "päivää yö");
+        type_keys(&mut vi, &mut buffer, "dw");
+        assert_eq!(buffer.as_str(), "echo yö");
+        type_keys(&mut vi, &mut buffer, "$");
+        assert_eq!(buffer.text_after_cursor(), "ö");
+    }
+}
@@ -2,10 +2,12 @@ use std::io::{self, Write};
 use std::env;
 use std::panic;
 use std::path::PathBuf;
+use std::process;
 use std::str::FromStr;
-use crate::command::{ParsedCommand, builtin};
+use crate::command::{ParsedCommand, builtin, not_found};
+use crate::command::resolver::{self, Lookup};
 use crate::input::autocompletion::AutoCompletion;
-use crate::input::read_line_with_completion;
+use crate::input::{load_inputrc, prompt, read_line_with_completion, EditedLine, ReadLine};
 use crate::history::History;
 
 mod args;
@@ -13,26 +15,48 @@ mod path;
 mod command;
 mod input;
 mod history;
+mod signals;
 
-fn execute<F>(mut f: F) -> ()
+// Number of consecutive Ctrl-D presses ignored when IGNOREEOF is set without a numeric value
+const DEFAULT_IGNORE_EOF_COUNT: usize = 10;
+
+fn execute<F>(mut f: F) -> i32
 where
-  F: FnMut() -> Result<(), anyhow::Error>,
+  F: FnMut() -> Result<i32, anyhow::Error>,
 {
     match panic::catch_unwind(panic::AssertUnwindSafe(|| {
         f()
     })) {
         Ok(result) => {
-            if let Err(err) = result {
-                eprintln!("{}", err);
+            match result {
+                Ok(status) => status,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    command::EXIT_STATUS_FAILURE
+                }
             }
         },
         Err(err) => {
             println!("{:?}", err);
-            ()
+            command::EXIT_STATUS_FAILURE
         }
     }
 }
 
+/// How many consecutive Ctrl-D presses on an empty line are ignored before the shell exits.
+fn ignore_eof_count(ignore_eof: Option<&str>) -> usize {
+    match ignore_eof {
+        None => 0,
+        Some(value) => value.trim().parse().unwrap_or(DEFAULT_IGNORE_EOF_COUNT),
+    }
+}
+
+fn save_history(history: &mut History) -> Result<(), anyhow::Error> {
+    if let Some(history_file) = &env::var("HISTFILE").ok() {
+        history.write_to_file(&PathBuf::from_str(history_file)?)?;
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), anyhow::Error> {
     let path = path::Path::parse(&env::var("PATH").unwrap_or("".to_owned()))?;
@@ -42,41 +66,161 @@ fn main() -> Result<(), anyhow::Error> {
     }
     let automcomplete_path = path.clone();
     let autocomplete = AutoCompletion::new_with_dynamic_completion(
-        vec!["echo", "cd", "pwd", "exit", "type"],
+        vec!["echo", "cd", "pwd", "exit", "type", "bind", "set", "complete", "compgen", "hash", "command", "builtin", "dirs", "pushd", "popd"],
         Box::new(move |partial: &str| automcomplete_path.find_matching_executables(partial))
     );
+    // Children see the directory the shell is in, even when the inherited PWD is out of date
+    if let Ok(current_directory) = builtin::pwd::current_directory(builtin::pwd::Links::Logical) {
+        env::set_var("PWD", current_directory);
+    }
+    signals::ignore_interrupts();
+    signals::watch_window_size();
+    load_inputrc();
+    let mut ignored_end_of_files = 0;
+    let mut resumed_line: Option<EditedLine> = None;
 
     loop {
-        print!("$ ");
+        print!("{}", prompt());
         io::stdout().flush()?;
-        let input = read_line_with_completion(&autocomplete, &history)?;
-        let parsed_command = ParsedCommand::parse_command(&input)?;
-        if let Some(mut parsed_command) = parsed_command {
-            history.append(&input);
-            if let Err(cmd_name) = path.resolve_piped_commands(&mut parsed_command) {
-                println!("\r{}: command not found", cmd_name.trim());
+        let input = match read_line_with_completion(&autocomplete, &history, resumed_line.take())? {
+            ReadLine::Line(input) => input,
+            ReadLine::Interrupted => {
+                command::set_last_exit_status(command::EXIT_STATUS_INTERRUPTED);
                 continue;
             }
-
-            let command = &parsed_command.command;
-            if parsed_command.piped_command.is_some() {
-                let command = command::ShellCommand::Exec;
-                execute(|| command.run(&parsed_command, &mut history));
-            } else if let Some(builtin_command) = builtin::BUILTIN_COMMANDS.get(command.as_str()) {
-                execute(|| builtin_command.run(&parsed_command, &mut history));
-            } else if let Some(_found_executable) = path.find_command(command.as_str()) {
-                let command = command::ShellCommand::Exec;
-                execute(|| command.run(&parsed_command, &mut history));
-            } else {
-                println!("\r{}: command not found", command.trim());
+            ReadLine::EndOfFile => {
+                if ignored_end_of_files < ignore_eof_count(env::var("IGNOREEOF").ok().as_deref()) {
+                    ignored_end_of_files += 1;
+                    println!("Use \"exit\" to leave the shell.");
+                    continue;
+                }
+                println!("exit");
+                save_history(&mut history)?;
+                process::exit(command::last_exit_status());
+            }
+            ReadLine::BoundCommand(command_line, line) => {
+                resumed_line = Some(run_bound_command(&command_line, line, &path, &mut history)?);
+                continue;
+            }
+        };
+        ignored_end_of_files = 0;
+        // A pasted snippet runs command by command
+        for command_line in ParsedCommand::split_lines(&input) {
+            let parsed_command = ParsedCommand::parse_command(&command_line)?;
+            if let Some(parsed_command) = parsed_command {
+                history.append_in_directory(&command_line, env::current_dir().ok());
+                run_parsed_command(parsed_command, &path, &mut history);
             }
         }
     }
 }
 
+/// Runs a command line and records its exit status in `$?`.
+fn run_parsed_command(mut parsed_command: ParsedCommand, path: &path::Path, history: &mut History) {
+    if let Err(cmd_name) = path.resolve_piped_commands(&mut parsed_command) {
+        print!("\r{}", not_found::message(cmd_name.trim(), &not_found::suggestions(cmd_name.trim(), path)));
+        command::set_last_exit_status(command::EXIT_STATUS_NOT_FOUND);
+        return;
+    }
+
+    let status = if parsed_command.piped_command.is_some() {
+        let command = command::ShellCommand::Exec;
+        execute(|| command.run(&parsed_command, history))
+    } else if let Some(resolution) = resolver::resolve(&parsed_command.command, path, Lookup::RUN) {
+        execute(|| resolver::run(&resolution, &parsed_command, history))
+    } else {
+        return handle_command_not_found(parsed_command, path, history);
+    };
+    command::set_last_exit_status(status);
+}
+
+/// With the `correct` option, offers to run the closest command instead; otherwise runs `command_not_found_handle`
+/// or reports the command with the close matches.
+fn handle_command_not_found(mut parsed_command: ParsedCommand, path: &path::Path, history: &mut History) {
+    let name = parsed_command.command.trim().to_string();
+    let suggestions = not_found::suggestions(&name, path);
+    if let Some(suggestion) = suggestions.first().filter(|_| not_found::is_correct_enabled()) {
+        if not_found::confirm_correction(&mut io::stdin().lock(), &mut io::stdout(), suggestion).unwrap_or(false) {
+            parsed_command.command = suggestion.clone();
+            return run_parsed_command(parsed_command, path, history);
+        }
+    }
+    let status = not_found::run_handler(&parsed_command, path).unwrap_or_else(|| {
+        print!("\r{}", not_found::message(&name, &suggestions));
+        command::EXIT_STATUS_NOT_FOUND
+    });
+    command::set_last_exit_status(status);
+}
+
+/// Runs the command of a `bind -x` binding with the edited line in READLINE_LINE and the cursor
+/// position, counted in characters, in READLINE_POINT; the line is resumed from their values afterwards.
+fn run_bound_command(command_line: &str, line: EditedLine, path: &path::Path, history: &mut History) -> Result<EditedLine, anyhow::Error> {
+    env::set_var("READLINE_LINE", &line.text);
+    env::set_var("READLINE_POINT", line.text[..line.cursor].chars().count().to_string());
+    // A binding that does not parse is reported like a typed line; the edited line is kept either way
+    match ParsedCommand::parse_command(command_line) {
+        Ok(Some(parsed_command)) => run_parsed_command(parsed_command, path, history),
+        Ok(None) => (),
+        Err(err) => {
+            eprintln!("{}", err);
+            command::set_last_exit_status(command::EXIT_STATUS_FAILURE);
+        }
+    }
+    let text = env::var("READLINE_LINE").unwrap_or(line.text);
+    let point = env::var("READLINE_POINT").ok().and_then(|point| point.parse().ok()).unwrap_or(0);
+    env::remove_var("READLINE_LINE");
+    env::remove_var("READLINE_POINT");
+    Ok(EditedLine { cursor: char_position_to_byte_index(&text, point), text })
+}
+
+fn char_position_to_byte_index(text: &str, position: usize) -> usize {
+    text.char_indices().nth(position).map(|(idx, _)| idx).unwrap_or(text.len())
+}
+
 //TODO: Add tests
 // Unknown command provided
 // Known command provided
 // Unknown command with arguments provided
 // Known command with arguments provided
 // Known command is provided but its arguments are incomplete or invalid => command execution fails. REPL loop should still continue
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_position_to_byte_index() {
+        assert_eq!(char_position_to_byte_index("echo päivää", 7), 8);
+        assert_eq!(char_position_to_byte_index("echo", 2), 2);
+        assert_eq!(char_position_to_byte_index("echo", 10), 4);
+    }
+
+    #[test]
+    fn test_bound_command_that_does_not_parse_keeps_the_line() -> Result<(), anyhow::Error> {
+        let _lock = command::LAST_EXIT_STATUS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let path = path::Path::parse("")?;
+        let line = EditedLine { text: "ls -l".to_string(), cursor: 2 };
+        let resumed = run_bound_command("echo 'unterminated", line, &path, &mut History::new())?;
+        assert_eq!(resumed.text, "ls -l");
+        assert_eq!(resumed.cursor, 2);
+        assert_eq!(command::last_exit_status(), command::EXIT_STATUS_FAILURE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_eof_count_unset() {
+        assert_eq!(ignore_eof_count(None), 0);
+    }
+
+    #[test]
+    fn test_ignore_eof_count_numeric() {
+        assert_eq!(ignore_eof_count(Some("3")), 3);
+        assert_eq!(ignore_eof_count(Some("0")), 0);
+    }
+
+    #[test]
+    fn test_ignore_eof_count_non_numeric_defaults_to_ten() {
+        assert_eq!(ignore_eof_count(Some("")), DEFAULT_IGNORE_EOF_COUNT);
+        assert_eq!(ignore_eof_count(Some("yes")), DEFAULT_IGNORE_EOF_COUNT);
+    }
+}
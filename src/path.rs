@@ -1,8 +1,167 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{CStr, CString, OsStr};
 use std::path;
 use std::fs;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::PermissionsExt;
 use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::SystemTime;
+use lazy_static::lazy_static;
 use crate::command::{self, ParsedCommand};
+use crate::input::matcher::Matcher;
+
+lazy_static! {
+    static ref EXECUTABLE_INDEX: RwLock<ExecutableIndex> = RwLock::new(ExecutableIndex::default());
+    static ref COMMAND_HASH: RwLock<CommandHash> = RwLock::new(CommandHash::default());
+}
+
+/// The executables in the PATH directories by name, each the first one in PATH order, with the modification
+/// times of the directories when read.
+#[derive(Default)]
+struct ExecutableIndex {
+    directories: Vec<(String, Option<SystemTime>)>,
+    commands: BTreeMap<String, PathBuf>,
+}
+
+impl ExecutableIndex {
+    fn build(path: &Path) -> Self {
+        let mut commands = BTreeMap::new();
+        for directory in &path.directories {
+            let Ok(entries) = fs::read_dir(directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if let Ok(name) = entry.file_name().into_string() {
+                    if !commands.contains_key(&name) && path.is_executable(&entry.path()).unwrap_or(false) {
+                        commands.insert(name, entry.path());
+                    }
+                }
+            }
+        }
+        let directories = path.directories.iter()
+            .map(|directory| (directory.clone(), modification_time(directory)))
+            .collect();
+        ExecutableIndex { directories, commands }
+    }
+
+    fn is_current(&self, path: &Path) -> bool {
+        self.directories.len() == path.directories.len()
+            && self.directories.iter().zip(&path.directories)
+                .all(|((directory, modified), current)| directory == current && *modified == modification_time(current))
+    }
+}
+
+/// A command remembered by `hash`: where it was found and how many times it ran since.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct HashedCommand {
+    pub(crate) path: PathBuf,
+    pub(crate) hits: usize,
+}
+
+/// The commands looked up for running, like bash's hash table. It is emptied when the PATH directories change.
+#[derive(Default)]
+struct CommandHash {
+    directories: Vec<String>,
+    commands: BTreeMap<String, HashedCommand>,
+}
+
+impl CommandHash {
+    fn for_path(&mut self, path: &Path) -> &mut BTreeMap<String, HashedCommand> {
+        if self.directories != path.directories {
+            self.directories = path.directories.clone();
+            self.commands.clear();
+        }
+        &mut self.commands
+    }
+}
+
+/// The remembered commands, sorted by name.
+pub(crate) fn hashed_commands(path: &Path) -> Vec<(String, HashedCommand)> {
+    COMMAND_HASH.write()
+        .map(|mut hash| hash.for_path(path).iter().map(|(name, command)| (name.clone(), command.clone())).collect())
+        .unwrap_or_default()
+}
+
+/// Where a remembered command runs from, for commands that are not looked up again before they run.
+pub(crate) fn hashed_command(name: &str) -> Option<PathBuf> {
+    COMMAND_HASH.read().ok()?.commands.get(name).map(|command| command.path.clone())
+}
+
+/// `hash -p`: runs the command from that file from now on.
+pub(crate) fn hash_command(path: &Path, name: &str, executable: PathBuf) -> Result<(), anyhow::Error> {
+    let mut hash = COMMAND_HASH.write()
+        .map_err(|_| anyhow::anyhow!("The hash table is unavailable"))?;
+    hash.for_path(path).insert(name.to_string(), HashedCommand { path: executable, hits: 0 });
+    Ok(())
+}
+
+/// `hash -d`: forgets a command, returning whether it was remembered.
+pub(crate) fn forget_hashed_command(path: &Path, name: &str) -> Result<bool, anyhow::Error> {
+    let mut hash = COMMAND_HASH.write()
+        .map_err(|_| anyhow::anyhow!("The hash table is unavailable"))?;
+    Ok(hash.for_path(path).remove(name).is_some())
+}
+
+/// `hash -r`: forgets all commands.
+pub(crate) fn forget_hashed_commands() -> Result<(), anyhow::Error> {
+    let mut hash = COMMAND_HASH.write()
+        .map_err(|_| anyhow::anyhow!("The hash table is unavailable"))?;
+    hash.commands.clear();
+    Ok(())
+}
+
+/// The directory a leading `~name` stands for: the home directory of the user, the current and previous
+/// directories for `~+` and `~-`, and entries of the directory stack for `~N`, `~+N` and `~-N`.
+pub(crate) fn tilde_directory(name: &str) -> Option<PathBuf> {
+    let position = name.strip_prefix(['+', '-']).unwrap_or(name);
+    match name {
+        "+" => env::var("PWD").ok().map(PathBuf::from),
+        "-" => env::var("OLDPWD").ok().map(PathBuf::from),
+        _ if !position.is_empty() && position.chars().all(|ch| ch.is_ascii_digit()) => command::builtin::dirs::entry(name),
+        _ => home_directory(name),
+    }
+}
+
+/// Home directory of the user named in `~user`, or of the current user for a plain `~`.
+pub(crate) fn home_directory(user: &str) -> Option<PathBuf> {
+    if user.is_empty() {
+        if let Ok(home) = env::var("HOME") {
+            return Some(PathBuf::from(home));
+        }
+    }
+    let name = CString::new(user).ok()?;
+    let entry = unsafe {
+        if user.is_empty() { libc::getpwuid(libc::getuid()) } else { libc::getpwnam(name.as_ptr()) }
+    };
+    if entry.is_null() {
+        return None;
+    }
+    let directory = unsafe { CStr::from_ptr((*entry).pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(directory.to_bytes())))
+}
+
+/// Names of the users in the password database, for completing `~user`.
+pub(crate) fn user_names() -> Vec<String> {
+    let mut names = Vec::new();
+    unsafe {
+        libc::setpwent();
+        loop {
+            let entry = libc::getpwent();
+            if entry.is_null() {
+                break;
+            }
+            names.push(CStr::from_ptr((*entry).pw_name).to_string_lossy().into_owned());
+        }
+        libc::endpwent();
+    }
+    names
+}
+
+fn modification_time(directory: &str) -> Option<SystemTime> {
+    fs::metadata(directory).and_then(|metadata| metadata.modified()).ok()
+}
 
 #[derive(Debug, Clone)]
 pub(crate) struct Path {
@@ -33,37 +192,100 @@ impl Path {
         Ok(())
     }
 
+    /// Whether one of the directories has an executable of that name. The answer comes from an index that is
+    /// only read again when a directory changes, so that it is cheap enough to ask on every keystroke.
+    pub(crate) fn has_executable(&self, name: &str) -> bool {
+        self.with_index(|index| index.commands.contains_key(name))
+    }
+
+    /// Where the command is found in the directories, without remembering it.
+    pub(crate) fn locate(&self, name: &str) -> Option<PathBuf> {
+        self.with_index(|index| index.commands.get(name).cloned())
+    }
+
+    /// The names of all executables in the directories.
+    pub(crate) fn executable_names(&self) -> Vec<String> {
+        self.with_index(|index| index.commands.keys().cloned().collect())
+    }
+
+    fn with_index<T>(&self, f: impl FnOnce(&ExecutableIndex) -> T) -> T {
+        if let Ok(index) = EXECUTABLE_INDEX.read() {
+            if index.is_current(self) {
+                return f(&index);
+            }
+        }
+        let index = ExecutableIndex::build(self);
+        let result = f(&index);
+        if let Ok(mut current) = EXECUTABLE_INDEX.write() {
+            *current = index;
+        }
+        result
+    }
+
+    pub(crate) fn is_executable_file(&self, path: &path::Path) -> bool {
+        self.is_executable(&path.to_path_buf()).unwrap_or(false)
+    }
+
+    /// Finds a command to run it. Commands found once are remembered, like in bash, and run from the same
+    /// file as long as it is there.
     pub(crate) fn find_command(&self, command_name: &str) -> Option<String> {
-        self.find_executable_path(command_name)
+        if command_name.contains('/') {
+            return self.find_executable_path(command_name);
+        }
+        let mut hash = COMMAND_HASH.write().ok()?;
+        self.find_hashed_command(&mut hash, command_name)
     }
 
-    pub(crate) fn find_matching_executables(&self, partial: &str) -> Vec<String> {
-        if partial.is_empty() {
-            return Vec::new();
+    fn find_hashed_command(&self, hash: &mut CommandHash, command_name: &str) -> Option<String> {
+        let commands = hash.for_path(self);
+        if let Some(command) = commands.get_mut(command_name).filter(|command| self.is_executable(&command.path).unwrap_or(false)) {
+            command.hits += 1;
+            return command.path.to_str().map(|path| path.to_string());
         }
+        let executable = self.locate(command_name)?;
+        commands.insert(command_name.to_string(), HashedCommand { path: executable.clone(), hits: 1 });
+        executable.to_str().map(|path| path.to_string())
+    }
 
-        let mut matches = Vec::new();
-        let mut seen = std::collections::HashSet::new();
+    /// Where a remembered command runs from, without counting it as run.
+    pub(crate) fn hashed_path(&self, command_name: &str) -> Option<PathBuf> {
+        let mut hash = COMMAND_HASH.write().ok()?;
+        hash.for_path(self).get(command_name)
+            .map(|command| command.path.clone())
+            .filter(|path| self.is_executable(path).unwrap_or(false))
+    }
 
+    /// Every executable of that name in the directories, in PATH order.
+    pub(crate) fn locate_all(&self, command_name: &str) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Vec::new();
         for directory in &self.directories {
-            let dir_path = path::Path::new(directory);
-            if let Ok(entries) = fs::read_dir(dir_path) {
-                for entry in entries.flatten() {
-                    if let Ok(file_name) = entry.file_name().into_string() {
-                        if file_name.starts_with(partial) {
-                            let full_path = entry.path();
-                            if self.is_executable(&full_path).unwrap_or(false) {
-                                if seen.insert(file_name.clone()) {
-                                    matches.push(file_name);
-                                }
-                            }
-                        }
-                    }
-                }
+            let candidate = path::Path::new(directory).join(command_name);
+            if self.is_executable(&candidate).unwrap_or(false) && !paths.contains(&candidate) {
+                paths.push(candidate);
             }
         }
-        matches.sort();
-        matches
+        paths
+    }
+
+    /// `hash name`: looks the command up and remembers it without counting it as run.
+    pub(crate) fn remember(&self, command_name: &str) -> Result<bool, anyhow::Error> {
+        let Some(executable) = self.locate(command_name) else {
+            return Ok(false);
+        };
+        hash_command(self, command_name, executable)?;
+        Ok(true)
+    }
+
+    /// The executable names that match the typed text, served from the index of the directories.
+    pub(crate) fn find_matching_executables(&self, partial: &str) -> Vec<String> {
+        if partial.is_empty() {
+            return Vec::new();
+        }
+        let matcher = Matcher::current();
+        self.with_index(|index| index.commands.keys()
+            .filter(|name| matcher.matches(partial, name))
+            .cloned()
+            .collect())
     }
 
     fn find_executable_path(&self, command_name: &str) -> Option<String> {
@@ -96,6 +318,16 @@ mod tests {
     use std::io::Write;
     use std::os::unix::fs::PermissionsExt;
 
+    // Root's home from the password database, which is not /root everywhere
+    fn root_home() -> Option<PathBuf> {
+        let entry = unsafe { libc::getpwnam(c"root".as_ptr()) };
+        if entry.is_null() {
+            return None;
+        }
+        let directory = unsafe { CStr::from_ptr((*entry).pw_dir) };
+        Some(PathBuf::from(OsStr::from_bytes(directory.to_bytes())))
+    }
+
     fn create_test_directory() -> tempfile::TempDir {
         tempfile::tempdir().expect("Failed to create temp directory")
     }
@@ -443,4 +675,96 @@ mod tests {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), "nonexistent");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_has_executable_notices_new_executables() {
+        let temp_dir = create_test_directory();
+        create_executable_file(temp_dir.path(), "cat");
+        create_non_executable_file(temp_dir.path(), "notes");
+
+        let path = Path {
+            directories: vec![temp_dir.path().to_str().unwrap().to_string()]
+        };
+
+        assert!(path.has_executable("cat"));
+        assert!(!path.has_executable("notes"));
+        assert!(!path.has_executable("ls"));
+        create_executable_file(temp_dir.path(), "ls");
+        assert!(path.has_executable("ls"));
+    }
+
+    #[test]
+    fn test_found_commands_are_remembered() {
+        let temp_dir = create_test_directory();
+        let other_dir = create_test_directory();
+        let cat = create_executable_file(temp_dir.path(), "cat");
+        let path = Path {
+            directories: vec![temp_dir.path().to_str().unwrap().to_string(), other_dir.path().to_str().unwrap().to_string()]
+        };
+        let mut hash = CommandHash::default();
+
+        assert_eq!(path.find_hashed_command(&mut hash, "cat"), cat.to_str().map(|cat| cat.to_string()));
+        path.find_hashed_command(&mut hash, "cat");
+        assert_eq!(hash.commands.get("cat"), Some(&HashedCommand { path: cat.clone(), hits: 2 }));
+        assert_eq!(path.find_hashed_command(&mut hash, "missing"), None);
+
+        // A removed file is looked up again
+        fs::remove_file(&cat).unwrap();
+        let other_cat = create_executable_file(other_dir.path(), "cat");
+        assert_eq!(path.find_hashed_command(&mut hash, "cat"), other_cat.to_str().map(|cat| cat.to_string()));
+        assert_eq!(hash.commands.get("cat").map(|command| command.hits), Some(1));
+    }
+
+    #[test]
+    fn test_hash_is_emptied_when_directories_change() {
+        let temp_dir = create_test_directory();
+        create_executable_file(temp_dir.path(), "cat");
+        let path = Path { directories: vec![temp_dir.path().to_str().unwrap().to_string()] };
+        let mut hash = CommandHash::default();
+        path.find_hashed_command(&mut hash, "cat");
+        assert_eq!(hash.commands.len(), 1);
+        assert!(hash.for_path(&Path { directories: vec!["/nonexistent".to_string()] }).is_empty());
+    }
+
+    #[test]
+    fn test_locate_prefers_earlier_directories() {
+        let first_dir = create_test_directory();
+        let second_dir = create_test_directory();
+        create_executable_file(second_dir.path(), "tool");
+        let first_tool = create_executable_file(first_dir.path(), "tool");
+        let path = Path {
+            directories: vec![first_dir.path().to_str().unwrap().to_string(), second_dir.path().to_str().unwrap().to_string()]
+        };
+        assert_eq!(path.locate("tool"), Some(first_tool));
+    }
+
+    #[test]
+    fn test_locate_all_lists_every_directory() {
+        let first_dir = create_test_directory();
+        let second_dir = create_test_directory();
+        let second_tool = create_executable_file(second_dir.path(), "tool");
+        let first_tool = create_executable_file(first_dir.path(), "tool");
+        let path = Path {
+            directories: vec![first_dir.path().to_str().unwrap().to_string(), second_dir.path().to_str().unwrap().to_string()]
+        };
+        assert_eq!(path.locate_all("tool"), vec![first_tool, second_tool]);
+        assert!(path.locate_all("missing").is_empty());
+    }
+
+    #[test]
+    fn test_tilde_directory() {
+        assert_eq!(tilde_directory("root"), root_home());
+        let current_directory = command::builtin::pwd::current_directory(command::builtin::pwd::Links::Logical).ok();
+        assert_eq!(tilde_directory("+0"), current_directory);
+        assert_eq!(tilde_directory("0"), tilde_directory("+0"));
+        assert_eq!(tilde_directory("+99"), None);
+    }
+
+    #[test]
+    fn test_home_directory_of_user() {
+        assert!(root_home().is_some());
+        assert_eq!(home_directory("root"), root_home());
+        assert!(user_names().iter().any(|name| name == "root"));
+        assert_eq!(home_directory("no-such-user-here"), None);
+    }
+}
@@ -0,0 +1,32 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Set by the SIGWINCH handler until the line editor redraws for the new terminal size
+static WINDOW_RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn ignore_signal(_signal: libc::c_int) {}
+
+/// Keeps the shell alive when Ctrl-C is pressed while a command runs in the foreground.
+///
+/// A no-op handler is installed instead of `SIG_IGN` because handlers are reset to the default
+/// action on `exec`, so that the interrupted child processes still terminate.
+pub(crate) fn ignore_interrupts() {
+    unsafe {
+        libc::signal(libc::SIGINT, ignore_signal as *const () as libc::sighandler_t);
+    }
+}
+
+extern "C" fn record_window_resize(_signal: libc::c_int) {
+    WINDOW_RESIZED.store(true, Ordering::Relaxed);
+}
+
+/// Records terminal resizes so that the line being edited can be drawn again for the new width.
+pub(crate) fn watch_window_size() {
+    unsafe {
+        libc::signal(libc::SIGWINCH, record_window_resize as *const () as libc::sighandler_t);
+    }
+}
+
+/// Whether the terminal was resized since the last call.
+pub(crate) fn take_window_resize() -> bool {
+    WINDOW_RESIZED.swap(false, Ordering::Relaxed)
+}